@@ -0,0 +1,92 @@
+# Benachrichtigungen
+
+typing-countdown = Ergebnis wird getippt
+typing-countdown-remaining = in { $seconds } s…
+generating = Wird erzeugt…
+
+focus-changed = Fokus gewechselt
+result-copied = Das Ergebnis wurde in die Zwischenablage kopiert
+
+password-field = Passwortfeld
+password-field-detail = Das Ergebnis wurde nicht in ein Passwortfeld getippt
+
+window-blocked = Fenster gesperrt
+window-blocked-detail = In Fenstern, auf die { $entry } passt, wird nichts gelesen oder getippt
+
+screen-shared = Bildschirm wird geteilt
+
+reply-drafted = Antwort entworfen
+reply-copied = Die Antwort wurde in die Zwischenablage kopiert
+
+models-compared = Modelle verglichen
+models-compared-copied =
+    { $count ->
+        [one] { $count } Ergebnis wurde in die Zwischenablage kopiert
+       *[other] { $count } Ergebnisse wurden in die Zwischenablage kopiert
+    }
+
+readability = Lesbarkeit
+
+facts-changed = Fakten geändert
+facts-changed-check = Bitte prüfen: { $list }
+
+paused = Pausiert
+paused-detail = Tastenkürzel sind deaktiviert, bis sie fortgesetzt werden
+resumed = Fortgesetzt
+resumed-detail = Tastenkürzel sind aktiviert
+
+model = Modell
+
+busy = Die vorherige Anfrage läuft noch
+busy-already-queued = Dieses Tastenkürzel ist bereits in der Warteschlange
+busy-queue-full = Warteschlange voll, Tastendruck ignoriert
+busy-queued = In der Warteschlange, bis die vorherige fertig ist
+
+cancel = Abbrechen
+command-confirm = Diesen Befehl ausführen?
+command-run = Ausführen
+command-succeeded = Befehl erfolgreich
+command-failed = Befehl fehlgeschlagen (Exit-Code { $code })
+command-killed = Befehl wurde beendet
+command-timed-out = Befehl nach { $seconds } s abgebrochen
+command-no-output = Keine Ausgabe
+command-dangerous = ⚠ Möglicherweise destruktiv: { $reasons }
+command-not-typed = Befehl nicht getippt
+command-invalid-reply = Die Antwort des Modells war nicht das erwartete JSON. Drücke den Hotkey erneut, um es nochmal zu versuchen.
+command-copied = Er wurde stattdessen in die Zwischenablage kopiert.
+command-added-to-history = Zum { $shell }-Verlauf hinzugefügt
+
+session-reset = Sitzung zurückgesetzt
+session-reset-detail = Frühere Verbesserungen werden nicht mehr als Kontext verwendet
+
+profile = Profil
+
+listening = Höre zu…
+listening-detail = Sag, wie die Auswahl geändert werden soll ({ $seconds } s)
+
+result-rejected = Verworfen
+nothing-pending = Nichts wartet auf Bestätigung
+
+result = Ergebnis
+
+request-timed-out = Zeitüberschreitung
+request-timed-out-detail = Keine Antwort innerhalb von { $seconds } s. Drücke den Hotkey erneut, um es nochmal zu versuchen.
+
+model-evicted = Modell entladen
+model-evicted-detail = { $model } ist nicht mehr geladen, die nächste Anfrage wartet auf das Laden.
+model-on-cpu = Modell läuft auf der CPU
+model-on-cpu-detail = { $model } liegt zu { $gpu } % auf der GPU, Antworten werden langsam. Gib GPU-Speicher frei oder wähle ein kleineres Modell.
+
+same-selection = Gleiche Auswahl wie zuletzt
+same-selection-detail = Drücke den Hotkey innerhalb von { $seconds } s erneut, um sie trotzdem zu senden.
+
+menu-not-running = improve-writing läuft nicht
+menu-paused = Pausiert
+menu-model = Modell: { $model }
+menu-recent = Letzte Ergebnisse
+menu-no-recent = Nichts aufgezeichnet (mit --history starten)
+menu-quit = Menüleistensymbol beenden
+
+# Fehlerhinweise
+
+hint-missing-tool = ist { $tool } installiert?
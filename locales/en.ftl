@@ -0,0 +1,92 @@
+# Notifications
+
+typing-countdown = Typing result
+typing-countdown-remaining = in { $seconds } s…
+generating = Generating…
+
+focus-changed = Focus changed
+result-copied = The result was copied to the clipboard
+
+password-field = Password field
+password-field-detail = The result was not typed into a password field
+
+window-blocked = Window blocked
+window-blocked-detail = Nothing is read from or typed into windows matching { $entry }
+
+screen-shared = Screen shared
+
+reply-drafted = Reply drafted
+reply-copied = The reply was copied to the clipboard
+
+models-compared = Models compared
+models-compared-copied =
+    { $count ->
+        [one] { $count } result was copied to the clipboard
+       *[other] { $count } results were copied to the clipboard
+    }
+
+readability = Readability
+
+facts-changed = Facts changed
+facts-changed-check = Check these: { $list }
+
+paused = Paused
+paused-detail = Hotkeys are disabled until resumed
+resumed = Resumed
+resumed-detail = Hotkeys are enabled
+
+model = Model
+
+busy = Still working on the previous one
+busy-already-queued = The same hotkey is already queued
+busy-queue-full = Queue is full, press ignored
+busy-queued = Queued until it's done
+
+cancel = Cancel
+command-confirm = Run this command?
+command-run = Run
+command-succeeded = Command succeeded
+command-failed = Command failed (exit code { $code })
+command-killed = Command was killed
+command-timed-out = Command killed after { $seconds } s
+command-no-output = No output
+command-dangerous = ⚠ Possibly destructive: { $reasons }
+command-not-typed = Command not typed
+command-invalid-reply = The model's reply was not the expected JSON. Press the hotkey again to retry.
+command-copied = It was copied to the clipboard instead.
+command-added-to-history = Added to { $shell } history
+
+session-reset = Session reset
+session-reset-detail = Earlier improvements are no longer used as context
+
+profile = Profile
+
+listening = Listening…
+listening-detail = Say how to change the selection ({ $seconds } s)
+
+result-rejected = Rejected
+nothing-pending = Nothing waiting to be accepted
+
+result = Result
+
+request-timed-out = Request timed out
+request-timed-out-detail = No response within { $seconds } s. Press the hotkey again to retry.
+
+model-evicted = Model unloaded
+model-evicted-detail = { $model } is no longer loaded, so the next request waits for it to load.
+model-on-cpu = Model running on the CPU
+model-on-cpu-detail = { $model } has { $gpu }% on the GPU, so responses will be slow. Free GPU memory or pick a smaller model.
+
+same-selection = Same selection as last time
+same-selection-detail = Press the hotkey again within { $seconds } s to send it anyway.
+
+menu-not-running = improve-writing is not running
+menu-paused = Paused
+menu-model = Model: { $model }
+menu-recent = Recent results
+menu-no-recent = Nothing recorded (run with --history)
+menu-quit = Quit menu bar item
+
+# Error hints
+
+hint-missing-tool = is { $tool } installed?
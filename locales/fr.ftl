@@ -0,0 +1,92 @@
+# Notifications
+
+typing-countdown = Saisie du résultat
+typing-countdown-remaining = dans { $seconds } s…
+generating = Génération…
+
+focus-changed = Fenêtre changée
+result-copied = Le résultat a été copié dans le presse-papiers
+
+password-field = Champ de mot de passe
+password-field-detail = Le résultat n'a pas été tapé dans un champ de mot de passe
+
+window-blocked = Fenêtre bloquée
+window-blocked-detail = Rien n'est lu ni tapé dans les fenêtres correspondant à { $entry }
+
+screen-shared = Écran partagé
+
+reply-drafted = Réponse rédigée
+reply-copied = La réponse a été copiée dans le presse-papiers
+
+models-compared = Modèles comparés
+models-compared-copied =
+    { $count ->
+        [one] { $count } résultat a été copié dans le presse-papiers
+       *[other] { $count } résultats ont été copiés dans le presse-papiers
+    }
+
+readability = Lisibilité
+
+facts-changed = Faits modifiés
+facts-changed-check = À vérifier : { $list }
+
+paused = En pause
+paused-detail = Les raccourcis sont désactivés jusqu'à la reprise
+resumed = Repris
+resumed-detail = Les raccourcis sont activés
+
+model = Modèle
+
+busy = La requête précédente est toujours en cours
+busy-already-queued = Ce raccourci est déjà en file d'attente
+busy-queue-full = File d'attente pleine, appui ignoré
+busy-queued = En attente de la fin de la précédente
+
+cancel = Annuler
+command-confirm = Exécuter cette commande ?
+command-run = Exécuter
+command-succeeded = Commande réussie
+command-failed = Échec de la commande (code de sortie { $code })
+command-killed = La commande a été tuée
+command-timed-out = Commande tuée après { $seconds } s
+command-no-output = Aucune sortie
+command-dangerous = ⚠ Potentiellement destructeur : { $reasons }
+command-not-typed = Commande non saisie
+command-invalid-reply = La réponse du modèle n'était pas le JSON attendu. Appuyez à nouveau sur le raccourci pour réessayer.
+command-copied = Elle a été copiée dans le presse-papiers à la place.
+command-added-to-history = Ajoutée à l'historique de { $shell }
+
+session-reset = Session réinitialisée
+session-reset-detail = Les améliorations précédentes ne servent plus de contexte
+
+profile = Profil
+
+listening = Écoute…
+listening-detail = Dites comment modifier la sélection ({ $seconds } s)
+
+result-rejected = Rejeté
+nothing-pending = Rien en attente de validation
+
+result = Résultat
+
+request-timed-out = Délai dépassé
+request-timed-out-detail = Aucune réponse en { $seconds } s. Appuyez à nouveau sur le raccourci pour réessayer.
+
+model-evicted = Modèle déchargé
+model-evicted-detail = { $model } n'est plus chargé, la prochaine requête attendra son chargement.
+model-on-cpu = Modèle exécuté sur le CPU
+model-on-cpu-detail = { $model } est à { $gpu } % sur le GPU, les réponses seront lentes. Libérez de la mémoire GPU ou choisissez un modèle plus petit.
+
+same-selection = Même sélection que la dernière fois
+same-selection-detail = Appuyez à nouveau sur le raccourci dans les { $seconds } s pour l'envoyer quand même.
+
+menu-not-running = improve-writing n'est pas lancé
+menu-paused = En pause
+menu-model = Modèle : { $model }
+menu-recent = Résultats récents
+menu-no-recent = Rien enregistré (lancer avec --history)
+menu-quit = Quitter l'icône de la barre des menus
+
+# Indications d'erreur
+
+hint-missing-tool = { $tool } est-il installé ?
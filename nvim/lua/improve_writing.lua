@@ -0,0 +1,71 @@
+-- Improve the visual selection in place through a running improve-writing
+-- daemon started with --nvim. See docs/protocol.md for the request schema.
+local M = {}
+
+-- Same fallback as the daemon: the temp directory
+local runtime_dir = vim.env.XDG_RUNTIME_DIR or (vim.env.TMPDIR or '/tmp'):gsub('/$', '')
+
+M.config = {
+  socket = runtime_dir .. '/improve-writing.nvim.sock',
+}
+
+local channel
+
+local function request(method, params)
+  if not channel then
+    local ok, chan = pcall(vim.fn.sockconnect, 'pipe', M.config.socket, { rpc = true })
+    if not ok or chan == 0 then
+      error('cannot connect to ' .. M.config.socket .. ' (is improve-writing running with --nvim?)', 0)
+    end
+    channel = chan
+  end
+  local ok, result = pcall(vim.rpcrequest, channel, method, params)
+  if not ok then
+    -- The daemon may have restarted; reconnect on the next call
+    pcall(vim.fn.chanclose, channel)
+    channel = nil
+    error(result, 0)
+  end
+  return result
+end
+
+--- Range of the last visual selection as 0-based, end-exclusive
+--- (start_row, start_col, end_row, end_col) for nvim_buf_get_text.
+local function selection()
+  local mode = vim.fn.visualmode()
+  local start = vim.api.nvim_buf_get_mark(0, '<')
+  local finish = vim.api.nvim_buf_get_mark(0, '>')
+  local sr, sc, er, ec = start[1] - 1, start[2], finish[1] - 1, finish[2]
+  local last = vim.api.nvim_buf_get_lines(0, er, er + 1, true)[1]
+  if mode == 'V' then
+    return sr, 0, er, #last
+  elseif mode == 'v' then
+    -- The end mark is on the first byte of the last character
+    return sr, sc, er, math.min(ec + vim.str_utf_end(last, ec + 1) + 1, #last)
+  end
+  return nil
+end
+
+--- Improve the last visual selection. With `refine`, refine the previous
+--- result instead of starting over.
+function M.improve(refine)
+  local sr, sc, er, ec = selection()
+  if not sr then
+    vim.notify('improve-writing: select text with v or V', vim.log.levels.WARN)
+    return
+  end
+  local text = table.concat(vim.api.nvim_buf_get_text(0, sr, sc, er, ec, {}), '\n')
+
+  local ok, result = pcall(request, 'improve', { text = text, refine = refine or false })
+  if not ok then
+    vim.notify('improve-writing: ' .. tostring(result), vim.log.levels.ERROR)
+    return
+  end
+  vim.api.nvim_buf_set_text(0, sr, sc, er, ec, vim.split(result.text, '\n', { plain = true }))
+end
+
+function M.setup(opts)
+  M.config = vim.tbl_extend('force', M.config, opts or {})
+end
+
+return M
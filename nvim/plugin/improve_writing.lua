@@ -0,0 +1,9 @@
+if vim.g.loaded_improve_writing then
+  return
+end
+vim.g.loaded_improve_writing = true
+
+-- :'<,'>ImproveWriting improves the selection; with ! it refines the last result
+vim.api.nvim_create_user_command('ImproveWriting', function(opts)
+  require('improve_writing').improve(opts.bang)
+end, { range = true, bang = true, desc = 'Improve the visual selection with improve-writing' })
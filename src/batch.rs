@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::chunk;
+use crate::ollama::TextImprover;
+use crate::pipeline::Pipeline;
+
+/// Files are improved a chunk of paragraphs at a time, so long documents fit
+/// in the context window and progress can be shown.
+const CHUNK_CHARS: usize = 2_000;
+
+/// Improve whole files, chunk by chunk, through the pipeline if one is set.
+/// Up to `parallelism` chunks are sent at once. Results replace the files
+/// when `in_place`, and otherwise go next to them with `suffix` before the
+/// extension (`notes.md` -> `notes.improved.md`). A file that fails is
+/// reported and skipped.
+pub async fn run(
+    improver: &TextImprover,
+    pipeline: Option<&Pipeline>,
+    parallelism: usize,
+    paths: &[PathBuf],
+    in_place: bool,
+    suffix: &str,
+) -> Result<()> {
+    let mut failed = 0;
+    for (i, path) in paths.iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, paths.len(), path.display());
+        let target = if in_place {
+            path.clone()
+        } else {
+            with_suffix(path, suffix)
+        };
+        match improve_file(improver, pipeline, parallelism, path, &target).await {
+            Ok(()) => println!("  wrote {}", target.display()),
+            Err(e) => {
+                tracing::error!("Failed to improve {}: {:#}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} files failed", failed, paths.len());
+    }
+    Ok(())
+}
+
+async fn improve_file(
+    improver: &TextImprover,
+    pipeline: Option<&Pipeline>,
+    parallelism: usize,
+    path: &Path,
+    target: &Path,
+) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let chunks = chunk::split(&text, CHUNK_CHARS);
+    let count = chunks.len();
+
+    // Each chunk gets its own copy of the improver, so chunks can be in
+    // flight at once; results still come back in order
+    let base = improver.fork();
+    let pipeline = pipeline.cloned();
+    let mut results = chunk::ordered(chunks, parallelism, move |chunk: String| {
+        let mut improver = base.fork();
+        let pipeline = pipeline.clone();
+        async move {
+            let result = match &pipeline {
+                Some(pipeline) => pipeline.run(&mut improver, &chunk, false).await,
+                None => improver.improve(&chunk, false).await,
+            };
+            (chunk, result)
+        }
+    });
+
+    let mut improved = Vec::new();
+    while let Some((chunk, result)) = results.recv().await {
+        let i = improved.len();
+        print!("\r  chunk {}/{}", i + 1, count);
+        let _ = std::io::stdout().flush();
+        improved.push(match result {
+            Ok(result) if !result.trim().is_empty() => result,
+            Ok(_) => {
+                tracing::warn!("Ollama returned empty response, keeping chunk {}", i + 1);
+                chunk
+            }
+            Err(e) => {
+                println!();
+                return Err(e);
+            }
+        });
+    }
+    println!();
+    if improved.len() < count {
+        anyhow::bail!("Chunk {} of {} was lost", improved.len() + 1, count);
+    }
+
+    let mut output = improved.join("\n\n");
+    if text.ends_with('\n') {
+        output.push('\n');
+    }
+    std::fs::write(target, output).with_context(|| format!("Failed to write {}", target.display()))
+}
+
+/// Files the watch mode picks up.
+const WATCH_EXTENSIONS: [&str; 2] = ["txt", "md"];
+
+/// A file must have been left alone this long before it is picked up, so
+/// half-synced files aren't improved.
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Longest wait before a file that keeps failing is tried again.
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// A watched file that failed to improve, and when to try it again.
+struct Failure {
+    attempts: u32,
+    retry_at: Instant,
+    /// The file's modification time when it failed; editing it retries at
+    /// once
+    modified: Option<SystemTime>,
+}
+
+impl Failure {
+    fn due(&self, path: &Path) -> bool {
+        Instant::now() >= self.retry_at || modified(path) != self.modified
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+/// Poll `dir` every `interval` and improve each `.txt`/`.md` file whose
+/// result (with `suffix`, next to it) is missing or older than the file.
+/// A file that fails is retried with exponential backoff, or as soon as it
+/// changes; its result is never written. Runs until interrupted.
+pub async fn watch(
+    improver: &TextImprover,
+    pipeline: Option<&Pipeline>,
+    parallelism: usize,
+    dir: &Path,
+    suffix: &str,
+    interval: Duration,
+) -> Result<()> {
+    tracing::info!("Watching {} for .txt and .md files", dir.display());
+    let mut failures: HashMap<PathBuf, Failure> = HashMap::new();
+    loop {
+        for path in pending(dir, suffix)? {
+            if failures
+                .get(&path)
+                .is_some_and(|failure| !failure.due(&path))
+            {
+                continue;
+            }
+            let target = with_suffix(&path, suffix);
+            tracing::info!("Improving {}", path.display());
+            match improve_file(improver, pipeline, parallelism, &path, &target).await {
+                Ok(()) => {
+                    failures.remove(&path);
+                    tracing::info!("Wrote {}", target.display());
+                }
+                Err(e) => {
+                    let attempts = failures.get(&path).map_or(1, |f| f.attempts + 1);
+                    let backoff = interval
+                        .saturating_mul(1 << attempts.min(16))
+                        .min(MAX_BACKOFF);
+                    tracing::error!(
+                        "Failed to improve {} (attempt {}), retrying in {:?}: {:#}",
+                        path.display(),
+                        attempts,
+                        backoff,
+                        e
+                    );
+                    let failure = Failure {
+                        attempts,
+                        retry_at: Instant::now() + backoff,
+                        modified: modified(&path),
+                    };
+                    failures.insert(path, failure);
+                }
+            }
+        }
+        failures.retain(|path, _| path.exists());
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Settled files in `dir` whose result is missing or out of date.
+fn pending(dir: &Path, suffix: &str) -> Result<Vec<PathBuf>> {
+    let now = SystemTime::now();
+    let mut pending = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        let watched = path
+            .extension()
+            .is_some_and(|ext| WATCH_EXTENSIONS.iter().any(|e| ext == *e));
+        let is_result = path
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().ends_with(suffix));
+        if !watched || is_result || !path.is_file() {
+            continue;
+        }
+
+        let Ok(modified) = path.metadata().and_then(|m| m.modified()) else {
+            continue;
+        };
+        if now.duration_since(modified).unwrap_or_default() < SETTLE {
+            continue;
+        }
+        let done = with_suffix(&path, suffix)
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|result| result >= modified);
+        if !done {
+            pending.push(path);
+        }
+    }
+    pending.sort();
+    Ok(pending)
+}
+
+/// `dir/notes.md` with suffix `.improved` -> `dir/notes.improved.md`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}{suffix}.{}", ext.to_string_lossy()),
+        None => format!("{stem}{suffix}"),
+    };
+    path.with_file_name(name)
+}
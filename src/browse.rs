@@ -0,0 +1,202 @@
+use anyhow::Result;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::{Line, Text},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+};
+
+use crate::history::{Entry, local_time};
+use crate::output::copy_to_clipboard;
+
+const HELP: &str = "type to filter · ↑/↓ select · ^O copy original · ^Y copy improved · Esc quit";
+
+/// Browse history entries in the terminal, newest first.
+pub async fn run(entries: Vec<Entry>) -> Result<()> {
+    if entries.is_empty() {
+        println!("History is empty (record it with --history)");
+        return Ok(());
+    }
+
+    let mut terminal = ratatui::init();
+    let result = Browser::new(entries).run(&mut terminal).await;
+    ratatui::restore();
+    result
+}
+
+struct Browser {
+    /// Newest first
+    entries: Vec<Entry>,
+    filter: String,
+    /// Indices of the entries matching the filter
+    matches: Vec<usize>,
+    list: ListState,
+    status: String,
+}
+
+impl Browser {
+    fn new(mut entries: Vec<Entry>) -> Self {
+        entries.reverse();
+        let mut browser = Self {
+            entries,
+            filter: String::new(),
+            matches: Vec::new(),
+            list: ListState::default(),
+            status: HELP.to_string(),
+        };
+        browser.refilter();
+        browser
+    }
+
+    async fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if ctrl => return Ok(()),
+                KeyCode::Char('o') if ctrl => self.copy(false).await,
+                KeyCode::Char('y') if ctrl => self.copy(true).await,
+                KeyCode::Up => self.step(-1),
+                KeyCode::Down => self.step(1),
+                KeyCode::PageUp => self.step(-10),
+                KeyCode::PageDown => self.step(10),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.refilter();
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    self.filter.push(c);
+                    self.refilter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Keep the entries that contain every word of the filter in their
+    /// date, action, app, original, or improved text (ignoring case).
+    fn refilter(&mut self) {
+        let terms: Vec<String> = self
+            .filter
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        self.matches = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                let haystack = format!(
+                    "{} {} {} {} {}",
+                    local_time(entry.time),
+                    entry.action,
+                    entry.app.as_deref().unwrap_or_default(),
+                    entry.original,
+                    entry.improved
+                )
+                .to_lowercase();
+                terms.iter().all(|term| haystack.contains(term))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.list.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn step(&mut self, delta: isize) {
+        let Some(selected) = self.list.selected() else {
+            return;
+        };
+        let last = self.matches.len().saturating_sub(1);
+        self.list
+            .select(Some(selected.saturating_add_signed(delta).min(last)));
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        let i = *self.matches.get(self.list.selected()?)?;
+        self.entries.get(i)
+    }
+
+    async fn copy(&mut self, improved: bool) {
+        let Some(entry) = self.selected() else {
+            return;
+        };
+        let (what, text) = if improved {
+            ("improved", entry.improved.clone())
+        } else {
+            ("original", entry.original.clone())
+        };
+        self.status = match copy_to_clipboard(&text).await {
+            Ok(()) => format!("Copied the {what} text to the clipboard"),
+            Err(e) => format!("Failed to copy: {e}"),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [filter_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main_area);
+
+        let title = format!("Filter ({}/{})", self.matches.len(), self.entries.len());
+        frame.render_widget(
+            Paragraph::new(self.filter.as_str()).block(Block::bordered().title(title)),
+            filter_area,
+        );
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&i| {
+                let entry = &self.entries[i];
+                let summary = entry.original.lines().next().unwrap_or_default();
+                ListItem::new(format!(
+                    "{}  {}  {}",
+                    local_time(entry.time),
+                    entry.action,
+                    summary
+                ))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("History"))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        if let Some(entry) = self.selected() {
+            let bold = Style::new().add_modifier(Modifier::BOLD);
+            let mut lines = vec![Line::styled("Original", bold)];
+            lines.extend(entry.original.lines().map(Line::from));
+            lines.push(Line::default());
+            lines.push(Line::styled("Improved", bold));
+            lines.extend(entry.improved.lines().map(Line::from));
+
+            let title = match &entry.app {
+                Some(app) => format!("{} in {}", entry.action, app),
+                None => entry.action.clone(),
+            };
+            frame.render_widget(
+                Paragraph::new(Text::from(lines))
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title(title)),
+                detail_area,
+            );
+        }
+
+        frame.render_widget(Paragraph::new(self.status.as_str()), status_area);
+    }
+}
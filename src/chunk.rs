@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use tokio::sync::mpsc;
+
+/// Split text into chunks of at most `max_chars` characters, preferring
+/// paragraph boundaries, then sentence boundaries, then word boundaries.
+pub fn split(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for (sep, piece) in pieces(text, max_chars) {
+        if !current.is_empty() && char_len(&current) + sep.len() + char_len(&piece) > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str(sep);
+        }
+        current.push_str(&piece);
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Split text into sentences, keeping terminal punctuation attached.
+pub fn sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        current.push(ch);
+        let at_end =
+            matches!(ch, '.' | '!' | '?') && chars.peek().is_none_or(|next| next.is_whitespace());
+        if at_end {
+            let sentence = current.trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence.to_string());
+            }
+            current.clear();
+        }
+    }
+
+    let rest = current.trim();
+    if !rest.is_empty() {
+        sentences.push(rest.to_string());
+    }
+    sentences
+}
+
+/// Break text into pieces no longer than `max_chars`, each of which is a
+/// paragraph, a sentence, or a run of words. Each piece carries the separator
+/// that joins it to the previous one, so paragraphs survive reassembly.
+fn pieces(text: &str, max_chars: usize) -> Vec<(&'static str, String)> {
+    let mut pieces = Vec::new();
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if char_len(paragraph) <= max_chars {
+            pieces.push(("\n\n", paragraph.to_string()));
+            continue;
+        }
+        let mut sep = "\n\n";
+        for sentence in sentences(paragraph) {
+            let parts = if char_len(&sentence) <= max_chars {
+                vec![sentence]
+            } else {
+                words(&sentence, max_chars)
+            };
+            for part in parts {
+                pieces.push((sep, part));
+                sep = " ";
+            }
+        }
+    }
+
+    pieces
+}
+
+fn words(text: &str, max_chars: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        if !current.is_empty() && char_len(&current) + 1 + char_len(word) > max_chars {
+            out.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Run `f` on each item with up to `parallelism` running at once, receiving
+/// the results strictly in the order of the items, each as soon as it and
+/// all before it are done. Dropping the receiver cancels the rest.
+pub fn ordered<T, F, Fut>(items: Vec<T>, parallelism: usize, f: F) -> mpsc::Receiver<Fut::Output>
+where
+    T: Send + 'static,
+    F: Fn(T) -> Fut + Send + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut items = items.into_iter();
+        let mut running = VecDeque::new();
+        loop {
+            while running.len() < parallelism.max(1) {
+                let Some(item) = items.next() else {
+                    break;
+                };
+                running.push_back(tokio::spawn(f(item)));
+            }
+            let Some(next) = running.pop_front() else {
+                break;
+            };
+            let delivered = match next.await {
+                Ok(result) => tx.send(result).await.is_ok(),
+                Err(e) => {
+                    tracing::error!("Chunk task failed: {}", e);
+                    false
+                }
+            };
+            if !delivered {
+                running.iter().for_each(|task| task.abort());
+                break;
+            }
+        }
+    });
+    rx
+}
@@ -0,0 +1,109 @@
+/// Line comment markers recognized at the start of each line, longest first
+/// so `///` isn't taken for `//`. `*` covers the middle lines of `/* */` and
+/// Javadoc-style blocks.
+const MARKERS: [&str; 9] = ["///", "//!", "//", "##", "#", "--", ";;", ";", "*"];
+
+/// Maximum line length of rewritten comments, including indentation and
+/// markers.
+pub const WIDTH: usize = 80;
+
+/// Reflowed lines are never made narrower than this, however deep the
+/// indentation.
+const MIN_TEXT_WIDTH: usize = 20;
+
+/// The indentation and marker shared by every line of a comment.
+pub struct Comment {
+    /// Indentation plus marker, e.g. `    ///`
+    prefix: String,
+}
+
+impl Comment {
+    /// Split a comment into its common prefix and the bare text, or `None`
+    /// if the lines don't all start with the same indentation and marker.
+    pub fn strip(text: &str) -> Option<(Comment, String)> {
+        let first = text.lines().find(|line| !line.trim().is_empty())?;
+        let rest = first.trim_start();
+        let indent = &first[..first.len() - rest.len()];
+        let marker = MARKERS.iter().find(|m| rest.starts_with(**m))?;
+        let prefix = format!("{indent}{marker}");
+
+        let mut body = Vec::new();
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                body.push("");
+                continue;
+            }
+            let content = line.strip_prefix(prefix.as_str())?;
+            body.push(content.strip_prefix(' ').unwrap_or(content).trim_end());
+        }
+
+        Some((Comment { prefix }, body.join("\n").trim().to_string()))
+    }
+
+    /// Put the markers back, reflowing plain paragraphs to fit in `WIDTH`
+    /// columns. Paragraphs with lists or code keep their line breaks.
+    pub fn apply(&self, text: &str) -> String {
+        let width = WIDTH
+            .saturating_sub(self.prefix.chars().count() + 1)
+            .max(MIN_TEXT_WIDTH);
+
+        let mut lines = Vec::new();
+        for (i, paragraph) in text.trim().split("\n\n").enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            if keeps_lines(paragraph) {
+                lines.extend(paragraph.lines().map(str::to_string));
+            } else {
+                lines.extend(wrap(paragraph, width));
+            }
+        }
+
+        lines
+            .iter()
+            .map(|line| {
+                if line.is_empty() {
+                    self.prefix.clone()
+                } else {
+                    format!("{} {}", self.prefix, line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Whether a paragraph has structure that reflowing would destroy.
+fn keeps_lines(paragraph: &str) -> bool {
+    paragraph.lines().any(|line| {
+        let trimmed = line.trim_start();
+        line.starts_with("    ")
+            || trimmed.starts_with("```")
+            || trimmed.starts_with("- ")
+            || trimmed.starts_with("* ")
+            || trimmed.starts_with('@')
+            || trimmed
+                .split_once(". ")
+                .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Greedily wrap a paragraph to `width` characters. Words longer than the
+/// width get a line of their own.
+fn wrap(paragraph: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in paragraph.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
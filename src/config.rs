@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use clap::CommandFactory;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::prompts;
+
+/// Top-level table of named profiles, which aren't options themselves.
+const PROFILES: &str = "profiles";
+
+/// Config file location, unless `IMPROVE_WRITING_CONFIG` points elsewhere.
+pub fn path() -> PathBuf {
+    std::env::var_os("IMPROVE_WRITING_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| prompts::config_dir().join("config.toml"))
+}
+
+/// Fill in options from the config file that neither the command line nor
+/// the environment set, so precedence is CLI > env > file > default.
+///
+/// The file is TOML with one key per long option (`ollama-model` or
+/// `ollama_model`); repeatable options take arrays and flags take booleans.
+/// A table groups options by prefix: `tone` in `[reply]` sets `reply-tone`.
+/// File options are inserted ahead of the real arguments so they never
+/// shadow them.
+pub fn merge_file<C: CommandFactory>(mut argv: Vec<OsString>) -> Result<Vec<OsString>> {
+    let path = path();
+    if !path.exists() {
+        return Ok(argv);
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut table: toml::Table = contents
+        .parse()
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+    table.remove(PROFILES);
+    let lines = key_lines(&contents);
+    let at = |long: &str| match lines.get(long) {
+        Some(line) => format!("{}:{line}", path.display()),
+        None => path.display().to_string(),
+    };
+
+    // Let the real parse report usage errors and handle --help
+    let command = C::command();
+    let Ok(matches) = command.clone().try_get_matches_from(&argv) else {
+        return Ok(argv);
+    };
+
+    let mut injected = Vec::new();
+    for (key, value) in flatten(table) {
+        let long = key.replace('_', "-");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()))
+            .with_context(|| format!("Unknown option {key:?} at {}", at(&long)))?;
+
+        let explicit = matches!(
+            matches.value_source(arg.get_id().as_str()),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        );
+        if explicit {
+            continue;
+        }
+
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        let mut option: Vec<OsString> = Vec::new();
+        for value in values {
+            if !arg.get_action().takes_values() {
+                if value.as_bool() == Some(true) {
+                    option.push(format!("--{long}").into());
+                }
+                continue;
+            }
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                other => anyhow::bail!("Unsupported value for {key:?} at {}: {other}", at(&long)),
+            };
+            option.push(format!("--{long}={value}").into());
+        }
+
+        // Parse the option on its own, so a bad value is reported with its
+        // line rather than as if it came from the command line
+        let alone = argv.iter().take(1).cloned().chain(option.iter().cloned());
+        if let Err(e) = command.clone().try_get_matches_from(alone)
+            && matches!(
+                e.kind(),
+                ErrorKind::InvalidValue | ErrorKind::ValueValidation
+            )
+        {
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            anyhow::bail!(
+                "Invalid value for {key:?} at {}: {}",
+                at(&long),
+                message.trim_start_matches("error: ")
+            );
+        }
+        injected.extend(option);
+    }
+
+    argv.splice(1..1, injected);
+    Ok(argv)
+}
+
+/// The `[profiles.NAME]` tables of the config file, by name.
+pub fn profiles() -> Result<Vec<(String, toml::Table)>> {
+    let path = path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut table: toml::Table = contents
+        .parse()
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+    let Some(profiles) = table.remove(PROFILES) else {
+        return Ok(Vec::new());
+    };
+    let toml::Value::Table(profiles) = profiles else {
+        anyhow::bail!(
+            "{PROFILES} in {} must be [profiles.NAME] tables",
+            path.display()
+        );
+    };
+    profiles
+        .into_iter()
+        .map(|(name, value)| match value {
+            toml::Value::Table(table) => Ok((name, table)),
+            _ => anyhow::bail!("Profile {name:?} in {} must be a table", path.display()),
+        })
+        .collect()
+}
+
+/// Where an option is set in the config file (`path:line`), if it is.
+pub fn location(long: &str) -> Option<String> {
+    let path = path();
+    let contents = std::fs::read_to_string(&path).ok()?;
+    let line = key_lines(&contents).get(long).copied()?;
+    Some(format!("{}:{line}", path.display()))
+}
+
+/// The line (1-based) each option is set on, by long option name. A light
+/// scan rather than a TOML parse, good enough to point at a key.
+fn key_lines(contents: &str) -> HashMap<String, usize> {
+    let mut lines = HashMap::new();
+    let mut table: Option<String> = None;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            // An array of tables is located at its first header
+            let name = name.trim();
+            if let Some((parent, key)) = name.rsplit_once('.') {
+                lines
+                    .entry(format!("{parent}-{key}").replace('_', "-"))
+                    .or_insert(i + 1);
+            }
+            table = Some(name.to_string());
+        } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            table = Some(name.trim().to_string());
+        } else if let Some((key, _)) = line.split_once('=') {
+            let key = key.trim().trim_matches('"');
+            let key = match &table {
+                Some(table) => format!("{table}-{key}"),
+                None => key.to_string(),
+            };
+            lines.entry(key.replace('_', "-")).or_insert(i + 1);
+        }
+    }
+    lines
+}
+
+/// Turn `[prefix] key = value` tables into `prefix-key` entries.
+fn flatten(table: toml::Table) -> Vec<(String, toml::Value)> {
+    let mut entries = Vec::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(inner) => {
+                entries.extend(inner.into_iter().map(|(k, v)| (format!("{key}-{k}"), v)));
+            }
+            value => entries.push((key, value)),
+        }
+    }
+    entries
+}
@@ -0,0 +1,460 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::private;
+
+/// Bumped on any incompatible change to methods, params, or results.
+/// See docs/protocol.md.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// A request from a socket client, to be handled by the event loop.
+#[derive(Debug)]
+pub enum Request {
+    Improve { text: String, refine: bool },
+    GenerateCommand { text: String, refine: bool },
+    Status,
+    Pause,
+    Resume,
+    CycleModel,
+    SetModel { name: String },
+    ResetSession,
+    SetProfile { name: String },
+    Recent { limit: usize },
+    Translate { text: String, language: String },
+    Reload,
+    History { query: String, limit: usize },
+}
+
+/// A parsed request plus the channel its result goes back on.
+pub struct Envelope {
+    pub request: Request,
+    pub reply: oneshot::Sender<Result<Value>>,
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// `None` only when the key is missing (a notification); `"id": null`
+    /// is a request like any other
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Value>,
+}
+
+/// Deserialize a field that is there, null included, as `Some`.
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+    id: Value,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct TextParams {
+    text: String,
+    #[serde(default)]
+    refine: bool,
+}
+
+#[derive(Deserialize)]
+struct ProfileParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ModelParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TranslateParams {
+    text: String,
+    language: String,
+}
+
+#[derive(Deserialize)]
+struct HistoryParams {
+    query: String,
+    #[serde(default = "default_recent_limit")]
+    limit: usize,
+}
+
+#[derive(Deserialize)]
+struct RecentParams {
+    #[serde(default = "default_recent_limit")]
+    limit: usize,
+}
+
+fn default_recent_limit() -> usize {
+    10
+}
+
+/// Default socket location: `$XDG_RUNTIME_DIR/improve-writing.sock`, falling
+/// back to the temp directory.
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("improve-writing.sock")
+}
+
+/// Bind the control socket and serve clients in the background. Requests are
+/// forwarded to `tx`.
+pub fn spawn_server(path: &Path, tx: mpsc::Sender<Envelope>) -> Result<()> {
+    let listener = bind(path)?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve_connection(stream, tx.clone()));
+                }
+                Err(e) => tracing::warn!("Control socket accept failed: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Bind a socket only the user can connect to, replacing a stale one left by
+/// a crashed instance. It is created with those permissions, rather than
+/// changed to them after binding, so no one else can connect in between.
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!(
+                "Another instance is already listening on {}",
+                path.display()
+            );
+        }
+        std::fs::remove_file(path).context("Failed to remove stale socket")?;
+    }
+
+    private::with_umask(0o177, || UnixListener::bind(path))
+        .with_context(|| format!("Failed to bind socket {}", path.display()))
+}
+
+/// Serve newline-delimited JSON-RPC requests on one connection.
+async fn serve_connection(stream: UnixStream, tx: mpsc::Sender<Envelope>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some(response) = handle_line(&line, &tx).await else {
+            continue;
+        };
+
+        let mut out = serde_json::to_string(&response).unwrap_or_default();
+        out.push('\n');
+        if let Err(e) = writer.write_all(out.as_bytes()).await {
+            tracing::debug!("Control client went away: {}", e);
+            break;
+        }
+    }
+}
+
+/// Handle one request line. Returns `None` for notifications (no `id`).
+async fn handle_line(line: &str, tx: &mpsc::Sender<Envelope>) -> Option<RpcResponse> {
+    let rpc: RpcRequest = match serde_json::from_str(line) {
+        Ok(rpc) => rpc,
+        Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+    };
+
+    let id = rpc.id.clone();
+    let response = match dispatch(rpc, tx).await {
+        Ok(result) => RpcResponse {
+            jsonrpc: "2.0",
+            result: Some(result),
+            error: None,
+            id: id.clone().unwrap_or(Value::Null),
+        },
+        Err((code, message)) => error_response(id.clone().unwrap_or(Value::Null), code, message),
+    };
+
+    id.map(|_| response)
+}
+
+async fn dispatch(rpc: RpcRequest, tx: &mpsc::Sender<Envelope>) -> Result<Value, (i64, String)> {
+    if rpc.jsonrpc != "2.0" {
+        return Err((INVALID_REQUEST, "jsonrpc must be \"2.0\"".to_string()));
+    }
+    forward(&rpc.method, rpc.params, tx).await
+}
+
+/// Forward a method call to the event loop and wait for its result. Shared
+/// with the Neovim msgpack-RPC socket, so both protocols expose the same
+/// methods.
+pub async fn forward(
+    method: &str,
+    params: Value,
+    tx: &mpsc::Sender<Envelope>,
+) -> Result<Value, (i64, String)> {
+    let request = match method {
+        "improve" | "generate_command" => {
+            let params: TextParams =
+                serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            if method == "improve" {
+                Request::Improve {
+                    text: params.text,
+                    refine: params.refine,
+                }
+            } else {
+                Request::GenerateCommand {
+                    text: params.text,
+                    refine: params.refine,
+                }
+            }
+        }
+        "status" => Request::Status,
+        "pause" => Request::Pause,
+        "resume" => Request::Resume,
+        "cycle_model" => Request::CycleModel,
+        "set_model" => {
+            let params: ModelParams =
+                serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            Request::SetModel { name: params.name }
+        }
+        "recent" => {
+            // Params are optional
+            let params: RecentParams = if params.is_null() {
+                RecentParams {
+                    limit: default_recent_limit(),
+                }
+            } else {
+                serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?
+            };
+            Request::Recent {
+                limit: params.limit,
+            }
+        }
+        "translate" => {
+            let params: TranslateParams =
+                serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            Request::Translate {
+                text: params.text,
+                language: params.language,
+            }
+        }
+        "history" => {
+            let params: HistoryParams =
+                serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            Request::History {
+                query: params.query,
+                limit: params.limit,
+            }
+        }
+        "reload" => Request::Reload,
+        "reset_session" => Request::ResetSession,
+        "set_profile" => {
+            let params: ProfileParams =
+                serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            Request::SetProfile { name: params.name }
+        }
+        "version" => return Ok(version()),
+        other => return Err((METHOD_NOT_FOUND, format!("Unknown method: {other}"))),
+    };
+
+    let (reply, rx) = oneshot::channel();
+    tx.send(Envelope { request, reply })
+        .await
+        .map_err(|_| (SERVER_ERROR, "Daemon is shutting down".to_string()))?;
+
+    match rx.await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(e)) => Err((SERVER_ERROR, format!("{e:#}"))),
+        Err(_) => Err((SERVER_ERROR, "Request was dropped".to_string())),
+    }
+}
+
+/// Call a method on a running instance and return its result.
+pub async fn call(path: &Path, method: &str, params: Value) -> Result<Value> {
+    let stream = UnixStream::connect(path).await.with_context(|| {
+        format!(
+            "Failed to connect to {} (is improve-writing running?)",
+            path.display()
+        )
+    })?;
+    let (reader, mut writer) = stream.into_split();
+
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let mut line = serde_json::to_string(&request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let response = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .context("Connection closed without a response")?;
+    let mut response: Value = serde_json::from_str(&response)?;
+
+    if let Some(error) = response.get("error") {
+        anyhow::bail!("{}", error["message"].as_str().unwrap_or("Unknown error"));
+    }
+    Ok(response["result"].take())
+}
+
+/// Result of the `version` method, also embedded in `status`.
+pub fn version() -> Value {
+    json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "version": env!("CARGO_PKG_VERSION"),
+    })
+}
+
+fn error_response(id: Value, code: i64, message: String) -> RpcResponse {
+    RpcResponse {
+        jsonrpc: "2.0",
+        result: None,
+        error: Some(RpcError { code, message }),
+        id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Forward a call and answer it with the request it was parsed into.
+    async fn forwarded(method: &str, params: Value) -> Result<String, (i64, String)> {
+        let (tx, mut rx) = mpsc::channel::<Envelope>(1);
+        let server = tokio::spawn(async move {
+            if let Some(envelope) = rx.recv().await {
+                let request = format!("{:?}", envelope.request);
+                let _ = envelope.reply.send(Ok(json!(request)));
+            }
+        });
+        let result = forward(method, params, &tx).await;
+        drop(tx);
+        server.await.unwrap();
+        result.map(|value| value.as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn parses_methods() {
+        assert_eq!(
+            forwarded("improve", json!({ "text": "teh cat" }))
+                .await
+                .unwrap(),
+            r#"Improve { text: "teh cat", refine: false }"#
+        );
+        assert_eq!(
+            forwarded(
+                "translate",
+                json!({ "text": "Hallo", "language": "English" })
+            )
+            .await
+            .unwrap(),
+            r#"Translate { text: "Hallo", language: "English" }"#
+        );
+        assert_eq!(
+            forwarded("history", json!({ "query": "cat" }))
+                .await
+                .unwrap(),
+            r#"History { query: "cat", limit: 10 }"#
+        );
+        assert_eq!(
+            forwarded("recent", Value::Null).await.unwrap(),
+            "Recent { limit: 10 }"
+        );
+        assert_eq!(forwarded("reload", Value::Null).await.unwrap(), "Reload");
+        assert_eq!(forwarded("status", Value::Null).await.unwrap(), "Status");
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_calls() {
+        let missing = forwarded("translate", json!({ "text": "Hallo" })).await;
+        assert_eq!(missing.unwrap_err().0, INVALID_PARAMS);
+        let missing = forwarded("history", Value::Null).await;
+        assert_eq!(missing.unwrap_err().0, INVALID_PARAMS);
+        let unknown = forwarded("rewrite", Value::Null).await;
+        assert_eq!(unknown.unwrap_err().0, METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn answers_lines() {
+        let (tx, _rx) = mpsc::channel(1);
+
+        let response = handle_line("{not json", &tx).await.unwrap();
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+
+        let line = r#"{"jsonrpc":"1.0","id":1,"method":"version"}"#;
+        let response = handle_line(line, &tx).await.unwrap();
+        assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+
+        let line = r#"{"jsonrpc":"2.0","id":7,"method":"version"}"#;
+        let response = handle_line(line, &tx).await.unwrap();
+        assert_eq!(response.id, json!(7));
+        assert_eq!(
+            response.result.unwrap()["protocol_version"],
+            json!(PROTOCOL_VERSION)
+        );
+
+        // Notifications get no response, a null id does
+        let line = r#"{"jsonrpc":"2.0","method":"version"}"#;
+        assert!(handle_line(line, &tx).await.is_none());
+        let line = r#"{"jsonrpc":"2.0","id":null,"method":"version"}"#;
+        let response = handle_line(line, &tx).await.unwrap();
+        assert_eq!(response.id, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn binds_private_socket() {
+        // `bind` sets the process-wide umask for a moment; in a process of
+        // its own it can't affect files other tests create meanwhile
+        if std::env::var_os("IMPROVE_WRITING_BIND_TEST").is_none() {
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "control::tests::binds_private_socket"])
+                .env("IMPROVE_WRITING_BIND_TEST", "1")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        let dir = std::env::temp_dir().join(format!("improve-writing-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("control.sock");
+
+        let listener = bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // A second instance is refused while the first listens
+        assert!(bind(&path).is_err());
+
+        drop(listener);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
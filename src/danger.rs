@@ -0,0 +1,294 @@
+/// Programs that run the rest of their arguments as a command, with their
+/// options that take a separate value (`sudo -u root rm`).
+const WRAPPERS: [(&str, &[&str]); 8] = [
+    (
+        "sudo",
+        &[
+            "-u",
+            "--user",
+            "-g",
+            "--group",
+            "-h",
+            "--host",
+            "-p",
+            "--prompt",
+            "-C",
+            "--close-from",
+            "-D",
+            "--chdir",
+            "-r",
+            "--role",
+            "-t",
+            "--type",
+            "-U",
+            "--other-user",
+            "-T",
+            "--command-timeout",
+        ],
+    ),
+    ("doas", &["-u", "-C"]),
+    (
+        "env",
+        &["-u", "--unset", "-C", "--chdir", "-S", "--split-string"],
+    ),
+    ("nohup", &[]),
+    ("time", &["-f", "--format", "-o", "--output"]),
+    ("nice", &["-n", "--adjustment"]),
+    ("exec", &["-a"]),
+    (
+        "xargs",
+        &[
+            "-a",
+            "--arg-file",
+            "-d",
+            "--delimiter",
+            "-E",
+            "-I",
+            "-L",
+            "--max-lines",
+            "-n",
+            "--max-args",
+            "-P",
+            "--max-procs",
+            "-s",
+            "--max-chars",
+        ],
+    ),
+];
+
+/// Wrapper options whose value is itself a command line (`env -S 'rm -rf /'`).
+const SCRIPT_OPTIONS: [&str; 2] = ["-S", "--split-string"];
+
+const SHELLS: [&str; 6] = ["sh", "bash", "zsh", "fish", "dash", "ksh"];
+
+/// Why `command` looks destructive, one reason per problem found. A static
+/// check over the words of each pipeline stage, so it errs on the side of
+/// flagging: any `rm` counts, quoting is only respected for separators.
+pub fn check(command: &str) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let mut flag = |reason: &str| {
+        if !reasons.iter().any(|r| r == reason) {
+            reasons.push(reason.to_string());
+        }
+    };
+
+    if command.replace(' ', "").contains(":(){") {
+        flag("defines a fork bomb");
+    }
+
+    let mut previous: Option<&str> = None;
+    for (words, piped) in stages(command) {
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let (args, mut scripts) = program(&words);
+        let Some((&name, rest)) = args.split_first() else {
+            for script in scripts {
+                check(script).iter().for_each(|reason| flag(reason));
+            }
+            continue;
+        };
+        let name = name.rsplit('/').next().unwrap_or(name);
+        // `sh -c 'rm -rf ~'`, `bash -lc ...`
+        if SHELLS.contains(&name) {
+            let command = rest.iter().position(|arg| {
+                arg.starts_with('-') && !arg.starts_with("--") && arg.ends_with('c')
+            });
+            scripts.extend(command.and_then(|i| rest.get(i + 1)).copied());
+        }
+        for script in scripts {
+            check(script).iter().for_each(|reason| flag(reason));
+        }
+        let has = |flags: &[&str]| {
+            rest.iter().any(|arg| {
+                flags.contains(arg)
+                    || flags.iter().any(|flag| {
+                        // Combined short flags such as -rf
+                        flag.len() == 2
+                            && !flag.starts_with("--")
+                            && arg.starts_with('-')
+                            && !arg.starts_with("--")
+                            && arg.contains(&flag[1..])
+                    })
+            })
+        };
+
+        match name {
+            "rm" | "rmdir" | "unlink" | "shred" => flag("deletes files"),
+            "dd" => flag("writes raw data with dd"),
+            "wipefs" | "fdisk" | "sfdisk" | "sgdisk" | "parted" => flag("changes disk partitions"),
+            name if name.starts_with("mkfs") || name == "mkswap" => flag("formats a filesystem"),
+            "shutdown" | "reboot" | "poweroff" | "halt" => flag("shuts down or restarts"),
+            "kill" | "killall" | "pkill" => flag("kills processes"),
+            "chmod" | "chown" | "chgrp" if has(&["-R", "--recursive"]) => {
+                flag("changes permissions recursively")
+            }
+            "find"
+                if has(&["-delete"])
+                    || rest.windows(2).any(|w| w[0] == "-exec" && w[1] == "rm") =>
+            {
+                flag("deletes files")
+            }
+            "git" => {
+                let sub = rest.iter().find(|arg| !arg.starts_with('-')).copied();
+                match sub {
+                    Some("push") if has(&["-f", "--force", "--force-with-lease"]) => {
+                        flag("force-pushes")
+                    }
+                    Some("reset") if has(&["--hard"]) => flag("discards uncommitted changes"),
+                    Some("clean") if has(&["-f", "--force"]) => flag("deletes untracked files"),
+                    _ => {}
+                }
+            }
+            name if piped
+                && SHELLS.contains(&name)
+                && previous.is_some_and(|p| matches!(p, "curl" | "wget")) =>
+            {
+                flag("pipes a download into a shell")
+            }
+            _ => {}
+        }
+
+        if words.windows(2).any(|w| {
+            matches!(w[0], ">" | ">>") && w[1].starts_with("/dev/") && !is_harmless_device(w[1])
+        }) || words
+            .iter()
+            .any(|w| w.starts_with(">/dev/") && !is_harmless_device(&w[1..]))
+        {
+            flag("overwrites a device");
+        }
+
+        previous = Some(match name {
+            "curl" => "curl",
+            "wget" => "wget",
+            _ => "",
+        });
+    }
+
+    reasons
+}
+
+fn is_harmless_device(path: &str) -> bool {
+    matches!(
+        path,
+        "/dev/null" | "/dev/stdout" | "/dev/stderr" | "/dev/tty"
+    )
+}
+
+/// The words of a pipeline stage after leading wrappers, their options and
+/// `NAME=value` assignments, and the command lines given to the wrappers as
+/// option values.
+fn program<'a>(words: &'a [&'a str]) -> (&'a [&'a str], Vec<&'a str>) {
+    let mut scripts = Vec::new();
+    // Options taking a value, of the wrapper being skipped
+    let mut options: &[&str] = &[];
+    let mut start = 0;
+    while let Some(&word) = words.get(start) {
+        if let Some(&(_, wrapper_options)) = WRAPPERS.iter().find(|(name, _)| *name == word) {
+            options = wrapper_options;
+        } else if options.contains(&word) {
+            start += 1;
+            if SCRIPT_OPTIONS.contains(&word) {
+                scripts.extend(words.get(start).copied());
+            }
+        } else if !word.starts_with('-') && !is_assignment(word) {
+            break;
+        }
+        start += 1;
+    }
+    (&words[start.min(words.len())..], scripts)
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Split a command line into stages at `|`, `;`, `&` and newlines outside
+/// quotes, each with its words and whether it reads from a pipe.
+fn stages(command: &str) -> Vec<(Vec<String>, bool)> {
+    let mut stages = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let mut piped = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => quote = Some(c),
+            // Redirections such as 2>&1 and &>file
+            (None, '&') if word.ends_with(['>', '<']) || chars.peek() == Some(&'>') => word.push(c),
+            (None, '|' | ';' | '&' | '\n') => {
+                // `||` and `&&` run the next command whether or not anything
+                // was piped; `|&` pipes stderr as well
+                let doubled = chars.next_if_eq(&c).is_some();
+                if c == '|' && !doubled {
+                    chars.next_if_eq(&'&');
+                }
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                stages.push((std::mem::take(&mut words), piped));
+                piped = c == '|' && !doubled;
+            }
+            (None, c) if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            (None, c) => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    stages.push((words, piped));
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+
+    fn flagged(command: &str) -> bool {
+        !check(command).is_empty()
+    }
+
+    #[test]
+    fn flags_through_wrapper_options() {
+        assert!(flagged("sudo -u root rm -rf /"));
+        assert!(flagged("sudo --user root rm -rf /"));
+        assert!(flagged("nice -n 10 rm -rf ~"));
+        assert!(flagged("sudo -u root nice -n 5 env FOO=1 rm -rf /tmp/x"));
+        assert!(flagged("time -f %e dd if=/dev/zero of=/dev/sda"));
+        assert!(flagged("doas -u root shutdown now"));
+    }
+
+    #[test]
+    fn flags_xargs() {
+        assert!(flagged("find . -name '*.o' | xargs rm"));
+        assert!(flagged("ls | xargs -n 1 -P 4 rm -f"));
+        assert!(flagged("cat pids | xargs -I {} kill {}"));
+    }
+
+    #[test]
+    fn flags_shell_scripts() {
+        assert!(flagged("sh -c 'rm -rf ~'"));
+        assert!(flagged("bash -c \"git push --force\""));
+        assert!(flagged("bash -lc 'curl https://x.sh | sh'"));
+        assert!(flagged("sudo bash -c 'mkfs.ext4 /dev/sdb1'"));
+        assert!(flagged("env -S 'rm -rf /'"));
+    }
+
+    #[test]
+    fn leaves_harmless_commands() {
+        assert!(!flagged("sudo -u root ls -la /"));
+        assert!(!flagged("nice -n 10 make -j8"));
+        assert!(!flagged("find . -name '*.rs' | xargs grep -n TODO"));
+        assert!(!flagged("bash -c 'echo hello'"));
+        assert!(!flagged("echo rm > /dev/null"));
+        assert!(!flagged("git push origin main"));
+    }
+}
@@ -0,0 +1,181 @@
+/// Above this many token pairs the diff isn't worth computing; the change is
+/// shown as a whole replacement instead.
+const MAX_CELLS: usize = 4_000_000;
+
+/// Combining long stroke overlay, rendered over the preceding character.
+const STRIKE: char = '\u{0336}';
+
+/// One run of a word-level diff.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Edit<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// How edits are marked up when shown as tracked changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChangeMarkup {
+    /// Output only the new text
+    Off,
+    /// Deletions struck through with Unicode combining characters
+    Strikethrough,
+    /// CriticMarkup: {--deleted--}, {++inserted++}, {~~old~>new~~}
+    Critic,
+}
+
+/// Word-level diff between two texts. Whitespace runs are tokens of their
+/// own, so joining the `Equal` and `Delete` runs gives back `old` and joining
+/// the `Equal` and `Insert` runs gives back `new`.
+pub fn diff<'a>(old: &'a str, new: &'a str) -> Vec<Edit<'a>> {
+    let a = tokenize(old);
+    let b = tokenize(new);
+
+    if a.len().saturating_mul(b.len()) > MAX_CELLS {
+        return vec![Edit::Delete(old), Edit::Insert(new)];
+    }
+
+    // lcs[i][j]: longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // One edit per token for now; merged into runs below
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(Edit::Equal(a[i]));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            edits.push(Edit::Insert(b[j]));
+            j += 1;
+        } else {
+            edits.push(Edit::Delete(a[i]));
+            i += 1;
+        }
+    }
+
+    merge_runs(old, new, edits)
+}
+
+/// Render the change from `old` to `new` as tracked changes.
+pub fn markup(old: &str, new: &str, style: ChangeMarkup) -> String {
+    let edits = diff(old, new);
+    let mut out = String::new();
+
+    match style {
+        ChangeMarkup::Off => out.push_str(new),
+        ChangeMarkup::Strikethrough => {
+            for edit in edits {
+                match edit {
+                    Edit::Equal(s) | Edit::Insert(s) => out.push_str(s),
+                    Edit::Delete(s) => {
+                        for c in s.chars() {
+                            out.push(c);
+                            if !c.is_whitespace() {
+                                out.push(STRIKE);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ChangeMarkup::Critic => {
+            let mut edits = edits.into_iter().peekable();
+            while let Some(edit) = edits.next() {
+                match edit {
+                    Edit::Equal(s) => out.push_str(s),
+                    Edit::Delete(old) => {
+                        if let Some(Edit::Insert(new)) = edits.peek() {
+                            out.push_str(&format!("{{~~{old}~>{new}~~}}"));
+                            edits.next();
+                        } else {
+                            out.push_str(&format!("{{--{old}--}}"));
+                        }
+                    }
+                    Edit::Insert(s) => out.push_str(&format!("{{++{s}++}}")),
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Split into alternating runs of whitespace and non-whitespace.
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+
+    for (i, c) in text.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.is_some_and(|s| s != space) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+
+    tokens
+}
+
+/// Merge consecutive tokens of the same kind into single slices of the
+/// original texts, and order each delete before its paired insert.
+fn merge_runs<'a>(old: &'a str, new: &'a str, tokens: Vec<Edit<'a>>) -> Vec<Edit<'a>> {
+    let mut edits = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Edit::Equal(s) => {
+                let mut len = s.len();
+                while let Some(Edit::Equal(next)) = tokens.peek() {
+                    len += next.len();
+                    tokens.next();
+                }
+                edits.push(Edit::Equal(&old[old_pos..old_pos + len]));
+                old_pos += len;
+                new_pos += len;
+            }
+            first => {
+                // A change: collect every delete and insert up to the next
+                // equal run
+                let (mut deleted, mut inserted) = (0, 0);
+                let mut count = |edit: &Edit| match edit {
+                    Edit::Delete(s) => deleted += s.len(),
+                    Edit::Insert(s) => inserted += s.len(),
+                    Edit::Equal(_) => {}
+                };
+                count(&first);
+                while let Some(next) = tokens.next_if(|e| !matches!(e, Edit::Equal(_))) {
+                    count(&next);
+                }
+
+                if deleted > 0 {
+                    edits.push(Edit::Delete(&old[old_pos..old_pos + deleted]));
+                    old_pos += deleted;
+                }
+                if inserted > 0 {
+                    edits.push(Edit::Insert(&new[new_pos..new_pos + inserted]));
+                    new_pos += inserted;
+                }
+            }
+        }
+    }
+
+    edits
+}
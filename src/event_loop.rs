@@ -1,16 +1,544 @@
-use anyhow::Result;
-use hotkey_listener::{HotkeyEvent, HotkeyListenerHandle};
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use fluent_bundle::FluentArgs;
+use serde_json::{Value, json};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::atomic::{AtomicBool, Ordering::Acquire};
-use std::time::Duration;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, mpsc};
+use tracing::Instrument;
 
-use crate::ollama::TextImprover;
-use crate::output::{clear_line, copy_to_clipboard, get_primary_selection, type_text};
+use crate::chunk;
+use crate::control::{self, Envelope, Request};
+use crate::danger;
+use crate::diff::{self, ChangeMarkup};
+use crate::execute;
+use crate::facts::{self, FactCheck};
+use crate::history::{self, History};
+use crate::i18n;
+use crate::journal::Journal;
+use crate::metrics::{self, Readability, ReadabilityReport};
+use crate::notify;
+use crate::ollama::{InvalidCommandReply, Profile, TextImprover, TimedOut};
+use crate::osd::Osd;
+use crate::output::{
+    ClipboardSlots, ClipboardTarget, OutputMode, Placeholder, ReasoningOutput, clear_line, copy_to,
+    copy_to_clipboard, deliver, edit, get_clipboard, get_primary_selection,
+};
+use crate::password;
+use crate::picker;
+use crate::pipeline::Pipeline;
+use crate::profiles::{self, Profile as UserProfile};
+use crate::queue::PressQueue;
+use crate::screencast;
+use crate::screenshot::{self, ImageSource};
+use crate::script::{Before, Script};
+use crate::select::{self, Strategy};
+use crate::shell::{self, Shell};
+use crate::speech::{self, ReadAloud};
+use crate::throttle::Throttle;
+use crate::voice::Voice;
+use crate::web;
+use crate::window::{self, Blocklist, Window};
 
-enum Mode {
+/// Page text is summarized in chunks of this many characters...
+const SUMMARY_CHUNK_CHARS: usize = 6000;
+/// ...and at most this many chunks are read from a single page.
+const SUMMARY_MAX_CHUNKS: usize = 8;
+
+/// Pause after refocusing a window before typing into it.
+const REFOCUS_SETTLE: Duration = Duration::from_millis(150);
+
+/// What a hotkey does. Hotkeys are registered in the same order as the modes
+/// passed to `run_event_loop`, so a press of hotkey `i` maps to `modes[i]`.
+#[derive(Clone, Copy, Debug)]
+pub enum Mode {
     Improve,
     ImproveShowOriginal,
     ShellCommand,
+    /// Improve text recognized in a screenshot region
+    Ocr,
+    /// Ask a vision model about a screenshot or clipboard image
+    Image,
+    /// Improve with several alternatives and let the user pick one
+    Pick,
+    /// Shorten the selection to the configured word/character limits
+    Shorten,
+    /// Expand notes or bullet points into prose
+    Expand,
+    /// Toggle whether the other hotkeys do anything
+    Pause,
+    /// Switch to the next model in --models
+    CycleModel,
+    /// Rewrite a code comment, keeping its markers
+    Comment,
+    /// Draft a reply to a selected email onto the clipboard
+    Reply,
+    /// Suggest titles for the selection and insert the one picked
+    Title,
+    /// Rewrite with a preset prompt
+    Rewrite(Profile),
+    /// Improve with each of --compare-models side by side
+    Compare,
+    /// Run the last action again on its original text
+    Repeat,
+    /// Forget the improvements remembered as session context
+    ResetSession,
+    /// Switch to the next profile
+    CycleProfile,
+    /// Explain the selected shell command without typing anything
+    ExplainCommand,
+    /// Rewrite the selection as a spoken instruction says
+    Voice,
+    /// Improve the selection one sentence at a time, typing each as soon
+    /// as it is ready
+    Sentences,
+    /// Type the result that was read aloud
+    Accept,
+    /// Drop the result that was read aloud
+    Reject,
+}
+
+impl Mode {
+    /// Whether the result's line breaks are part of it (comments for code
+    /// editors, lists), so they are typed rather than flattened.
+    fn keeps_newlines(&self) -> bool {
+        match self {
+            Mode::Comment => true,
+            Mode::Rewrite(profile) => profile.keeps_newlines(),
+            _ => false,
+        }
+    }
+
+    /// Name passed to script hooks.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Improve => "improve",
+            Mode::ImproveShowOriginal => "improve-show-original",
+            Mode::ShellCommand => "command",
+            Mode::Ocr => "ocr",
+            Mode::Image => "image",
+            Mode::Pick => "pick",
+            Mode::Shorten => "shorten",
+            Mode::Expand => "expand",
+            Mode::Pause => "pause",
+            Mode::CycleModel => "cycle-model",
+            Mode::Comment => "comment",
+            Mode::Reply => "reply",
+            Mode::Title => "title",
+            Mode::Rewrite(profile) => profile.name(),
+            Mode::Compare => "compare",
+            Mode::Repeat => "repeat",
+            Mode::ResetSession => "reset-session",
+            Mode::CycleProfile => "cycle-profile",
+            Mode::ExplainCommand => "explain-command",
+            Mode::Voice => "voice",
+            Mode::Sentences => "sentences",
+            Mode::Accept => "accept",
+            Mode::Reject => "reject",
+        }
+    }
+}
+
+/// Which improve hotkeys get a second, critic pass over their rewrite.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CriticPass {
+    /// Never
+    Off,
+    /// Only the show-original (Shift) hotkey
+    ShowOriginal,
+    /// Every improve hotkey, including OCR
+    Always,
+}
+
+/// Where the compare hotkey puts its results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CompareOutput {
+    /// Offer them in a menu and output the one picked
+    Picker,
+    /// Copy them all, labeled with their models, to the clipboard
+    Clipboard,
+}
+
+/// Where the explain-command hotkey puts its explanation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExplanationOutput {
+    /// Show it in a notification
+    Notify,
+    /// Copy it to the clipboard
+    Clipboard,
+    /// Both
+    Both,
+}
+
+/// Options that shape how the event loop handles each hotkey press.
+pub struct Settings {
+    pub output: OutputMode,
+    pub changes: ChangeMarkup,
+    pub critic: CriticPass,
+    /// Rewrites sampled by the show-original hotkey, of which the best is kept
+    pub best_of: usize,
+    pub best_of_strategy: Strategy,
+    /// Replaces the single improve request of the improve hotkeys
+    pub pipeline: Option<Pipeline>,
+    pub script: Option<Script>,
+    pub candidates: usize,
+    pub readability: ReadabilityReport,
+    pub require_readable: bool,
+    pub check_facts: FactCheck,
+    pub image_source: ImageSource,
+    pub image_prompt: String,
+    pub reasoning: ReasoningOutput,
+    /// Seconds to wait before typing a result
+    pub type_delay: u64,
+    /// Count the delay down in a notification
+    pub type_countdown: bool,
+    /// Don't type into a window other than the one the hotkey was pressed in
+    pub check_focus: bool,
+    /// Focus that window again rather than falling back to the clipboard
+    pub refocus: bool,
+    /// Refuse to type into a focused password field
+    pub check_password_fields: bool,
+    /// Windows never read from or typed into
+    pub blocklist: Blocklist,
+    /// Copy results to the clipboard instead of typing them while the screen
+    /// is shared
+    pub screen_share_guard: bool,
+    /// Models the cycle-model hotkey steps through
+    pub models: Vec<String>,
+    /// Models the compare hotkey runs side by side
+    pub compare_models: Vec<String>,
+    pub compare_output: CompareOutput,
+    pub osd: Option<Osd>,
+    /// Typed at the cursor while a request is in flight
+    pub placeholder: Option<Placeholder>,
+    /// Where results are recorded, if anywhere
+    pub history: Option<History>,
+    /// Daily Markdown files results are appended to, if any
+    pub journal: Option<Journal>,
+    /// Command results are opened in with the editor output mode
+    pub editor: Option<String>,
+    /// Cooldowns and rate limit for hotkey actions
+    pub throttle: Throttle,
+    /// Hotkey actions that may run at once; one-shot actions beyond the
+    /// first run in the background
+    pub concurrency: usize,
+    /// Chunks of one long input sent to the backend at once
+    pub chunk_parallelism: usize,
+    /// Ask before sending the same selection to the same action twice in a
+    /// row
+    pub confirm_repeats: bool,
+    /// Use the clipboard text when nothing is selected
+    pub fallback_to_clipboard: bool,
+    /// Leave the clipboard as it was before a hotkey press, instead of
+    /// putting the original text there as a backup
+    pub preserve_clipboard: bool,
+    /// Where the original and the result go after an improvement, if
+    /// anywhere
+    pub clipboard_slots: Option<ClipboardSlots>,
+    /// Offer to run generated commands instead of typing them
+    pub execute_commands: bool,
+    /// Have the model explain generated commands before they are output
+    pub review_commands: bool,
+    /// Type generated commands even when they look destructive
+    pub allow_dangerous_commands: bool,
+    /// Shell whose history generated commands are appended to instead of
+    /// being typed
+    pub command_history: Option<Shell>,
+    /// Where explanations of selected commands go
+    pub explanation_output: ExplanationOutput,
+    /// The default profile followed by those from the config file, replaced
+    /// when the control socket's `reload` reads the file again
+    pub profiles: RwLock<Vec<UserProfile>>,
+    /// The default profile as the command line sets it, before the config
+    /// file's `[profiles.default]`; reloads start from it
+    pub profile_base: UserProfile,
+    /// Recording and transcription for the voice hotkey
+    pub voice: Option<Voice>,
+    /// Read results aloud and type them only once accepted
+    pub read_aloud: Option<ReadAloud>,
+}
+
+impl Settings {
+    /// A copy of the current profile list.
+    fn profiles(&self) -> Vec<UserProfile> {
+        self.profiles
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Give the user time to focus the right window before a result is typed.
+async fn wait_before_typing(settings: &Settings) {
+    if settings.type_delay == 0 {
+        return;
+    }
+    if settings.type_countdown {
+        notify::countdown(&i18n::t("typing-countdown"), settings.type_delay).await;
+    } else {
+        tokio::time::sleep(Duration::from_secs(settings.type_delay)).await;
+    }
+}
+
+/// Get ready to type `text`: wait for --type-delay, then check that the
+/// window focused when the hotkey was pressed still has focus. If it doesn't,
+/// it is refocused when --refocus is set; otherwise (or if that fails) `text`
+/// goes to the clipboard instead and this returns false. Nothing is typed
+/// into a password field.
+async fn ready_to_type(settings: &Settings, origin: Option<&Window>, text: &str) -> bool {
+    wait_before_typing(settings).await;
+
+    if let Some(refusal) = refusal(settings, origin).await {
+        // It went to another window and can't be erased from here
+        if let Some(placeholder) = &settings.placeholder {
+            placeholder.abandon();
+        }
+        divert(refusal, text).await;
+        return false;
+    }
+    if let Some(placeholder) = &settings.placeholder {
+        placeholder.erase().await;
+    }
+    true
+}
+
+/// Whether `window`, or the focused window when not given, is on the
+/// blocklist; it is then left alone, with a notification. A window that
+/// can't be found isn't blocked.
+async fn in_blocked_window(settings: &Settings, window: Option<&Window>) -> bool {
+    if settings.blocklist.is_empty() {
+        return false;
+    }
+    let focused = match window {
+        Some(_) => None,
+        None => window::focused().await,
+    };
+    let Some(window) = window.or(focused.as_ref()) else {
+        return false;
+    };
+    let Some(entry) = settings.blocklist.blocking(window) else {
+        return false;
+    };
+
+    tracing::info!(
+        "{:?} is blocked by {:?} - leaving it alone",
+        window.app,
+        entry
+    );
+    let args = FluentArgs::from_iter([("entry", entry)]);
+    if let Err(e) = notify::notify(
+        &i18n::t("window-blocked"),
+        &i18n::t_args("window-blocked-detail", Some(&args)),
+    )
+    .await
+    {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+    true
+}
+
+/// Why a result can't be typed right now.
+#[derive(Clone, Copy, Debug)]
+enum Refusal {
+    /// The origin window lost focus; the result goes to the clipboard
+    FocusMoved,
+    /// The screen is shared; the result goes to the clipboard rather than
+    /// being typed in front of the audience
+    ScreenShared,
+    /// A blocked window or a password field has focus; the result is
+    /// dropped (the check already said why)
+    Dropped,
+}
+
+/// The checks of `ready_to_type`, without the wait, in order. Also run
+/// before each piece of a result typed piece by piece.
+async fn refusal(settings: &Settings, origin: Option<&Window>) -> Option<Refusal> {
+    if !keep_focus(settings, origin).await {
+        return Some(Refusal::FocusMoved);
+    }
+    if in_blocked_window(settings, None).await || in_password_field(settings).await {
+        return Some(Refusal::Dropped);
+    }
+    if on_shared_screen(settings).await {
+        return Some(Refusal::ScreenShared);
+    }
+    None
+}
+
+/// Put `text`, refused for typing, where `refusal` allows: the clipboard,
+/// or nowhere.
+async fn divert(refusal: Refusal, text: &str) {
+    let title = match refusal {
+        Refusal::FocusMoved => "focus-changed",
+        Refusal::ScreenShared => "screen-shared",
+        Refusal::Dropped => return,
+    };
+    if let Err(e) = copy_to_clipboard(text).await {
+        tracing::error!("Failed to copy result to clipboard: {}", e);
+    }
+    if let Err(e) = notify::notify(&i18n::t(title), &i18n::t("result-copied")).await {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// The --screen-share-guard check of `ready_to_type`.
+async fn on_shared_screen(settings: &Settings) -> bool {
+    if !settings.screen_share_guard {
+        return false;
+    }
+    match screencast::active().await {
+        Ok(false) => false,
+        Ok(true) => {
+            tracing::info!(
+                "The screen is shared - copying the result to the clipboard instead of typing it"
+            );
+            true
+        }
+        Err(e) => {
+            tracing::debug!("Can't tell whether the screen is shared: {:#}", e);
+            false
+        }
+    }
+}
+
+/// The password field check of `ready_to_type`. The result isn't copied to
+/// the clipboard either: a password prompt may be watching it.
+async fn in_password_field(settings: &Settings) -> bool {
+    if !settings.check_password_fields {
+        return false;
+    }
+    match password::focused().await {
+        Ok(false) => false,
+        Ok(true) => {
+            tracing::warn!("A password field has focus - not typing the result");
+            if let Err(e) = notify::notify(
+                &i18n::t("password-field"),
+                &i18n::t("password-field-detail"),
+            )
+            .await
+            {
+                tracing::warn!("Failed to show notification: {}", e);
+            }
+            true
+        }
+        Err(e) => {
+            tracing::debug!("Can't tell whether a password field has focus: {:#}", e);
+            false
+        }
+    }
+}
+
+/// The focus check of `ready_to_type`.
+async fn keep_focus(settings: &Settings, origin: Option<&Window>) -> bool {
+    // A type delay exists to let the user pick another window
+    if !settings.check_focus || settings.type_delay > 0 {
+        return true;
+    }
+    let Some(origin) = origin else {
+        return true;
+    };
+    let Some(current) = window::focused().await else {
+        return true;
+    };
+    if current.id == origin.id {
+        return true;
+    }
+
+    if settings.refocus {
+        tracing::info!(
+            "Focus moved to {:?} - refocusing {:?}",
+            current.app,
+            origin.app
+        );
+        match window::focus(origin).await {
+            Ok(()) => {
+                // Let the compositor deliver focus before keystrokes arrive
+                tokio::time::sleep(REFOCUS_SETTLE).await;
+                return true;
+            }
+            Err(e) => tracing::warn!("Failed to refocus the original window: {}", e),
+        }
+    }
+
+    tracing::warn!(
+        "Focus moved from {:?} to {:?} - copying the result to the clipboard instead of typing it",
+        origin.app,
+        current.app
+    );
+    false
+}
+
+/// Output a result using the configured output mode, or read it aloud to be
+/// accepted first when it would be typed and --read-aloud is set.
+async fn output_result(settings: &Settings, origin: Option<&Window>, text: &str) -> Result<()> {
+    if let Some(read_aloud) = &settings.read_aloud
+        && settings.output.types_eventually()
+    {
+        return read_aloud.hold(origin, text).await;
+    }
+    output_now(settings, origin, text).await
+}
+
+/// Put the original on one clipboard and the result on the other
+/// (--clipboard-slots).
+async fn fill_clipboard_slots(settings: &Settings, original: &str, result: &str) {
+    let Some(slots) = settings.clipboard_slots else {
+        return;
+    };
+    let (primary, clipboard) = match slots {
+        ClipboardSlots::OriginalPrimary => (original, result),
+        ClipboardSlots::ImprovedPrimary => (result, original),
+    };
+    if let Err(e) = copy_to(ClipboardTarget::Primary, primary).await {
+        tracing::warn!("Failed to set the primary selection: {}", e);
+    }
+    if let Err(e) = copy_to(ClipboardTarget::Clipboard, clipboard).await {
+        tracing::warn!("Failed to copy to clipboard: {}", e);
+    }
+}
+
+/// Output a result using the configured output mode. Typed results wait
+/// for `ready_to_type` first.
+async fn output_now(settings: &Settings, origin: Option<&Window>, text: &str) -> Result<()> {
+    if settings.output == OutputMode::Editor {
+        return edit_and_type(settings, origin, text).await;
+    }
+    if settings.output.types() && !ready_to_type(settings, origin, text).await {
+        return Ok(());
+    }
+    // The placeholder already took the selection's place
+    let mode = match settings.output {
+        OutputMode::Replace if settings.placeholder.is_some() => OutputMode::Type,
+        other => other,
+    };
+    deliver(mode, text).await
+}
+
+/// Open a result in the editor and type what was saved, unless it was
+/// emptied.
+async fn edit_and_type(settings: &Settings, origin: Option<&Window>, text: &str) -> Result<()> {
+    let editor = settings
+        .editor
+        .as_deref()
+        .context("--output editor needs --editor, $VISUAL or $EDITOR")?;
+    let edited = edit(editor, text).await?;
+    let edited = edited.trim_end();
+    if edited.trim().is_empty() {
+        tracing::info!("Edited text is empty - nothing to type");
+        return Ok(());
+    }
+    // Results reach the editor already flattened unless their mode keeps
+    // newlines; typing a newline the user added would press Enter
+    let edited = if text.contains('\n') {
+        edited.to_string()
+    } else {
+        edited.replace('\n', "  ")
+    };
+    if !ready_to_type(settings, origin, &edited).await {
+        return Ok(());
+    }
+    deliver(OutputMode::Type, &edited).await
 }
 
 /// Check for the REDO keyword (whole word, all-caps). Returns the cleaned text
@@ -29,116 +557,1623 @@ fn extract_refine(text: &str) -> (String, bool) {
     }
 }
 
-pub async fn run_event_loop(
-    handle: HotkeyListenerHandle,
-    mut improver: TextImprover,
-    running: Arc<AtomicBool>,
+async fn handle_image(
+    improver: &TextImprover,
+    origin: Option<&Window>,
+    settings: &Settings,
 ) -> Result<()> {
-    log::info!("Listening for hotkey... Press Ctrl+C to exit.");
+    let image = screenshot::capture_image(settings.image_source).await?;
+    let result = improver
+        .describe_image(&settings.image_prompt, image)
+        .await?;
 
-    while running.load(Acquire) {
-        // Check for hotkey events
-        match handle.recv_timeout(Duration::from_millis(100)) {
-            Ok(event) => {
-                // Only handle press events, not releases
-                let mode = match event {
-                    HotkeyEvent::Pressed(0) => Mode::Improve,
-                    HotkeyEvent::Pressed(1) => Mode::ImproveShowOriginal,
-                    HotkeyEvent::Pressed(2) => Mode::ShellCommand,
-                    _ => continue,
-                };
+    if result.is_empty() {
+        tracing::warn!("Ollama returned empty response");
+        return Ok(());
+    }
 
-                log::info!("Hotkey pressed - getting selection...");
+    tracing::debug!("Image result: {:?}", result);
 
-                // Get highlighted text
-                match get_primary_selection().await {
-                    Ok(text) => {
-                        let text = text.trim();
-                        if text.is_empty() {
-                            log::warn!("No text selected");
-                            continue;
+    let output = if settings.output.types() {
+        result.replace('\n', "  ")
+    } else {
+        result
+    };
+    // There is no selection to replace
+    if settings.output == OutputMode::Replace {
+        if !ready_to_type(settings, origin, &output).await {
+            return Ok(());
+        }
+        return deliver(OutputMode::Type, &output).await;
+    }
+    output_result(settings, origin, &output).await
+}
+
+/// Run the script's before hook, if there is a script. Returns the text to
+/// work on, or `None` if the request should be dropped.
+fn before_hook(
+    settings: &Settings,
+    improver: &mut TextImprover,
+    mode: Mode,
+    origin: Option<&Window>,
+    text: &str,
+) -> Option<String> {
+    let Some(script) = &settings.script else {
+        return Some(text.to_string());
+    };
+
+    match script.before(mode.name(), app_name(origin), text) {
+        Ok(Before::Proceed { text, instructions }) => {
+            improver.set_instructions(instructions);
+            Some(text)
+        }
+        Ok(Before::Veto) => {
+            tracing::info!("Script vetoed the request");
+            None
+        }
+        Err(e) => {
+            tracing::error!("Script before hook failed: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Let the script inspect, replace, or veto a result before it is output.
+fn after_hook(
+    settings: &Settings,
+    mode: Mode,
+    origin: Option<&Window>,
+    input: &str,
+    result: String,
+) -> Option<String> {
+    let result = match &settings.script {
+        Some(script) => match script.after(mode.name(), app_name(origin), input, &result) {
+            Ok(Some(result)) => result,
+            Ok(None) => {
+                tracing::info!("Script vetoed the result");
+                return None;
+            }
+            Err(e) => {
+                tracing::error!("Script after hook failed: {:#}", e);
+                return None;
+            }
+        },
+        None => result,
+    };
+
+    if let Some(history) = &settings.history
+        && let Err(e) = history.record(mode.name(), app_name(origin), input, &result)
+    {
+        tracing::warn!("Failed to record history: {:#}", e);
+    }
+    if let Some(journal) = &settings.journal
+        && let Err(e) = journal.append(mode.name(), app_name(origin), input, &result)
+    {
+        tracing::warn!("Failed to append to the journal: {:#}", e);
+    }
+    Some(result)
+}
+
+/// A result recorded in the history for the same text and action, so asking
+/// again doesn't wait for the model. A refinement always asks the model.
+fn recorded(settings: &Settings, mode: Mode, input: &str, refine: bool) -> Option<String> {
+    if refine {
+        return None;
+    }
+    match settings.history.as_ref()?.lookup(mode.name(), input) {
+        Ok(Some(improved)) => {
+            tracing::info!("Reusing the recorded result for this text");
+            Some(improved)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("Failed to look up history: {:#}", e);
+            None
+        }
+    }
+}
+
+fn app_name(origin: Option<&Window>) -> Option<&str> {
+    origin?.app.as_deref()
+}
+
+/// Request alternative rewrites and deliver the one the user picks.
+async fn handle_pick(
+    improver: &TextImprover,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    let mut candidates = improver
+        .improve_candidates(text, settings.candidates)
+        .await?;
+
+    let chosen = if candidates.len() == 1 {
+        tracing::info!("All candidates were identical");
+        Some(0)
+    } else {
+        picker::pick(&candidates).await?
+    };
+    let Some(chosen) = chosen else {
+        tracing::info!("No candidate picked");
+        return Ok(());
+    };
+
+    let result = candidates.swap_remove(chosen);
+    tracing::debug!("Picked candidate {}: {:?}", chosen + 1, result);
+
+    let Some(result) = after_hook(settings, Mode::Pick, origin, text, result) else {
+        return Ok(());
+    };
+    let output = if settings.output.types() {
+        result.replace('\n', "  ")
+    } else {
+        result
+    };
+    output_result(settings, origin, &output).await
+}
+
+/// Suggest titles for the selection and deliver the one the user picks.
+async fn handle_title(
+    improver: &TextImprover,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    let mut titles = improver.suggest_titles(text, settings.candidates).await?;
+    if titles.is_empty() {
+        tracing::warn!("Ollama returned no titles");
+        return Ok(());
+    }
+
+    let chosen = if titles.len() == 1 {
+        Some(0)
+    } else {
+        picker::pick(&titles).await?
+    };
+    let Some(chosen) = chosen else {
+        tracing::info!("No title picked");
+        return Ok(());
+    };
+
+    let title = titles.swap_remove(chosen);
+    tracing::debug!("Picked title: {:?}", title);
+
+    let Some(title) = after_hook(settings, Mode::Title, origin, text, title) else {
+        return Ok(());
+    };
+    output_result(settings, origin, &title).await
+}
+
+/// Rewrite the selection with one of the text actions besides improve.
+async fn handle_rewrite(
+    improver: &mut TextImprover,
+    mode: Mode,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    let (input, refine) = extract_refine(text);
+    let result = match mode {
+        Mode::Shorten => improver.shorten(&input, refine).await?,
+        Mode::Expand => improver.expand(&input, refine).await?,
+        Mode::Comment => improver.rewrite_comment(&input, refine).await?,
+        Mode::Rewrite(profile) => improver.rewrite(profile, &input, refine).await?,
+        Mode::Voice => {
+            let voice = settings.voice.as_ref().context("No --whisper-model set")?;
+            let args = FluentArgs::from_iter([("seconds", voice.seconds)]);
+            if let Err(e) = notify::notify(
+                &i18n::t("listening"),
+                &i18n::t_args("listening-detail", Some(&args)),
+            )
+            .await
+            {
+                tracing::warn!("Failed to show notification: {}", e);
+            }
+            let instruction = voice.listen().await?;
+            improver.instruct(&instruction, &input, refine).await?
+        }
+        _ => unreachable!("{mode:?} is not a rewrite mode"),
+    };
+
+    if result.is_empty() {
+        tracing::warn!("Ollama returned empty response");
+        return Ok(());
+    }
+
+    tracing::debug!("Rewritten text: {:?}", result);
+
+    let Some(result) = after_hook(settings, mode, origin, &input, result) else {
+        return Ok(());
+    };
+    let output = if settings.output.types() && !mode.keeps_newlines() {
+        result.replace('\n', "  ")
+    } else {
+        result.clone()
+    };
+    output_result(settings, origin, &output).await?;
+    fill_clipboard_slots(settings, text, &result).await;
+    Ok(())
+}
+
+/// Improve the selection sentence by sentence, typing each improved
+/// sentence as soon as it arrives rather than waiting for the whole rewrite.
+/// Results that aren't typed straight away are collected and output at the
+/// end. A sentence that fails to improve is output as it was, so a replaced
+/// selection loses nothing. With --chunk-parallelism, later sentences are
+/// requested while earlier ones are still in flight.
+async fn handle_sentences(
+    improver: &TextImprover,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    // A refinement of one sentence at a time has nothing to refine
+    let (input, _) = extract_refine(text);
+    // Each sentence with whether it starts a paragraph
+    let sentences: Vec<(bool, String)> = input
+        .split("\n\n")
+        .flat_map(|paragraph| {
+            chunk::sentences(paragraph)
+                .into_iter()
+                .enumerate()
+                .map(|(i, sentence)| (i == 0, sentence))
+        })
+        .collect();
+
+    let incremental = settings.output.types() && settings.read_aloud.is_none();
+    if incremental && !ready_to_type(settings, origin, &input).await {
+        return Ok(());
+    }
+    // The placeholder already took the selection's place
+    let mut mode = match settings.output {
+        OutputMode::Replace if settings.placeholder.is_some() => OutputMode::Type,
+        other => other,
+    };
+
+    let base = improver.fork();
+    let mut results = chunk::ordered(sentences, settings.chunk_parallelism, move |sentence| {
+        let mut improver = base.fork();
+        async move {
+            let (starts_paragraph, sentence) = sentence;
+            let result = improver.improve(&sentence, false).await;
+            (starts_paragraph, sentence, result)
+        }
+    });
+
+    let mut collected = String::new();
+    let mut first = true;
+    // Why typing stopped partway, if it did; the rest is collected instead
+    let mut refused = None;
+    while let Some((starts_paragraph, sentence, result)) = results.recv().await {
+        let improved = match result {
+            Ok(improved) if !improved.is_empty() => {
+                after_hook(settings, Mode::Sentences, origin, &sentence, improved)
+                    .unwrap_or(sentence)
+            }
+            Ok(_) => {
+                tracing::warn!("Ollama returned empty response, keeping the sentence");
+                sentence
+            }
+            Err(e) => {
+                tracing::error!("Failed to improve sentence, keeping it: {}", e);
+                sentence
+            }
+        };
+
+        // Newlines would submit forms when typed
+        let sep = match (first, starts_paragraph) {
+            (true, _) => "",
+            (false, true) if !incremental => "\n\n",
+            _ if incremental => "  ",
+            _ => " ",
+        };
+        first = false;
+        let piece = if incremental {
+            format!("{sep}{}", improved.replace('\n', "  "))
+        } else {
+            format!("{sep}{improved}")
+        };
+
+        // Focus, password fields, blocked windows and screen sharing can all
+        // change while the sentences come in
+        if incremental && refused.is_none() {
+            refused = refusal(settings, origin).await;
+        }
+        if incremental && refused.is_none() {
+            deliver(mode, &piece).await?;
+            mode = OutputMode::Type;
+        } else {
+            collected.push_str(&piece);
+        }
+    }
+
+    if let Some(refusal) = refused {
+        tracing::warn!("Stopped typing sentences partway ({:?})", refusal);
+        divert(refusal, collected.trim_start()).await;
+        return Ok(());
+    }
+    if incremental {
+        return Ok(());
+    }
+    output_result(settings, origin, &collected).await
+}
+
+/// Explain the selected shell command, leaving the selection as it is.
+async fn handle_explain_command(
+    improver: &TextImprover,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    let command = text.trim();
+    let explanation = improver.explain_command(command).await?;
+    if explanation.is_empty() {
+        tracing::warn!("Ollama returned empty response");
+        return Ok(());
+    }
+
+    tracing::debug!("Explanation: {:?}", explanation);
+
+    let Some(explanation) =
+        after_hook(settings, Mode::ExplainCommand, origin, command, explanation)
+    else {
+        return Ok(());
+    };
+    let output = settings.explanation_output;
+    if matches!(
+        output,
+        ExplanationOutput::Clipboard | ExplanationOutput::Both
+    ) {
+        copy_to_clipboard(&explanation).await?;
+    }
+    if matches!(output, ExplanationOutput::Notify | ExplanationOutput::Both)
+        && let Err(e) = notify::notify(command, &explanation).await
+    {
+        tracing::warn!("Failed to show explanation: {}", e);
+    }
+    Ok(())
+}
+
+/// Draft a reply to the selected email. Replies always go to the clipboard,
+/// to be pasted into the mail client's reply window.
+async fn handle_reply(
+    improver: &mut TextImprover,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    let (input, refine) = extract_refine(text);
+    let reply = improver.draft_reply(&input, refine).await?;
+    if reply.is_empty() {
+        tracing::warn!("Ollama returned empty response");
+        return Ok(());
+    }
+
+    tracing::debug!("Reply: {:?}", reply);
+
+    let Some(reply) = after_hook(settings, Mode::Reply, origin, &input, reply) else {
+        return Ok(());
+    };
+    copy_to_clipboard(&reply).await?;
+    if let Err(e) = notify::notify(&i18n::t("reply-drafted"), &i18n::t("reply-copied")).await {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+    Ok(())
+}
+
+/// Sample several rewrites at once and keep the best, by consensus or by
+/// asking the model to judge them.
+async fn best_of(improver: &TextImprover, text: &str, settings: &Settings) -> Result<String> {
+    let mut candidates = improver.improve_candidates(text, settings.best_of).await?;
+    if candidates.len() == 1 {
+        tracing::info!("All samples were identical");
+        return Ok(candidates.swap_remove(0));
+    }
+
+    let best = match settings.best_of_strategy {
+        Strategy::Consensus => select::consensus(&candidates),
+        Strategy::Judge => match improver.judge(text, &candidates).await {
+            Ok(best) => best,
+            Err(e) => {
+                tracing::warn!("Failed to judge samples, using consensus: {:#}", e);
+                select::consensus(&candidates)
+            }
+        },
+    };
+    tracing::debug!(
+        "Kept sample {} of {}: {:?}",
+        best + 1,
+        candidates.len(),
+        candidates[best]
+    );
+    Ok(candidates.swap_remove(best))
+}
+
+/// Improve with every compare model at once, then offer the results in a
+/// menu or copy them to the clipboard, labeled with their models.
+async fn handle_compare(
+    improver: &TextImprover,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    let mut results = Vec::new();
+    for (model, result) in improver
+        .improve_with_models(text, &settings.compare_models)
+        .await
+    {
+        match result {
+            Ok(improved) if !improved.is_empty() => results.push((model, improved)),
+            Ok(_) => tracing::warn!("{} returned empty response", model),
+            Err(e) => tracing::warn!("{} failed: {:#}", model, e),
+        }
+    }
+    if results.is_empty() {
+        anyhow::bail!("No model returned a result");
+    }
+    for (model, improved) in &results {
+        tracing::info!("{}: {:?}", model, improved);
+    }
+
+    if settings.compare_output == CompareOutput::Clipboard {
+        let labeled = results
+            .iter()
+            .map(|(model, improved)| format!("## {model}\n\n{improved}"))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        copy_to_clipboard(&labeled).await?;
+        let args = FluentArgs::from_iter([("count", results.len())]);
+        if let Err(e) = notify::notify(
+            &i18n::t("models-compared"),
+            &i18n::t_args("models-compared-copied", Some(&args)),
+        )
+        .await
+        {
+            tracing::warn!("Failed to show notification: {}", e);
+        }
+        return Ok(());
+    }
+
+    let options: Vec<String> = results
+        .iter()
+        .map(|(model, improved)| format!("{model}: {improved}"))
+        .collect();
+    let Some(chosen) = picker::pick(&options).await? else {
+        tracing::info!("No result picked");
+        return Ok(());
+    };
+    let (model, result) = results.swap_remove(chosen);
+    tracing::info!("Picked the result of {}", model);
+
+    let Some(result) = after_hook(settings, Mode::Compare, origin, text, result) else {
+        return Ok(());
+    };
+    let output = if settings.output.types() {
+        result.replace('\n', "  ")
+    } else {
+        result
+    };
+    output_result(settings, origin, &output).await
+}
+
+/// Report readability before and after improving. Returns false if the
+/// improvement should be discarded for being harder to read.
+async fn check_readability(original: &str, improved: &str, settings: &Settings) -> bool {
+    if settings.readability == ReadabilityReport::Off && !settings.require_readable {
+        return true;
+    }
+
+    let before = Readability::of(original);
+    let after = Readability::of(improved);
+    let report = metrics::report(&before, &after);
+    match settings.readability {
+        ReadabilityReport::Off => {}
+        ReadabilityReport::Log => tracing::info!("{}", report),
+        ReadabilityReport::Notify => {
+            if let Err(e) = notify::notify(&i18n::t("readability"), &report).await {
+                tracing::warn!("Failed to show readability report: {}", e);
+            }
+        }
+    }
+
+    if settings.require_readable && after.grade > before.grade {
+        tracing::warn!(
+            "Improved text is harder to read ({}) than the original ({}), keeping the original",
+            after,
+            before
+        );
+        return false;
+    }
+    true
+}
+
+/// Compare numbers, dates, and URLs before and after improving. Returns false
+/// if the improvement should be discarded for changing them.
+async fn check_facts(original: &str, improved: &str, settings: &Settings) -> bool {
+    if settings.check_facts == FactCheck::Off {
+        return true;
+    }
+
+    let changed = facts::changed(original, improved);
+    if changed.is_empty() {
+        return true;
+    }
+    let list = changed.join(", ");
+    if settings.check_facts == FactCheck::Reject {
+        tracing::warn!("Improved text changes {}, keeping the original", list);
+        return false;
+    }
+
+    tracing::warn!("Improved text changes {}", list);
+    let args = FluentArgs::from_iter([("list", list.as_str())]);
+    if let Err(e) = notify::notify(
+        &i18n::t("facts-changed"),
+        &i18n::t_args("facts-changed-check", Some(&args)),
+    )
+    .await
+    {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+    true
+}
+
+/// Fetch a page and summarize it, map-reduce style for long pages.
+async fn handle_url(
+    improver: &TextImprover,
+    url: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) -> Result<()> {
+    tracing::info!("Selection is a URL - fetching {}", url);
+    let text = web::fetch_readable_text(url).await?;
+    if text.is_empty() {
+        anyhow::bail!("No readable text found at {}", url);
+    }
+
+    let mut chunks = chunk::split(&text, SUMMARY_CHUNK_CHARS);
+    if chunks.len() > SUMMARY_MAX_CHUNKS {
+        tracing::warn!(
+            "Page has {} chunks, summarizing the first {}",
+            chunks.len(),
+            SUMMARY_MAX_CHUNKS
+        );
+        chunks.truncate(SUMMARY_MAX_CHUNKS);
+    }
+
+    let summary = if chunks.len() == 1 {
+        improver.summarize(&chunks[0]).await?
+    } else {
+        let count = chunks.len();
+        let base = improver.fork();
+        let mut results = chunk::ordered(chunks, settings.chunk_parallelism, move |chunk| {
+            let improver = base.fork();
+            async move { improver.summarize(&chunk).await }
+        });
+        let mut partials = Vec::new();
+        while let Some(partial) = results.recv().await {
+            tracing::debug!("Summarized chunk {}/{}", partials.len() + 1, count);
+            partials.push(partial?);
+        }
+        if partials.len() < count {
+            anyhow::bail!("Chunk {} of {} was lost", partials.len() + 1, count);
+        }
+        improver.summarize(&partials.join("\n\n")).await?
+    };
+
+    if summary.is_empty() {
+        tracing::warn!("Ollama returned empty response");
+        return Ok(());
+    }
+
+    tracing::debug!("Summary: {:?}", summary);
+
+    let output = if settings.output.types() {
+        summary.replace('\n', "  ")
+    } else {
+        summary
+    };
+    output_result(settings, origin, &output).await
+}
+
+/// Pause or resume the hotkeys, announcing the change.
+async fn set_paused(paused: &mut bool, pause: bool) {
+    if *paused == pause {
+        return;
+    }
+    *paused = pause;
+
+    let (summary, body) = if pause {
+        tracing::info!("Paused - hotkeys are disabled until resumed");
+        ("paused", "paused-detail")
+    } else {
+        tracing::info!("Resumed - hotkeys are enabled");
+        ("resumed", "resumed-detail")
+    };
+    if let Err(e) = notify::notify(&i18n::t(summary), &i18n::t(body)).await {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// Switch to `profile` and announce it.
+async fn switch_profile(improver: &mut TextImprover, profile: &UserProfile) -> Result<()> {
+    improver.apply_profile(profile)?;
+    if let Err(e) = notify::notify(&i18n::t("profile"), &profile.name).await {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+    Ok(())
+}
+
+/// Switch to the profile after the active one and announce it.
+async fn cycle_profile(improver: &mut TextImprover, profiles: &[UserProfile]) -> Result<()> {
+    if profiles.len() < 2 {
+        anyhow::bail!("No profiles to switch between (add [profiles.NAME] to the config file)");
+    }
+    let next = profiles
+        .iter()
+        .position(|profile| profile.name == improver.profile())
+        .map_or(0, |i| (i + 1) % profiles.len());
+    switch_profile(improver, &profiles[next]).await
+}
+
+/// Point the improver at the backend and model of the first route in the
+/// active profile that this request matches, or back at the profile's own.
+fn route_request(
+    improver: &mut TextImprover,
+    settings: &Settings,
+    app: Option<&str>,
+    action: &str,
+    text: &str,
+) {
+    let profiles = settings.profiles();
+    let route = profiles
+        .iter()
+        .find(|profile| profile.name == improver.profile())
+        .and_then(|profile| profile.route(app, action, text.chars().count()));
+    if let Some(route) = route {
+        tracing::info!("Routing {} ({})", action, route.describe());
+    }
+    improver.route(route);
+}
+
+/// Type the result waiting in the read-aloud flow, or drop it.
+async fn resolve_read_aloud(settings: &Settings, accept: bool) {
+    let Some(read_aloud) = &settings.read_aloud else {
+        tracing::warn!("The accept and reject hotkeys need --read-aloud");
+        return;
+    };
+    let Some(pending) = read_aloud.take().await else {
+        tracing::info!("No result waiting to be accepted");
+        say(&i18n::t("nothing-pending")).await;
+        return;
+    };
+
+    if accept {
+        tracing::info!("Result accepted");
+        if let Err(e) = output_now(settings, pending.origin.as_ref(), &pending.text).await {
+            tracing::error!("Failed to output text: {}", e);
+        }
+    } else {
+        tracing::info!("Result rejected");
+        say(&i18n::t("result-rejected")).await;
+    }
+}
+
+/// Speak a short confirmation and wait for it to finish.
+async fn say(text: &str) {
+    let result = match speech::speak(text) {
+        Ok(mut speaker) => speaker.wait().await.map(drop).map_err(anyhow::Error::from),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        tracing::warn!("Failed to speak: {}", e);
+    }
+}
+
+/// Forget the session's remembered improvements and announce it.
+async fn reset_session(improver: &mut TextImprover) {
+    improver.reset_session();
+    tracing::info!("Session memory cleared");
+    if let Err(e) =
+        notify::notify(&i18n::t("session-reset"), &i18n::t("session-reset-detail")).await
+    {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// Switch to the model after the current one in `models` (the first one if
+/// the current model isn't listed) and announce it.
+async fn cycle_model(improver: &mut TextImprover, models: &[String]) -> Result<String> {
+    if models.is_empty() {
+        anyhow::bail!("No models to cycle through (set --models)");
+    }
+    let next = models
+        .iter()
+        .position(|m| m == improver.model())
+        .map_or(0, |i| (i + 1) % models.len());
+    set_model(improver, &models[next]).await;
+    Ok(improver.model().to_string())
+}
+
+/// Switch to `model` and announce it.
+async fn set_model(improver: &mut TextImprover, model: &str) {
+    improver.set_model(model);
+
+    tracing::info!("Switched to model {}", improver.model());
+    if let Err(e) = notify::notify(&i18n::t("model"), improver.model()).await {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// Handle a request that arrived over the control socket.
+async fn handle_request(
+    request: Request,
+    improver: &mut TextImprover,
+    settings: &Settings,
+    paused: &mut bool,
+) -> Result<Value> {
+    match request {
+        Request::Improve { text, refine } => {
+            route_request(improver, settings, None, "improve", &text);
+            let improved = improver.improve(&text, refine).await;
+            improver.route(None);
+            Ok(json!({ "text": improved? }))
+        }
+        Request::GenerateCommand { text, refine } => {
+            route_request(improver, settings, None, "command", &text);
+            let generated = improver.generate_command(&text, refine).await;
+            improver.route(None);
+            let generated = generated?;
+            Ok(json!({ "text": generated.command, "explanation": generated.explanation }))
+        }
+        Request::Status => {
+            let mut status = control::version();
+            status["model"] = json!(improver.model());
+            status["paused"] = json!(*paused);
+            status["session"] = json!(improver.session_len());
+            status["profile"] = json!(improver.profile());
+            status["models"] = json!(settings.models);
+            Ok(status)
+        }
+        Request::Pause => {
+            set_paused(paused, true).await;
+            Ok(json!({ "paused": true }))
+        }
+        Request::Resume => {
+            set_paused(paused, false).await;
+            Ok(json!({ "paused": false }))
+        }
+        Request::SetProfile { name } => {
+            let profile = settings
+                .profiles()
+                .into_iter()
+                .find(|profile| profile.name == name)
+                .with_context(|| format!("No profile named {name:?}"))?;
+            switch_profile(improver, &profile).await?;
+            Ok(json!({ "profile": name }))
+        }
+        Request::ResetSession => {
+            reset_session(improver).await;
+            Ok(json!({ "session": 0 }))
+        }
+        Request::CycleModel => {
+            let model = cycle_model(improver, &settings.models).await?;
+            Ok(json!({ "model": model }))
+        }
+        Request::SetModel { name } => {
+            set_model(improver, &name).await;
+            Ok(json!({ "model": name }))
+        }
+        Request::Recent { limit } => {
+            let entries = match &settings.history {
+                Some(history) => history.recent(limit)?,
+                None => Vec::new(),
+            };
+            let entries: Vec<Value> = entries.iter().map(history_entry).collect();
+            Ok(json!({ "entries": entries }))
+        }
+        Request::History { query, limit } => {
+            let history = settings
+                .history
+                .as_ref()
+                .context("History is off (start with --history)")?;
+            let entries: Vec<Value> = history
+                .search(&query, limit)?
+                .iter()
+                .map(history_entry)
+                .collect();
+            Ok(json!({ "entries": entries }))
+        }
+        Request::Translate { text, language } => {
+            route_request(improver, settings, None, "translate", &text);
+            let translated = improver.translate(&text, &language).await;
+            improver.route(None);
+            Ok(json!({ "text": translated? }))
+        }
+        Request::Reload => reload_profiles(improver, settings).await,
+    }
+}
+
+/// A history entry as the `recent` and `history` methods return it.
+fn history_entry(entry: &history::Entry) -> Value {
+    json!({
+        "time": entry.time,
+        "action": entry.action,
+        "app": entry.app,
+        "original": entry.original,
+        "improved": entry.improved,
+    })
+}
+
+/// Read the config file's profiles again and re-apply the active one, or
+/// fall back to the default profile when it was removed. Other options only
+/// change on a restart.
+async fn reload_profiles(improver: &mut TextImprover, settings: &Settings) -> Result<Value> {
+    let loaded = profiles::load(settings.profile_base.clone())?;
+    let active = loaded
+        .iter()
+        .find(|profile| profile.name == improver.profile())
+        .unwrap_or(&loaded[0])
+        .clone();
+    improver.apply_profile(&active)?;
+    let names: Vec<String> = loaded.iter().map(|profile| profile.name.clone()).collect();
+    *settings
+        .profiles
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = loaded;
+    tracing::info!("Reloaded {} profiles", names.len());
+    Ok(json!({ "profiles": names, "profile": active.name }))
+}
+
+/// Whether an action on `text` is a one-shot request that leaves the
+/// conversation (and so REDO) alone: summarizing a URL, picking from or
+/// comparing rewrites, suggesting titles, and explaining a command.
+fn is_independent(mode: Mode, text: &str) -> bool {
+    match mode {
+        Mode::Improve | Mode::ImproveShowOriginal => web::is_url(text),
+        Mode::Pick | Mode::Title | Mode::Compare | Mode::ExplainCommand => true,
+        _ => false,
+    }
+}
+
+/// Run an action for which `is_independent` holds, logging any failure.
+async fn run_independent(
+    improver: &TextImprover,
+    mode: Mode,
+    text: &str,
+    origin: Option<&Window>,
+    settings: &Settings,
+) {
+    let (result, what) = match mode {
+        Mode::Pick => (
+            handle_pick(improver, text, origin, settings).await,
+            "pick a rewrite",
+        ),
+        Mode::Title => (
+            handle_title(improver, text, origin, settings).await,
+            "suggest titles",
+        ),
+        Mode::Compare => (
+            handle_compare(improver, text, origin, settings).await,
+            "compare models",
+        ),
+        Mode::ExplainCommand => (
+            handle_explain_command(improver, text, origin, settings).await,
+            "explain command",
+        ),
+        _ => (
+            handle_url(improver, text, origin, settings).await,
+            "summarize URL",
+        ),
+    };
+    if let Err(e) = result {
+        report_failure(what, &e).await;
+    }
+}
+
+/// Log a failed action. A timed-out request is also shown in a
+/// notification, since it is the one failure worth simply retrying.
+async fn report_failure(what: &str, e: &anyhow::Error) {
+    tracing::error!("Failed to {}: {}", what, e);
+    if e.downcast_ref::<InvalidCommandReply>().is_some() {
+        if let Err(e) = notify::notify(
+            &i18n::t("command-not-typed"),
+            &i18n::t("command-invalid-reply"),
+        )
+        .await
+        {
+            tracing::warn!("Failed to show failure: {}", e);
+        }
+        return;
+    }
+    let Some(timed_out) = e.downcast_ref::<TimedOut>() else {
+        return;
+    };
+    let args = FluentArgs::from_iter([("seconds", timed_out.timeout.as_secs_f64())]);
+    if let Err(e) = notify::notify(
+        &i18n::t("request-timed-out"),
+        &i18n::t_args("request-timed-out-detail", Some(&args)),
+    )
+    .await
+    {
+        tracing::warn!("Failed to show timeout: {}", e);
+    }
+}
+
+/// The clipboard text to use in place of an empty selection. The backup of
+/// the previous selection put there by the last hotkey press doesn't count.
+async fn clipboard_fallback(last: &Option<(Mode, String)>) -> Option<String> {
+    let text = match get_clipboard().await {
+        Ok(text) => text,
+        Err(e) => {
+            tracing::warn!("Failed to read the clipboard: {}", e);
+            return None;
+        }
+    };
+    let text = text.trim();
+    if text.is_empty() || last.as_ref().is_some_and(|(_, last)| last == text) {
+        return None;
+    }
+    tracing::info!("No text selected - using the clipboard");
+    Some(text.to_string())
+}
+
+/// The clipboard text before a hotkey press, for --preserve-clipboard.
+async fn save_clipboard() -> Option<String> {
+    match get_clipboard().await {
+        Ok(text) => Some(text),
+        Err(e) => {
+            tracing::warn!("Failed to read the clipboard, it won't be restored: {}", e);
+            None
+        }
+    }
+}
+
+/// Put `saved` back on the clipboard if reading the selection replaced it
+/// (macOS copies the selection to read it). Anything else on the clipboard
+/// was put there on purpose, e.g. a result or a drafted reply, and stays.
+async fn restore_clipboard(saved: String, last: &Option<(Mode, String)>) {
+    let current = match get_clipboard().await {
+        Ok(current) => current,
+        Err(e) => {
+            tracing::warn!("Failed to read the clipboard: {}", e);
+            return;
+        }
+    };
+    if current == saved {
+        return;
+    }
+    if !last
+        .as_ref()
+        .is_some_and(|(_, selected)| selected == current.trim())
+    {
+        tracing::debug!("Clipboard holds new content, not restoring it");
+        return;
+    }
+    match copy_to_clipboard(&saved).await {
+        Ok(()) => tracing::debug!("Clipboard restored"),
+        Err(e) => tracing::warn!("Failed to restore the clipboard: {}", e),
+    }
+}
+
+/// How long pressing a hotkey again confirms sending the same selection.
+const REPEAT_CONFIRM_WINDOW: Duration = Duration::from_secs(5);
+
+/// The last selection sent, hashed with its action, to catch a hotkey
+/// pressed twice on text that has already been improved.
+#[derive(Default)]
+struct LastSelection {
+    hash: Option<u64>,
+    /// When sending it again was last asked about
+    asked: Option<Instant>,
+}
+
+impl LastSelection {
+    /// Whether to send `text`: new text is sent straight away, the same
+    /// text again only when a second press confirms it.
+    async fn confirm(&mut self, mode: Mode, text: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        (mode.name(), text).hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.hash != Some(hash) {
+            *self = Self {
+                hash: Some(hash),
+                asked: None,
+            };
+            return true;
+        }
+        if self
+            .asked
+            .take()
+            .is_some_and(|asked| asked.elapsed() < REPEAT_CONFIRM_WINDOW)
+        {
+            tracing::info!("Sending the same selection again, as confirmed");
+            return true;
+        }
+
+        tracing::warn!(
+            "Selection unchanged since the last {} press - press again to send it anyway",
+            mode.name()
+        );
+        self.asked = Some(Instant::now());
+        let args = FluentArgs::from_iter([("seconds", REPEAT_CONFIRM_WINDOW.as_secs())]);
+        if let Err(e) = notify::notify(
+            &i18n::t("same-selection"),
+            &i18n::t_args("same-selection-detail", Some(&args)),
+        )
+        .await
+        {
+            tracing::warn!("Failed to ask about the same selection: {}", e);
+        }
+        false
+    }
+}
+
+/// What the run loop keeps from one press to the next.
+#[derive(Default)]
+struct PressState {
+    /// The last action run on text, and the text before any script hook
+    last: Option<(Mode, String)>,
+    /// For --confirm-repeats
+    selection: LastSelection,
+}
+
+/// Handle a hotkey action on the selection (or OCR text, or the text being
+/// repeated) and deliver the result.
+async fn handle_hotkey(
+    improver: &mut TextImprover,
+    settings: &Arc<Settings>,
+    background: &Arc<Semaphore>,
+    mode: Mode,
+    repeated: Option<String>,
+    origin: Option<&Window>,
+    state: &mut PressState,
+) {
+    let output_mode = settings.output;
+
+    tracing::info!("Hotkey pressed - getting selection...");
+    let from_selection = repeated.is_none() && !matches!(mode, Mode::Ocr);
+
+    // Get highlighted text, or OCR a screen region
+    let selection = match (repeated, mode) {
+        (Some(text), _) => Ok(text),
+        (None, Mode::Ocr) => {
+            screenshot::capture_text()
+                .instrument(tracing::info_span!("selection", source = "ocr"))
+                .await
+        }
+        (None, _) => {
+            get_primary_selection()
+                .instrument(tracing::info_span!("selection", source = "primary"))
+                .await
+        }
+    };
+
+    match selection {
+        Ok(selected) => {
+            let selected = match selected.trim() {
+                "" if settings.fallback_to_clipboard && from_selection => {
+                    match clipboard_fallback(&state.last).await {
+                        Some(text) => text,
+                        None => {
+                            tracing::warn!("No text selected, and nothing new on the clipboard");
+                            return;
                         }
+                    }
+                }
+                "" => {
+                    tracing::warn!("No text selected");
+                    return;
+                }
+                selected => selected.to_string(),
+            };
+            let selected = selected.as_str();
+            if settings.confirm_repeats
+                && from_selection
+                && !state.selection.confirm(mode, selected).await
+            {
+                return;
+            }
+
+            tracing::debug!("Selected text: {:?}", selected);
+            state.last = Some((mode, selected.to_string()));
+
+            // Copy original text to clipboard as backup
+            if settings.preserve_clipboard {
+                tracing::debug!("Preserving the clipboard, no backup of the original");
+            } else if let Err(e) = copy_to_clipboard(selected).await {
+                tracing::warn!("Failed to copy original to clipboard: {}", e);
+            } else {
+                tracing::debug!("Original text copied to clipboard");
+            }
+
+            let Some(text) = before_hook(settings, improver, mode, origin, selected) else {
+                return;
+            };
+            let text = text.as_str();
+            route_request(improver, settings, app_name(origin), mode.name(), text);
+
+            // One-shot actions leave the conversation alone, so they can run
+            // in the background while other hotkeys are handled. Each types
+            // into the window its own hotkey was pressed in.
+            if is_independent(mode, text)
+                && let Ok(permit) = background.clone().try_acquire_owned()
+            {
+                tracing::debug!("Running {} in the background", mode.name());
+                let improver = improver.fork();
+                let settings = settings.clone();
+                let origin = origin.cloned();
+                let text = text.to_string();
+                tokio::spawn(
+                    async move {
+                        let _permit = permit;
+                        let _busy = settings.osd.as_ref().map(Osd::busy);
+                        run_independent(&improver, mode, &text, origin.as_ref(), &settings).await;
+                    }
+                    .in_current_span(),
+                );
+                return;
+            }
 
-                        log::debug!("Selected text: {:?}", text);
+            if output_mode.types()
+                && !matches!(mode, Mode::Reply | Mode::ExplainCommand)
+                && !(matches!(mode, Mode::Compare)
+                    && settings.compare_output == CompareOutput::Clipboard)
+                && let Some(placeholder) = &settings.placeholder
+            {
+                placeholder.show().await;
+            }
 
-                        // Copy original text to clipboard as backup
-                        if let Err(e) = copy_to_clipboard(text).await {
-                            log::warn!("Failed to copy original to clipboard: {}", e);
-                        } else {
-                            log::debug!("Original text copied to clipboard");
+            match mode {
+                _ if is_independent(mode, text) => {
+                    run_independent(improver, mode, text, origin, settings).await;
+                }
+                Mode::Improve | Mode::ImproveShowOriginal | Mode::Ocr => {
+                    let show_original = matches!(mode, Mode::ImproveShowOriginal);
+                    let (input, refine) = if show_original {
+                        (text.to_string(), false)
+                    } else {
+                        extract_refine(text)
+                    };
+
+                    let critic = match settings.critic {
+                        CriticPass::Off => false,
+                        CriticPass::ShowOriginal => show_original,
+                        CriticPass::Always => true,
+                    };
+                    let result = match recorded(settings, mode, &input, refine) {
+                        Some(improved) => Ok(improved),
+                        None => {
+                            let first_pass = match &settings.pipeline {
+                                Some(pipeline) => pipeline.run(improver, &input, refine).await,
+                                None if show_original && settings.best_of > 1 => {
+                                    best_of(improver, &input, settings).await
+                                }
+                                None => improver.improve(&input, refine).await,
+                            };
+                            match first_pass {
+                                Ok(draft) if critic && !draft.is_empty() => {
+                                    tracing::debug!("First pass: {:?}", draft);
+                                    improver.critique(&input, &draft).await
+                                }
+                                result => result,
+                            }
                         }
+                    };
+
+                    match result {
+                        Ok(improved) => {
+                            if improved.is_empty() {
+                                tracing::warn!("Ollama returned empty response");
+                                return;
+                            }
+
+                            tracing::debug!("Improved text: {:?}", improved);
+
+                            if !check_readability(&input, &improved, settings).await {
+                                return;
+                            }
+                            if !check_facts(&input, &improved, settings).await {
+                                return;
+                            }
 
-                        match mode {
-                            Mode::Improve | Mode::ImproveShowOriginal => {
-                                let show_original = matches!(mode, Mode::ImproveShowOriginal);
-                                let (input, refine) = if show_original {
-                                    (text.to_string(), false)
+                            let Some(improved) =
+                                after_hook(settings, mode, origin, &input, improved)
+                            else {
+                                return;
+                            };
+                            // Without change markup, for the clipboard slots
+                            let plain = improved.clone();
+
+                            let improved = if show_original {
+                                improved
+                            } else {
+                                diff::markup(&input, &improved, settings.changes)
+                            };
+
+                            // Newlines would submit forms when typed; the
+                            // clipboard keeps them
+                            let flatten = |s: &str| {
+                                if output_mode.types() {
+                                    s.replace('\n', "  ")
                                 } else {
-                                    extract_refine(text)
-                                };
-
-                                match improver.improve(&input, refine).await {
-                                    Ok(improved) => {
-                                        if improved.is_empty() {
-                                            log::warn!("Ollama returned empty response");
-                                            continue;
-                                        }
+                                    s.to_string()
+                                }
+                            };
+                            let output = if show_original {
+                                format!("{} | {}", flatten(text), flatten(&improved))
+                            } else {
+                                flatten(&improved)
+                            };
 
-                                        log::debug!("Improved text: {:?}", improved);
+                            match output_result(settings, origin, &output).await {
+                                Ok(()) => fill_clipboard_slots(settings, text, &plain).await,
+                                Err(e) => tracing::error!("Failed to output text: {}", e),
+                            }
+                        }
+                        Err(e) => report_failure("improve text", &e).await,
+                    }
+                }
+                Mode::Sentences => {
+                    if let Err(e) = handle_sentences(improver, text, origin, settings).await {
+                        report_failure("improve sentences", &e).await;
+                    }
+                }
+                Mode::Reply => {
+                    if let Err(e) = handle_reply(improver, text, origin, settings).await {
+                        report_failure("draft a reply", &e).await;
+                    }
+                }
+                Mode::Shorten | Mode::Expand | Mode::Comment | Mode::Rewrite(_) | Mode::Voice => {
+                    if let Err(e) = handle_rewrite(improver, mode, text, origin, settings).await {
+                        report_failure("rewrite text", &e).await;
+                    }
+                }
+                Mode::Image
+                | Mode::Pause
+                | Mode::CycleModel
+                | Mode::ResetSession
+                | Mode::CycleProfile
+                | Mode::Accept
+                | Mode::Reject
+                | Mode::Repeat
+                | Mode::Pick
+                | Mode::Title
+                | Mode::Compare
+                | Mode::ExplainCommand => {
+                    tracing::warn!("{mode:?} does not act on selected text");
+                }
+                Mode::ShellCommand => {
+                    // Commands are one line, typed rather than edited
+                    let output_mode = match output_mode {
+                        OutputMode::Editor => OutputMode::Type,
+                        other => other,
+                    };
+                    let (input, refine) = extract_refine(text);
+                    match improver.generate_command(&input, refine).await {
+                        Ok(generated) => {
+                            let command = generated.command;
+                            if command.is_empty() {
+                                tracing::warn!("Ollama returned empty response");
+                                return;
+                            }
 
-                                        let improved_clean = improved.replace('\n', "  ");
-                                        let output = if show_original {
-                                            let text_clean = text.replace('\n', "  ");
-                                            format!("{} | {}", text_clean, improved_clean)
-                                        } else {
-                                            improved_clean
-                                        };
+                            tracing::debug!("Generated command: {:?}", command);
 
-                                        if let Err(e) = type_text(&output).await {
-                                            log::error!("Failed to type text: {}", e);
-                                        }
-                                    }
+                            let Some(command) = after_hook(settings, mode, origin, &input, command)
+                            else {
+                                return;
+                            };
+
+                            let dangers = danger::check(&command);
+                            let warning = (!dangers.is_empty()).then(|| {
+                                tracing::warn!("Generated command {}", dangers.join(", "));
+                                let args = FluentArgs::from_iter([("reasons", dangers.join(", "))]);
+                                i18n::t_args("command-dangerous", Some(&args))
+                            });
+                            let review = if settings.review_commands {
+                                match improver.review_command(&command).await {
+                                    Ok(review) => Some(review),
                                     Err(e) => {
-                                        log::error!("Failed to improve text: {}", e);
+                                        tracing::warn!("Failed to review command: {}", e);
+                                        None
                                     }
                                 }
-                            }
-                            Mode::ShellCommand => {
-                                let (input, refine) = extract_refine(text);
-                                match improver.generate_command(&input, refine).await {
-                                    Ok(command) => {
-                                        if command.is_empty() {
-                                            log::warn!("Ollama returned empty response");
-                                            continue;
-                                        }
+                            } else {
+                                None
+                            };
+                            let notes: Vec<String> = [warning, review, generated.explanation]
+                                .into_iter()
+                                .flatten()
+                                .filter(|note| !note.is_empty())
+                                .collect();
+                            let explanation = (!notes.is_empty()).then(|| notes.join("\n\n"));
 
-                                        log::debug!("Generated command: {:?}", command);
-
-                                        if let Err(e) = clear_line().await {
-                                            log::error!("Failed to clear line: {}", e);
-                                        }
+                            if settings.execute_commands {
+                                let explanation = explanation.as_deref();
+                                match execute::confirm(&command, explanation).await {
+                                    Ok(true) => {
+                                        tokio::spawn(async move {
+                                            if let Err(e) = execute::run(&command).await {
+                                                tracing::error!("Failed to run command: {}", e);
+                                            }
+                                        });
+                                    }
+                                    Ok(false) => tracing::info!("Generated command not run"),
+                                    Err(e) => tracing::error!("Failed to confirm command: {}", e),
+                                }
+                                return;
+                            }
 
-                                        if let Err(e) = type_text(&command).await {
-                                            log::error!("Failed to type command: {}", e);
+                            if let Some(shell) = settings.command_history {
+                                match shell::append_history(shell, &command) {
+                                    Ok(_) => {
+                                        let args = FluentArgs::from_iter([("shell", shell.name())]);
+                                        let summary =
+                                            i18n::t_args("command-added-to-history", Some(&args));
+                                        let body = [command.clone()]
+                                            .into_iter()
+                                            .chain(notes)
+                                            .collect::<Vec<_>>()
+                                            .join("\n\n");
+                                        if let Err(e) = notify::notify(&summary, &body).await {
+                                            tracing::warn!("Failed to show notification: {}", e);
                                         }
                                     }
                                     Err(e) => {
-                                        log::error!("Failed to generate command: {}", e);
+                                        tracing::error!("Failed to add command to history: {}", e)
                                     }
                                 }
+                                return;
+                            }
+
+                            if !dangers.is_empty()
+                                && output_mode.types()
+                                && !settings.allow_dangerous_commands
+                            {
+                                tracing::warn!(
+                                    "Not typing a destructive command, copying it instead"
+                                );
+                                if let Err(e) = copy_to_clipboard(&command).await {
+                                    tracing::error!("Failed to copy command to clipboard: {}", e);
+                                }
+                                let body = format!(
+                                    "{command}\n\n{}\n\n{}",
+                                    notes.join("\n\n"),
+                                    i18n::t("command-copied")
+                                );
+                                if let Err(e) =
+                                    notify::notify(&i18n::t("command-not-typed"), &body).await
+                                {
+                                    tracing::warn!("Failed to show notification: {}", e);
+                                }
+                                return;
+                            }
+
+                            if output_mode.types() {
+                                if !ready_to_type(settings, origin, &command).await {
+                                    return;
+                                }
+                                if let Err(e) = clear_line().await {
+                                    tracing::error!("Failed to clear line: {}", e);
+                                }
+                            }
+
+                            // Clearing the line already removed the selection
+                            let command_output = match output_mode {
+                                OutputMode::Replace => OutputMode::Type,
+                                other => other,
+                            };
+                            if let Err(e) = deliver(command_output, &command).await {
+                                tracing::error!("Failed to output command: {}", e);
+                            }
+
+                            if let Some(explanation) = explanation
+                                && let Err(e) = notify::notify(&command, &explanation).await
+                            {
+                                tracing::warn!("Failed to show explanation: {}", e);
                             }
                         }
+                        Err(e) => report_failure("generate command", &e).await,
                     }
-                    Err(e) => {
-                        log::error!("Failed to get selection: {}", e);
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to get selection: {}", e);
+        }
+    }
+}
+
+pub async fn run_event_loop(
+    presses: Option<Arc<PressQueue>>,
+    modes: Vec<Mode>,
+    mut improver: TextImprover,
+    settings: Settings,
+    mut requests: mpsc::Receiver<Envelope>,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    let output_mode = settings.output;
+    // Shared with actions running in the background
+    let settings = Arc::new(settings);
+    let background = Arc::new(Semaphore::new(settings.concurrency.saturating_sub(1)));
+    let mut paused = false;
+    let mut state = PressState::default();
+
+    tracing::info!("Listening for hotkey... Press Ctrl+C to exit.");
+
+    while running.load(Acquire) {
+        // Erase a placeholder left by a request that produced no output
+        if let Some(placeholder) = &settings.placeholder {
+            placeholder.erase().await;
+        }
+
+        // Surface reasoning from the previous request, if any
+        if let Some(reasoning) = improver.take_reasoning() {
+            match settings.reasoning {
+                ReasoningOutput::Off => {}
+                ReasoningOutput::Log => tracing::info!("Model reasoning:\n{}", reasoning),
+                ReasoningOutput::Clipboard => {
+                    if let Err(e) = copy_to_clipboard(&reasoning).await {
+                        tracing::warn!("Failed to copy reasoning to clipboard: {}", e);
                     }
                 }
             }
+        }
+
+        // Serve any pending control socket requests
+        while let Ok(envelope) = requests.try_recv() {
+            tracing::debug!("Control request: {:?}", envelope.request);
+            let result =
+                handle_request(envelope.request, &mut improver, &settings, &mut paused).await;
+            let _ = envelope.reply.send(result);
+        }
+
+        // Check for hotkey events, or just wait for requests when headless
+        let Some(presses) = &presses else {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            continue;
+        };
+        match presses.recv_timeout(Duration::from_millis(100)) {
+            Ok(i) => {
+                let mode = match modes.get(i) {
+                    Some(mode) => *mode,
+                    None => continue,
+                };
+
+                if let Mode::Pause = mode {
+                    let pause = !paused;
+                    set_paused(&mut paused, pause).await;
+                    continue;
+                }
+                if paused {
+                    tracing::debug!("Paused - ignoring hotkey");
+                    continue;
+                }
+                if let Mode::CycleProfile = mode {
+                    if let Err(e) = cycle_profile(&mut improver, &settings.profiles()).await {
+                        tracing::error!("Failed to switch profile: {}", e);
+                    }
+                    continue;
+                }
+                if let Mode::Accept | Mode::Reject = mode {
+                    resolve_read_aloud(&settings, matches!(mode, Mode::Accept)).await;
+                    continue;
+                }
+                if let Mode::ResetSession = mode {
+                    reset_session(&mut improver).await;
+                    continue;
+                }
+                if let Mode::CycleModel = mode {
+                    if let Err(e) = cycle_model(&mut improver, &settings.models).await {
+                        tracing::error!("Failed to switch model: {}", e);
+                    }
+                    continue;
+                }
+
+                // The repeat hotkey replays the last action without reading
+                // the selection, e.g. after a request failed
+                let (mode, repeated) = match mode {
+                    Mode::Repeat => match &state.last {
+                        Some((mode, text)) => {
+                            tracing::info!("Repeating the last {} action", mode.name());
+                            (*mode, Some(text.clone()))
+                        }
+                        None => {
+                            tracing::warn!("Nothing to repeat yet");
+                            continue;
+                        }
+                    },
+                    mode => (mode, None),
+                };
+
+                if let Err(reason) = settings.throttle.check(mode.name()) {
+                    tracing::warn!("Ignoring {} hotkey: {}", mode.name(), reason);
+                    continue;
+                }
+
+                let _busy = settings.osd.as_ref().map(Osd::busy);
+
+                // Remember where the hotkey was pressed, for the focus check
+                // and script hooks
+                let origin = if (settings.check_focus && output_mode.types_eventually())
+                    || settings.script.is_some()
+                    || !settings.blocklist.is_empty()
+                    || settings
+                        .profiles()
+                        .iter()
+                        .any(|profile| !profile.routes.is_empty())
+                {
+                    window::focused().await
+                } else {
+                    None
+                };
+                let origin = origin.as_ref();
+                if let Some(window) = origin
+                    && in_blocked_window(&settings, Some(window)).await
+                {
+                    continue;
+                }
+
+                // Parent of the selection, backend and typing spans
+                let span = tracing::info_span!("hotkey", action = mode.name());
+
+                if let Mode::Image = mode {
+                    tracing::info!("Hotkey pressed - capturing image...");
+                    if let Ok(permit) = background.clone().try_acquire_owned() {
+                        let improver = improver.fork();
+                        let settings = settings.clone();
+                        let origin = origin.cloned();
+                        tokio::spawn(
+                            async move {
+                                let _permit = permit;
+                                let _busy = settings.osd.as_ref().map(Osd::busy);
+                                if let Err(e) =
+                                    handle_image(&improver, origin.as_ref(), &settings).await
+                                {
+                                    report_failure("run image action", &e).await;
+                                }
+                            }
+                            .instrument(span),
+                        );
+                    } else if let Err(e) = handle_image(&improver, origin, &settings)
+                        .instrument(span)
+                        .await
+                    {
+                        report_failure("run image action", &e).await;
+                    }
+                    continue;
+                }
+
+                let saved_clipboard = if settings.preserve_clipboard {
+                    save_clipboard().await
+                } else {
+                    None
+                };
+                // A script's instructions are for this press only
+                let instructions = improver.instructions().map(str::to_string);
+                handle_hotkey(
+                    &mut improver,
+                    &settings,
+                    &background,
+                    mode,
+                    repeated,
+                    origin,
+                    &mut state,
+                )
+                .instrument(span)
+                .await;
+                improver.set_instructions(instructions);
+                improver.route(None);
+                if let Some(saved) = saved_clipboard {
+                    restore_clipboard(saved, &state.last).await;
+                }
+            }
             Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
                 // No event, continue loop
             }
             Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                log::debug!("Keyboard listener disconnected");
+                tracing::debug!("Keyboard listener disconnected");
                 break;
             }
         }
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use fluent_bundle::FluentArgs;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::{i18n, notify, picker};
+
+/// Commands still running after this are killed.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How much of the end of a command's output the notification shows.
+const OUTPUT_CHARS: usize = 400;
+
+/// Ask before running a generated command. Nothing runs unless "Run" is
+/// chosen explicitly; dismissing the dialog cancels.
+pub async fn confirm(command: &str, explanation: Option<&str>) -> Result<bool> {
+    let detail = match explanation {
+        Some(explanation) => format!("{command}\n\n{explanation}"),
+        None => command.to_string(),
+    };
+    picker::confirm(
+        &i18n::t("command-confirm"),
+        &detail,
+        &i18n::t("command-run"),
+    )
+    .await
+}
+
+/// Run `command` in the user's shell from the home directory and report its
+/// exit status and output in a notification.
+pub async fn run(command: &str) -> Result<()> {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+    tracing::info!("Running generated command with {}: {:?}", shell, command);
+
+    let mut cmd = Command::new(&shell);
+    cmd.arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(home) = std::env::var_os("HOME") {
+        cmd.current_dir(home);
+    }
+    let child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to run {shell}"))?;
+
+    let (summary, output) = match tokio::time::timeout(TIMEOUT, child.wait_with_output()).await {
+        Ok(output) => {
+            let output = output.context("Failed to wait for the command")?;
+            let summary = match output.status.code() {
+                Some(0) => i18n::t("command-succeeded"),
+                Some(code) => {
+                    let args = FluentArgs::from_iter([("code", code)]);
+                    i18n::t_args("command-failed", Some(&args))
+                }
+                None => i18n::t("command-killed"),
+            };
+            tracing::info!("Generated command finished: {}", output.status);
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            (summary, text)
+        }
+        Err(_) => {
+            tracing::warn!("Generated command timed out after {:?}", TIMEOUT);
+            let args = FluentArgs::from_iter([("seconds", TIMEOUT.as_secs())]);
+            (
+                i18n::t_args("command-timed-out", Some(&args)),
+                String::new(),
+            )
+        }
+    };
+
+    let body = tail(output.trim());
+    let body = if body.is_empty() {
+        i18n::t("command-no-output")
+    } else {
+        body
+    };
+    notify::notify(&summary, &body).await
+}
+
+/// The last `OUTPUT_CHARS` characters of `text`.
+fn tail(text: &str) -> String {
+    let count = text.chars().count();
+    if count <= OUTPUT_CHARS {
+        return text.to_string();
+    }
+    let rest: String = text.chars().skip(count - OUTPUT_CHARS).collect();
+    format!("…{rest}")
+}
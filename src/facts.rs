@@ -0,0 +1,74 @@
+/// What to do when the improved text changes a number, date, or URL from the
+/// original.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum FactCheck {
+    /// Don't check
+    Off,
+    /// Log and notify, but output the improved text anyway
+    Warn,
+    /// Keep the original text
+    Reject,
+}
+
+/// Where a URL starts inside a word, e.g. in a Markdown link.
+const URL_PREFIXES: [&str; 3] = ["https://", "http://", "www."];
+
+/// Punctuation that ends a sentence or closes a bracket after a URL.
+const URL_TRAILING: &[char] = &[
+    '.', ',', ';', ':', '!', '?', ')', ']', '>', '"', '\'', '`', '*',
+];
+
+/// Characters that join digits into one number: `1.2.3`, `2024-01-05`,
+/// `10:30`, `1,000`, `3/4`.
+const NUMBER_SEPARATORS: [char; 5] = ['.', ',', ':', '/', '-'];
+
+/// Numbers, dates, and URLs in `original` that `improved` no longer contains
+/// unchanged, each listed once.
+pub fn changed(original: &str, improved: &str) -> Vec<String> {
+    let kept = facts(improved);
+    let mut changed = Vec::new();
+    for fact in facts(original) {
+        if !kept.contains(&fact) && !changed.contains(&fact) {
+            changed.push(fact);
+        }
+    }
+    changed
+}
+
+/// Every number (with its separators) and URL in `text`, in order.
+fn facts(text: &str) -> Vec<String> {
+    let mut facts = Vec::new();
+    for word in text.split_whitespace() {
+        let url_start = URL_PREFIXES.iter().filter_map(|p| word.find(p)).min();
+        let (before, url) = match url_start {
+            Some(i) => (&word[..i], Some(&word[i..])),
+            None => (word, None),
+        };
+        numbers(before, &mut facts);
+        if let Some(url) = url {
+            facts.push(url.trim_end_matches(URL_TRAILING).to_string());
+        }
+    }
+    facts
+}
+
+/// Push the runs of digits in `word`, keeping separators between digits.
+fn numbers(word: &str, facts: &mut Vec<String>) {
+    let chars: Vec<char> = word.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len()
+            && (chars[i].is_ascii_digit()
+                || (NUMBER_SEPARATORS.contains(&chars[i])
+                    && chars.get(i + 1).is_some_and(char::is_ascii_digit)))
+        {
+            i += 1;
+        }
+        facts.push(chars[start..i].iter().collect());
+    }
+}
@@ -0,0 +1,272 @@
+use anyhow::{Context, Result};
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+/// What to do with profanity and injected personal data in a response.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFilter {
+    /// Output responses as they are
+    #[default]
+    Off,
+    /// Replace matches with asterisks or `[redacted]`
+    Mask,
+    /// Fail the request, so nothing is typed or copied
+    Block,
+}
+
+impl FromStr for OutputFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "off" => Ok(OutputFilter::Off),
+            "mask" => Ok(OutputFilter::Mask),
+            "block" => Ok(OutputFilter::Block),
+            _ => anyhow::bail!("filter must be off, mask or block"),
+        }
+    }
+}
+
+/// Built-in profanity, matched as whole words (case-insensitively) along with
+/// their common inflections.
+const PROFANITY: &[&str] = &[
+    "arse",
+    "arsehole",
+    "asshole",
+    "bastard",
+    "bitch",
+    "bollocks",
+    "bullshit",
+    "cock",
+    "crap",
+    "cunt",
+    "damn",
+    "dick",
+    "fuck",
+    "motherfucker",
+    "piss",
+    "prick",
+    "shit",
+    "twat",
+    "wank",
+    "wanker",
+];
+
+const INFLECTIONS: [&str; 7] = ["", "s", "es", "ed", "er", "ers", "ing"];
+
+/// Replaces personal data when masking.
+const REDACTED: &str = "[redacted]";
+
+/// Read extra words to filter, one per line; blank lines and lines starting
+/// with `#` are skipped.
+pub fn load_words(path: &Path) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read filter words {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Profanity,
+    Email,
+    Phone,
+    Card,
+}
+
+impl Kind {
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::Profanity => "profanity",
+            Kind::Email => "an email address",
+            Kind::Phone => "a phone number",
+            Kind::Card => "a card number",
+        }
+    }
+}
+
+/// Filter a response. Personal data (email addresses, phone and card
+/// numbers) only counts when it isn't in `known`, the text the model was
+/// given, so addresses from the selection survive; profanity always counts.
+pub fn apply(filter: OutputFilter, words: &[String], known: &str, text: &str) -> Result<String> {
+    if filter == OutputFilter::Off {
+        return Ok(text.to_string());
+    }
+
+    let mut matches = Vec::new();
+    find_emails(text, known, &mut matches);
+    find_numbers(text, known, &mut matches);
+    find_profanity(text, words, &mut matches);
+    if matches.is_empty() {
+        return Ok(text.to_string());
+    }
+
+    let mut kinds: Vec<&str> = Vec::new();
+    for (_, kind) in &matches {
+        if !kinds.contains(&kind.describe()) {
+            kinds.push(kind.describe());
+        }
+    }
+    if filter == OutputFilter::Block {
+        anyhow::bail!(
+            "Response blocked by the output filter: {}",
+            kinds.join(", ")
+        );
+    }
+    tracing::warn!("Masking {} in the response", kinds.join(", "));
+    Ok(mask(text, matches))
+}
+
+/// Replace every email address, phone and card number in `text` with
+/// `[redacted]`, e.g. before the text is sent to a model.
+pub fn redact(text: &str) -> String {
+    let mut matches = Vec::new();
+    find_emails(text, "", &mut matches);
+    find_numbers(text, "", &mut matches);
+    mask(text, matches)
+}
+
+/// Star out profanity and replace personal data in `matches`.
+fn mask(text: &str, mut matches: Vec<(Range<usize>, Kind)>) -> String {
+    matches.sort_by_key(|(range, _)| range.start);
+    let mut masked = String::new();
+    let mut end = 0;
+    for (range, kind) in matches {
+        if range.start < end {
+            continue;
+        }
+        masked.push_str(&text[end..range.start]);
+        if kind == Kind::Profanity {
+            masked.push_str(&mask_word(&text[range.clone()]));
+        } else {
+            masked.push_str(REDACTED);
+        }
+        end = range.end;
+    }
+    masked.push_str(&text[end..]);
+    masked
+}
+
+/// Keep the first letter of a word and star out the rest.
+fn mask_word(word: &str) -> String {
+    let mut chars = word.chars();
+    let first = chars.next().map(String::from).unwrap_or_default();
+    first + &"*".repeat(chars.count())
+}
+
+/// Whitespace-separated tokens with surrounding punctuation trimmed, and
+/// their byte ranges in `text`.
+fn tokens(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    text.split_whitespace().filter_map(move |token| {
+        let trimmed = token.trim_matches(|c: char| !c.is_alphanumeric());
+        if trimmed.is_empty() {
+            return None;
+        }
+        let start = trimmed.as_ptr() as usize - text.as_ptr() as usize;
+        Some((start..start + trimmed.len(), trimmed))
+    })
+}
+
+fn find_emails(text: &str, known: &str, matches: &mut Vec<(Range<usize>, Kind)>) {
+    let known = known.to_lowercase();
+    for (range, token) in tokens(text) {
+        let Some((user, domain)) = token.split_once('@') else {
+            continue;
+        };
+        let is_email = !user.is_empty()
+            && domain
+                .split_once('.')
+                .is_some_and(|(host, tld)| !host.is_empty() && !tld.is_empty());
+        if is_email && !known.contains(&token.to_lowercase()) {
+            matches.push((range, Kind::Email));
+        }
+    }
+}
+
+/// Phone numbers (9 to 15 digits) and card numbers (13 to 19 digits passing
+/// the Luhn check), allowing spaces, dashes, dots, and brackets between the
+/// digits.
+fn find_numbers(text: &str, known: &str, matches: &mut Vec<(Range<usize>, Kind)>) {
+    let known_digits: Vec<String> = number_runs(known)
+        .into_iter()
+        .map(|(_, digits)| digits)
+        .collect();
+    for (range, digits) in number_runs(text) {
+        if known_digits.iter().any(|known| known.contains(&digits)) {
+            continue;
+        }
+        let kind = if (13..=19).contains(&digits.len()) && luhn(&digits) {
+            Kind::Card
+        } else if (9..=15).contains(&digits.len()) {
+            Kind::Phone
+        } else {
+            continue;
+        };
+        matches.push((range, kind));
+    }
+}
+
+/// Runs of digits and number punctuation, with their byte ranges (from a
+/// leading `+` or the first digit to the last digit) and the digits alone.
+fn number_runs(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut runs = Vec::new();
+    let mut current: Option<(usize, usize, String)> = None;
+    for (i, c) in text.char_indices() {
+        if c.is_ascii_digit() {
+            let start = if text[..i].ends_with('+') { i - 1 } else { i };
+            let run = current.get_or_insert_with(|| (start, i, String::new()));
+            run.1 = i + 1;
+            run.2.push(c);
+        } else if current.is_some() && matches!(c, ' ' | '-' | '.' | '(' | ')' | '+') {
+            continue;
+        } else if let Some((start, end, digits)) = current.take() {
+            runs.push((start..end, digits));
+        }
+    }
+    if let Some((start, end, digits)) = current {
+        runs.push((start..end, digits));
+    }
+    runs
+}
+
+fn luhn(digits: &str) -> bool {
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+fn find_profanity(text: &str, words: &[String], matches: &mut Vec<(Range<usize>, Kind)>) {
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        if c.is_alphabetic() {
+            start.get_or_insert(i);
+            continue;
+        }
+        let Some(begin) = start.take() else {
+            continue;
+        };
+        let word = text[begin..i].to_lowercase();
+        let listed = |entry: &str| {
+            INFLECTIONS
+                .iter()
+                .any(|suffix| word.strip_suffix(suffix) == Some(entry))
+        };
+        if PROFANITY.iter().any(|entry| listed(entry)) || words.iter().any(|entry| listed(entry)) {
+            matches.push((begin..i, Kind::Profanity));
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Deserialize;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Entries, with a full-text index over both texts kept in sync by triggers.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS entries (
+    id INTEGER PRIMARY KEY,
+    time INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    app TEXT,
+    original TEXT NOT NULL,
+    improved TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS entries_original ON entries (original);
+CREATE VIRTUAL TABLE IF NOT EXISTS entries_fts
+    USING fts5 (original, improved, content = 'entries', content_rowid = 'id');
+CREATE TRIGGER IF NOT EXISTS entries_insert AFTER INSERT ON entries BEGIN
+    INSERT INTO entries_fts (rowid, original, improved)
+    VALUES (new.id, new.original, new.improved);
+END;
+CREATE TRIGGER IF NOT EXISTS entries_delete AFTER DELETE ON entries BEGIN
+    INSERT INTO entries_fts (entries_fts, rowid, original, improved)
+    VALUES ('delete', old.id, old.original, old.improved);
+END;
+"#;
+
+const COLUMNS: &str = "time, action, app, original, improved";
+
+/// One result, as stored in the history.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Entry {
+    /// Unix time in seconds
+    pub time: u64,
+    /// Hotkey action, as passed to script hooks
+    pub action: String,
+    /// Application the hotkey was pressed in, if known
+    #[serde(default)]
+    pub app: Option<String>,
+    pub original: String,
+    pub improved: String,
+}
+
+impl Entry {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            time: row.get::<_, i64>(0)? as u64,
+            action: row.get(1)?,
+            app: row.get(2)?,
+            original: row.get(3)?,
+            improved: row.get(4)?,
+        })
+    }
+}
+
+/// Default history database (`$XDG_DATA_HOME/improve-writing/history.db`).
+pub fn default_path() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+        .unwrap_or_default()
+        .join("improve-writing")
+        .join("history.db")
+}
+
+/// Local date and time of a Unix timestamp, e.g. `2024-05-01 14:03`.
+pub fn local_time(time: u64) -> String {
+    Local
+        .timestamp_opt(time as i64, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// Print entries for the terminal, one block per entry.
+pub fn print(entries: &[Entry]) {
+    if entries.is_empty() {
+        println!("No matches");
+        return;
+    }
+    for entry in entries {
+        match &entry.app {
+            Some(app) => println!("{}  {} in {}", local_time(entry.time), entry.action, app),
+            None => println!("{}  {}", local_time(entry.time), entry.action),
+        }
+        println!(
+            "  original: {}",
+            entry.original.replace('\n', "\n            ")
+        );
+        println!(
+            "  improved: {}",
+            entry.improved.replace('\n', "\n            ")
+        );
+        println!();
+    }
+}
+
+/// SQLite history of results, readable only by the user.
+pub struct History {
+    conn: Mutex<Connection>,
+}
+
+impl History {
+    /// Open (or create) the database at `path`. A `history.jsonl` left next
+    /// to it by earlier versions is imported once and renamed.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            crate::private::with_umask(0o077, || std::fs::create_dir_all(dir))
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        // A database from an earlier version may be readable by others.
+        // SQLite gives its journal the database's mode, so this covers both.
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to make {} private", path.display()))?;
+        }
+        let conn = crate::private::with_umask(0o077, || Connection::open(path))
+            .with_context(|| format!("Failed to open history {}", path.display()))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create history tables")?;
+
+        let history = Self {
+            conn: Mutex::new(conn),
+        };
+        let legacy = path.with_extension("jsonl");
+        if legacy.is_file() {
+            history.import_jsonl(&legacy)?;
+        }
+        Ok(history)
+    }
+
+    /// Record a result. The same result for the same text and action (e.g.
+    /// a refinement that came back unchanged) moves the existing entry to
+    /// the top rather than adding a duplicate.
+    pub fn record(
+        &self,
+        action: &str,
+        app: Option<&str>,
+        original: &str,
+        improved: &str,
+    ) -> Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE entries SET time = ?1, app = ?2
+             WHERE original = ?4 AND action = ?3 AND improved = ?5",
+            params![time, app, action, original, improved],
+        )?;
+        if updated == 0 {
+            conn.execute(
+                &format!("INSERT INTO entries ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5)"),
+                params![time, action, app, original, improved],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The newest result recorded for `original` with `action`, if any.
+    pub fn lookup(&self, action: &str, original: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let improved = conn
+            .query_row(
+                "SELECT improved FROM entries WHERE original = ?1 AND action = ?2
+                 ORDER BY time DESC, id DESC LIMIT 1",
+                params![original, action],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(improved)
+    }
+
+    /// All entries, oldest first.
+    pub fn load(&self) -> Result<Vec<Entry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement =
+            conn.prepare(&format!("SELECT {COLUMNS} FROM entries ORDER BY time, id"))?;
+        let entries = statement
+            .query_map([], Entry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// The `limit` newest entries, newest first.
+    pub fn recent(&self, limit: usize) -> Result<Vec<Entry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM entries ORDER BY time DESC, id DESC LIMIT ?1"
+        ))?;
+        let entries = statement
+            .query_map(params![limit as i64], Entry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Up to `limit` entries whose original or improved text contains every
+    /// word of `query`, newest first.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<Entry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT e.time, e.action, e.app, e.original, e.improved
+             FROM entries_fts JOIN entries e ON e.id = entries_fts.rowid
+             WHERE entries_fts MATCH ?1
+             ORDER BY e.time DESC, e.id DESC
+             LIMIT ?2",
+        )?;
+        let entries = statement
+            .query_map(params![fts_query(query), limit as i64], Entry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("History search failed")?;
+        Ok(entries)
+    }
+
+    fn import_jsonl(&self, path: &Path) -> Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut imported = 0;
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    tracing::warn!("Skipping history line {}: {}", i + 1, e);
+                    continue;
+                }
+            };
+            tx.execute(
+                &format!("INSERT INTO entries ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5)"),
+                params![
+                    entry.time as i64,
+                    entry.action,
+                    entry.app,
+                    entry.original,
+                    entry.improved
+                ],
+            )?;
+            imported += 1;
+        }
+        tx.commit()?;
+
+        let done = path.with_extension("jsonl.imported");
+        std::fs::rename(path, &done)
+            .with_context(|| format!("Failed to rename {}", path.display()))?;
+        tracing::info!(
+            "Imported {} history entries from {}",
+            imported,
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+/// Quote each word of a search so FTS5 matches it literally (all words must
+/// appear) instead of parsing operators and punctuation.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
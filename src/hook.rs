@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::ollama::TextImprover;
+use crate::shell;
+
+/// Marks the start of the diff git appends to the message file for
+/// `git commit --verbose`; everything from here on is ignored by git.
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+/// Messages git or the user generated that shouldn't be rewritten.
+const SKIP_PREFIXES: &[&str] = &["Merge ", "Revert \"", "fixup! ", "squash! ", "amend! "];
+
+/// Improve the commit message in `path` in place (the `commit-msg` hook).
+///
+/// Comment lines are kept as they are, for git to strip. If the model can't
+/// be reached the message is left alone, so the hook never blocks a commit.
+pub async fn commit_msg(improver: &TextImprover, path: &Path) -> Result<()> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let (message, comments) = split_message(&contents);
+
+    let message = message.trim();
+    if message.is_empty() || SKIP_PREFIXES.iter().any(|p| message.starts_with(p)) {
+        return Ok(());
+    }
+
+    let improved = match improver.commit_message(message).await {
+        Ok(improved) if !improved.trim().is_empty() => improved,
+        Ok(_) => {
+            tracing::warn!("Ollama returned empty response, keeping the commit message");
+            return Ok(());
+        }
+        Err(e) => {
+            tracing::warn!("Failed to improve commit message, keeping it: {:#}", e);
+            return Ok(());
+        }
+    };
+    tracing::debug!("Improved commit message: {:?}", improved);
+
+    let mut contents = format!("{}\n", improved.trim());
+    if !comments.is_empty() {
+        contents.push('\n');
+        contents.push_str(&comments);
+    }
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Split a message file into the message and the comment lines (plus the
+/// verbose diff, if any).
+fn split_message(contents: &str) -> (String, String) {
+    let (head, diff) = match contents.find(SCISSORS) {
+        Some(i) => contents.split_at(i),
+        None => (contents, ""),
+    };
+
+    let mut message = String::new();
+    let mut comments = String::new();
+    for line in head.lines() {
+        let target = if line.starts_with('#') {
+            &mut comments
+        } else {
+            &mut message
+        };
+        target.push_str(line);
+        target.push('\n');
+    }
+    comments.push_str(diff);
+    (message, comments)
+}
+
+/// Install the `commit-msg` hook into the current repository.
+pub async fn install(force: bool) -> Result<()> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()
+        .await
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!("Not inside a git repository");
+    }
+    let dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+
+    let path = dir.join("commit-msg");
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists (use --force to replace it)",
+            path.display()
+        );
+    }
+
+    let exe = std::env::current_exe().context("Failed to locate improve-writing binary")?;
+    let exe = shell::quote(&exe.display().to_string());
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    std::fs::write(
+        &path,
+        format!("#!/bin/sh\nexec {exe} hook commit-msg \"$1\"\n"),
+    )?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    println!("Installed hook: {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// Bundled translations of user-facing messages, by language. English is
+/// the fallback for languages and messages without a translation.
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+    ("fr", include_str!("../locales/fr.ftl")),
+];
+
+struct Messages {
+    /// Language subtag of the user's language, translated or not
+    language: String,
+    locale: Option<FluentBundle<FluentResource>>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+static MESSAGES: OnceLock<Messages> = OnceLock::new();
+
+/// Select the language for notifications and hints: `lang` if given (from
+/// --lang), otherwise LC_ALL, LC_MESSAGES or LANG. Call before any message
+/// is looked up; later calls have no effect.
+pub fn init(lang: Option<&str>) {
+    let language = language(lang);
+    tracing::debug!("Message language: {}", language.as_deref().unwrap_or("en"));
+    let _ = MESSAGES.set(load(language.as_deref()));
+}
+
+/// The language subtag to use, if it isn't English.
+fn language(lang: Option<&str>) -> Option<String> {
+    let value = match lang {
+        Some(lang) => lang.to_string(),
+        None => ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())?,
+    };
+
+    // de_DE.UTF-8@euro -> de-DE
+    let tag = value
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-");
+    if matches!(tag.as_str(), "" | "C" | "POSIX") {
+        return None;
+    }
+    match tag.parse::<LanguageIdentifier>() {
+        Ok(id) if id.language.as_str() != "en" => Some(id.language.as_str().to_string()),
+        Ok(_) => None,
+        Err(_) => {
+            tracing::warn!("Unrecognized language {:?}, using English", value);
+            None
+        }
+    }
+}
+
+fn load(language: Option<&str>) -> Messages {
+    let locale = language.and_then(|language| {
+        let bundle = bundle(language);
+        if bundle.is_none() {
+            tracing::info!("No translations for {:?}, using English", language);
+        }
+        bundle
+    });
+    Messages {
+        language: language.unwrap_or("en").to_string(),
+        locale,
+        fallback: bundle("en").expect("English messages are bundled"),
+    }
+}
+
+fn bundle(language: &str) -> Option<FluentBundle<FluentResource>> {
+    let (_, source) = LOCALES.iter().find(|(name, _)| *name == language)?;
+    let resource = FluentResource::try_new(source.to_string())
+        .unwrap_or_else(|(_, errors)| panic!("Invalid {language} messages: {errors:?}"));
+
+    let id: LanguageIdentifier = language.parse().ok()?;
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Notifications don't render bidi isolation marks
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .unwrap_or_else(|errors| panic!("Duplicate {language} messages: {errors:?}"));
+    Some(bundle)
+}
+
+/// The user's language subtag (`en`, `de`, ...), even where messages fall
+/// back to English.
+pub fn lang() -> String {
+    MESSAGES
+        .get_or_init(|| load(language(None).as_deref()))
+        .language
+        .clone()
+}
+
+/// The message `id` in the selected language.
+pub fn t(id: &str) -> String {
+    t_args(id, None)
+}
+
+/// The message `id` in the selected language, with its `{ $name }`
+/// placeholders filled from `args`.
+pub fn t_args(id: &str, args: Option<&FluentArgs>) -> String {
+    let messages = MESSAGES.get_or_init(|| load(language(None).as_deref()));
+    [messages.locale.as_ref(), Some(&messages.fallback)]
+        .into_iter()
+        .flatten()
+        .find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+            if !errors.is_empty() {
+                tracing::debug!("Errors formatting message {:?}: {:?}", id, errors);
+            }
+            Some(text.into_owned())
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Hint for an error caused by a missing external program.
+pub fn missing_tool(tool: &str) -> String {
+    t_args(
+        "hint-missing-tool",
+        Some(&FluentArgs::from_iter([("tool", tool)])),
+    )
+}
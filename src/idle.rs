@@ -0,0 +1,167 @@
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use anyhow::{Context, bail};
+use std::sync::Arc;
+#[cfg(target_os = "macos")]
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use tokio_stream::StreamExt;
+#[cfg(target_os = "linux")]
+use zbus::{Connection, Proxy, zvariant::OwnedObjectPath};
+
+use crate::queue::PressQueue;
+
+/// How often the session is checked where there is nothing to subscribe to.
+#[cfg(target_os = "macos")]
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Suspend `presses` while the session is idle or locked, and resume it as
+/// soon as the user is back. If the session can't be watched, it stays
+/// running.
+pub fn spawn_watcher(presses: Arc<PressQueue>) {
+    tokio::spawn(async move {
+        if let Err(e) = watch(&presses).await {
+            tracing::warn!("Can't tell when the session is idle or locked: {:#}", e);
+            presses.set_suspended(false);
+        }
+    });
+}
+
+/// Suspend or resume `presses` when `away` changed.
+fn update(presses: &PressQueue, suspended: &mut bool, away: bool) {
+    if away == *suspended {
+        return;
+    }
+    *suspended = away;
+    if away {
+        tracing::info!("Session idle or locked - suspending hotkeys");
+    } else {
+        tracing::info!("Session active - resuming hotkeys");
+    }
+    presses.set_suspended(away);
+}
+
+/// Follow logind's `IdleHint` and `LockedHint` for this session (or the
+/// user's display session, when started as a service), and the
+/// `org.freedesktop.ScreenSaver` screen saver, through their change signals.
+#[cfg(target_os = "linux")]
+async fn watch(presses: &PressQueue) -> Result<()> {
+    let system = Connection::system().await?;
+    let session = login_session(&system).await?;
+    let mut idle_changes = session.receive_property_changed::<bool>("IdleHint").await;
+    let mut lock_changes = session.receive_property_changed::<bool>("LockedHint").await;
+    let mut idle: bool = session.get_property("IdleHint").await?;
+    let mut locked: bool = session.get_property("LockedHint").await?;
+
+    // Not every locker sets LockedHint
+    let (mut saver_changes, mut saver_active) = match screen_saver().await {
+        Some(saver) => {
+            let active: bool = saver
+                .call("GetActive", &())
+                .await
+                .context("Failed to ask the screen saver")?;
+            (Some(saver.receive_signal("ActiveChanged").await?), active)
+        }
+        None => (None, false),
+    };
+
+    let mut suspended = false;
+    loop {
+        update(presses, &mut suspended, idle || locked || saver_active);
+        tokio::select! {
+            Some(change) = idle_changes.next() => idle = change.get().await?,
+            Some(change) = lock_changes.next() => locked = change.get().await?,
+            Some(signal) = async { saver_changes.as_mut()?.next().await } => {
+                saver_active = signal.body().deserialize()?;
+            }
+            else => bail!("The session's D-Bus signals stopped"),
+        }
+    }
+}
+
+/// The logind session, under its own path: logind sends change signals from
+/// there, not from `session/auto`.
+#[cfg(target_os = "linux")]
+async fn login_session(system: &Connection) -> Result<Proxy<'static>> {
+    let auto = Proxy::new(
+        system,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1/session/auto",
+        "org.freedesktop.login1.Session",
+    )
+    .await?;
+    let id: String = auto.get_property("Id").await.context("No logind session")?;
+    let path: OwnedObjectPath = system
+        .call_method(
+            Some("org.freedesktop.login1"),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            "GetSession",
+            &(id.as_str(),),
+        )
+        .await?
+        .body()
+        .deserialize()?;
+    tracing::debug!("Watching logind session {}", id);
+    Ok(Proxy::new(
+        system,
+        "org.freedesktop.login1",
+        path,
+        "org.freedesktop.login1.Session",
+    )
+    .await?)
+}
+
+/// The session bus screen saver, if one is running.
+#[cfg(target_os = "linux")]
+async fn screen_saver() -> Option<Proxy<'static>> {
+    let bus = Connection::session().await.ok()?;
+    let saver = Proxy::new(
+        &bus,
+        "org.freedesktop.ScreenSaver",
+        "/org/freedesktop/ScreenSaver",
+        "org.freedesktop.ScreenSaver",
+    )
+    .await
+    .ok()?;
+    let owner = bus
+        .call_method(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            Some("org.freedesktop.DBus"),
+            "NameHasOwner",
+            &("org.freedesktop.ScreenSaver",),
+        )
+        .await
+        .ok()?
+        .body()
+        .deserialize::<bool>()
+        .ok()?;
+    owner.then_some(saver)
+}
+
+/// Check every `CHECK_INTERVAL` whether the console session's screen is
+/// locked (`ioreg`); macOS has no signal for it outside an application.
+#[cfg(target_os = "macos")]
+async fn watch(presses: &PressQueue) -> Result<()> {
+    let mut suspended = false;
+    loop {
+        match locked().await {
+            Ok(away) => update(presses, &mut suspended, away),
+            Err(e) => tracing::debug!("Failed to check whether the screen is locked: {:#}", e),
+        }
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn locked() -> Result<bool> {
+    use anyhow::Context;
+
+    let output = tokio::process::Command::new("ioreg")
+        .args(["-n", "Root", "-d1"])
+        .output()
+        .await
+        .context("Failed to run ioreg")?;
+    Ok(String::from_utf8_lossy(&output.stdout).contains("\"CGSSessionScreenIsLocked\"=Yes"))
+}
@@ -0,0 +1,54 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Appends each result to a Markdown file per day (`YYYY-MM-DD.md`), e.g.
+/// in a notes vault.
+pub struct Journal {
+    dir: PathBuf,
+}
+
+impl Journal {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn append(
+        &self,
+        action: &str,
+        app: Option<&str>,
+        original: &str,
+        improved: &str,
+    ) -> Result<()> {
+        let now = Local::now();
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.dir.join(format!("{}.md", now.format("%Y-%m-%d")));
+
+        let mut entry = String::new();
+        if !path.exists() {
+            entry.push_str(&format!("# {}\n\n", now.format("%Y-%m-%d")));
+        }
+        entry.push_str(&format!("## {} · {}", now.format("%H:%M"), action));
+        if let Some(app) = app {
+            entry.push_str(&format!(" · {app}"));
+        }
+        entry.push_str("\n\n");
+        for line in original.lines() {
+            entry.push_str(format!("> {line}").trim_end());
+            entry.push('\n');
+        }
+        entry.push('\n');
+        entry.push_str(improved.trim_end());
+        entry.push_str("\n\n");
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        file.write_all(entry.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
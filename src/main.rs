@@ -1,104 +1,1857 @@
+mod batch;
+mod browse;
+mod chunk;
+mod comment;
+mod config;
+mod control;
+mod danger;
+mod diff;
 mod event_loop;
+mod execute;
+mod facts;
+mod filter;
+mod history;
+mod hook;
+mod i18n;
+mod idle;
+mod journal;
+mod markdown;
+#[cfg(target_os = "macos")]
+mod menubar;
+mod metrics;
+mod native_host;
+mod notify;
+mod nvim;
 mod ollama;
+mod osd;
 mod output;
+#[cfg(target_os = "linux")]
+mod paranoid;
+mod password;
+mod picker;
+mod pipeline;
+mod plugin;
+#[cfg(target_os = "linux")]
+mod portal;
+mod postprocess;
+mod private;
+mod profiles;
+mod prompts;
+mod protect;
+mod queue;
+mod residency;
+mod rewrite;
+#[cfg(target_os = "linux")]
+mod sandbox;
+mod screencast;
+mod screenshot;
+mod script;
+mod select;
+mod service;
+mod shell;
+mod speech;
+mod spell;
+mod telemetry;
+mod throttle;
+mod tmux;
+mod ui;
+mod voice;
+mod web;
+mod window;
 
-use anyhow::Result;
-use clap::Parser;
-use hotkey_listener::{HotkeyListenerBuilder, parse_hotkey};
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use diff::ChangeMarkup;
+use event_loop::{CompareOutput, CriticPass, ExplanationOutput, Mode, Settings};
+use facts::FactCheck;
+use filter::OutputFilter;
+use hotkey_listener::{Hotkey, HotkeyListenerBuilder, parse_hotkey};
+use metrics::ReadabilityReport;
+use native_host::Browser;
+use ollama::Profile;
+use output::{ClipboardBackend, ClipboardSlots, OutputMode, ReasoningOutput};
+use screenshot::ImageSource;
+use select::Strategy;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering::Release};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(name = "improve-writing")]
 #[command(about = "Hotkey-triggered text improvement via Ollama")]
 struct Args {
     /// Hotkey to trigger text improvement (e.g., F9, Shift+F9, Ctrl+Alt+F1)
-    #[arg(long, default_value = "F8")]
+    #[arg(long, env = "IMPROVE_WRITING_KEY", default_value = "F8")]
     key: String,
 
     /// Hotkey to output original + improved text (default: Shift+<key>)
-    #[arg(long)]
+    #[arg(long, env = "IMPROVE_WRITING_SHOW_ORIGINAL_KEY")]
     show_original_key: Option<String>,
 
     /// Hotkey to generate a shell command from a description
-    #[arg(long, default_value = "F7")]
+    #[arg(long, env = "IMPROVE_WRITING_CMD_KEY", default_value = "F7")]
     cmd_key: String,
 
+    // One option per action, declared with `hotkeys!`
+    #[command(flatten)]
+    hotkeys: Hotkeys,
+
+    /// Number of alternatives offered by the pick and title hotkeys
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_CANDIDATES",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u8).range(2..=9),
+    )]
+    candidates: u8,
+
+    /// Word limit for the shorten hotkey
+    #[arg(long, env = "IMPROVE_WRITING_SHORTEN_WORDS", default_value_t = 25)]
+    shorten_words: usize,
+
+    /// Character limit for the shorten hotkey
+    #[arg(long, env = "IMPROVE_WRITING_SHORTEN_CHARS", default_value_t = 280)]
+    shorten_chars: usize,
+
+    /// Reading level for the simplify hotkey, e.g. "CEFR B1" or "grade 6"
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_SIMPLIFY_LEVEL",
+        default_value = "CEFR B1"
+    )]
+    simplify_level: String,
+
+    /// Give each improve request the last N improvements (original and
+    /// result) as examples, to keep tone and terminology consistent within
+    /// a writing session; 0 turns it off
+    #[arg(long, env = "IMPROVE_WRITING_SESSION_MEMORY", default_value_t = 0)]
+    session_memory: usize,
+
+    /// Where the explain-command hotkey puts its explanation
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_EXPLAIN_CMD_OUTPUT",
+        value_enum,
+        default_value_t = ExplanationOutput::Notify,
+    )]
+    explain_cmd_output: ExplanationOutput,
+
+    /// whisper.cpp model file used to transcribe voice instructions
+    #[arg(long, env = "IMPROVE_WRITING_WHISPER_MODEL")]
+    whisper_model: Option<PathBuf>,
+
+    /// whisper.cpp command-line program
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_WHISPER_BIN",
+        default_value = "whisper-cli"
+    )]
+    whisper_bin: String,
+
+    /// How many seconds the voice hotkey records for
+    #[arg(long, env = "IMPROVE_WRITING_VOICE_SECONDS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..=60))]
+    voice_seconds: u64,
+
+    /// Read results aloud instead of typing them, and type one only when
+    /// the accept hotkey is pressed
+    #[arg(long, env = "IMPROVE_WRITING_READ_ALOUD", requires_all = ["accept_key", "reject_key"])]
+    read_aloud: bool,
+
+    /// Tone of drafted email replies
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_REPLY_TONE",
+        default_value = "friendly, professional"
+    )]
+    reply_tone: String,
+
+    /// A few words about you for drafted replies, e.g. your name and role
+    #[arg(long, env = "IMPROVE_WRITING_REPLY_PERSONA")]
+    reply_persona: Option<String>,
+
+    /// Sign-off appended to drafted replies (\n is a newline)
+    #[arg(long, env = "IMPROVE_WRITING_REPLY_SIGNATURE")]
+    reply_signature: Option<String>,
+
+    /// Where the image hotkey takes its image from
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_IMAGE_SOURCE",
+        value_enum,
+        default_value_t = ImageSource::Screenshot,
+    )]
+    image_source: ImageSource,
+
+    /// Prompt sent along with the image
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_IMAGE_PROMPT",
+        default_value = "Describe this image concisely."
+    )]
+    image_prompt: String,
+
+    /// Vision-capable model for image requests (default: --ollama-model)
+    #[arg(long, env = "IMPROVE_WRITING_VISION_MODEL")]
+    vision_model: Option<String>,
+
+    /// Request shell commands as JSON; only the command is typed and the
+    /// model's explanation is shown in a notification
+    #[arg(long, env = "IMPROVE_WRITING_CMD_JSON")]
+    cmd_json: bool,
+
+    /// Instead of typing generated shell commands, ask for confirmation and
+    /// run approved ones in $SHELL, showing their output in a notification.
+    /// Runs whatever the model produced, so review each command before
+    /// approving it
+    #[arg(long, env = "IMPROVE_WRITING_EXECUTE_COMMANDS")]
+    execute_commands: bool,
+
+    /// Ask the model in a second pass to explain each generated shell
+    /// command and flag destructive ones; shown in the notification
+    #[arg(long, env = "IMPROVE_WRITING_REVIEW_COMMANDS")]
+    review_commands: bool,
+
+    /// Append generated shell commands to this shell's history file instead
+    /// of typing them, to recall with Ctrl+R
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_CMD_HISTORY",
+        value_enum,
+        conflicts_with = "execute_commands"
+    )]
+    cmd_history: Option<shell::Shell>,
+
+    /// Type generated commands that look destructive (rm, dd, mkfs, curl |
+    /// sh, ...) too, instead of copying them to the clipboard with a warning
+    #[arg(long, env = "IMPROVE_WRITING_ALLOW_DANGEROUS_COMMANDS")]
+    allow_dangerous_commands: bool,
+
+    /// File of names, product terms, and phrases (one per line) that the
+    /// improve action must keep verbatim; changed terms trigger a retry, then
+    /// fall back to the original
+    #[arg(long, env = "IMPROVE_WRITING_PROTECTED_TERMS")]
+    protected_terms: Option<PathBuf>,
+
+    /// Fix unambiguous typos with hunspell before improve requests, in the
+    /// dictionary of the text's language
+    #[arg(long, env = "IMPROVE_WRITING_SPELLCHECK")]
+    spellcheck: bool,
+
+    /// Hunspell dictionary for a language, e.g. en=en_GB (repeatable)
+    #[arg(
+        long = "spellcheck-dict",
+        env = "IMPROVE_WRITING_SPELLCHECK_DICT",
+        value_name = "LANG=DICT"
+    )]
+    spellcheck_dicts: Vec<String>,
+
+    /// Record each result with its original text, for `improve-writing
+    /// history`
+    #[arg(long, env = "IMPROVE_WRITING_HISTORY")]
+    history: bool,
+
+    /// History database (default: $XDG_DATA_HOME/improve-writing/history.db)
+    #[arg(long, env = "IMPROVE_WRITING_HISTORY_FILE")]
+    history_file: Option<PathBuf>,
+
+    /// Directory to append each result (with its original text) to, in one
+    /// Markdown file per day, e.g. a folder in a notes vault
+    #[arg(long, env = "IMPROVE_WRITING_JOURNAL_DIR")]
+    journal_dir: Option<PathBuf>,
+
+    /// Mask or block profanity and personal data (email addresses, phone and
+    /// card numbers) the model adds to a response; override per action with
+    /// --action-opt ACTION.filter=off|mask|block
+    #[arg(long, env = "IMPROVE_WRITING_OUTPUT_FILTER", value_enum, default_value_t = OutputFilter::Off)]
+    output_filter: OutputFilter,
+
+    /// File of extra words (one per line) for the output filter to treat as
+    /// profanity
+    #[arg(long, env = "IMPROVE_WRITING_FILTER_WORDS")]
+    filter_words: Option<PathBuf>,
+
+    /// Let the model rewrite fenced and inline code in the selection
+    /// (by default code spans are hidden behind placeholders and restored)
+    #[arg(long, env = "IMPROVE_WRITING_REWRITE_CODE")]
+    rewrite_code: bool,
+
+    /// Run a second pass in which the model reviews the rewrite against the
+    /// original and corrects it (slower; tune with --action-opt critic.*)
+    #[arg(long, env = "IMPROVE_WRITING_CRITIC", value_enum, default_value_t = CriticPass::Off)]
+    critic: CriticPass,
+
+    /// Sample this many rewrites on the show-original (Shift) hotkey and
+    /// keep the best one (slower; 1 turns this off)
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_BEST_OF",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..=9),
+    )]
+    best_of: u8,
+
+    /// How --best-of picks a rewrite: the one the samples agree on most, or
+    /// the one the model judges best (one more request)
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_BEST_OF_STRATEGY",
+        value_enum,
+        default_value_t = Strategy::Consensus,
+    )]
+    best_of_strategy: Strategy,
+
+    /// Run the improve hotkeys through a pipeline of comma-separated stages
+    /// instead of a single improve request, e.g. improve,critic,truncate:280.
+    /// Stages: improve, shorten[:CHARS], expand, summarize, critic, fit:LIMIT,
+    /// plain, squeeze, truncate:LIMIT, words:N, verify, redact, deai,
+    /// regex:NAME, plugin:NAME (a LIMIT is characters, or N% of the input)
+    #[arg(long, env = "IMPROVE_WRITING_PIPELINE")]
+    pipeline: Option<pipeline::Pipeline>,
+
+    /// Keep quoted passages ("…", “…”, «…», and > quote lines) verbatim
+    #[arg(long, env = "IMPROVE_WRITING_PRESERVE_QUOTES")]
+    preserve_quotes: bool,
+
+    /// Prompt pack entry (namespace/name) to use instead of the built-in
+    /// improvement prompt
+    #[arg(long, env = "IMPROVE_WRITING_PROMPT")]
+    prompt: Option<String>,
+
+    /// Prompt pack entry (namespace/name) to use instead of the built-in
+    /// shell command prompt
+    #[arg(long, env = "IMPROVE_WRITING_CMD_PROMPT")]
+    cmd_prompt: Option<String>,
+
+    /// Prompt pack entry (namespace/name) to use instead of the built-in
+    /// expand prompt
+    #[arg(long, env = "IMPROVE_WRITING_EXPAND_PROMPT")]
+    expand_prompt: Option<String>,
+
+    /// Ollama model option as KEY=VALUE, passed with every request
+    /// (repeatable), e.g. num_ctx=8192 or num_gpu=99
+    #[arg(
+        long = "ollama-opt",
+        env = "IMPROVE_WRITING_OLLAMA_OPT",
+        value_name = "KEY=VALUE"
+    )]
+    ollama_opts: Vec<String>,
+
+    /// How long Ollama keeps the model loaded after a request:
+    /// -1 (forever), 0 (unload immediately), or e.g. 30s, 10m, 2h
+    #[arg(long, env = "IMPROVE_WRITING_KEEP_ALIVE", default_value = "-1")]
+    keep_alive: String,
+
+    /// Seconds a request to Ollama may take before it fails; override it
+    /// per action with --action-opt ACTION.timeout=SECONDS
+    #[arg(long, env = "IMPROVE_WRITING_TIMEOUT", default_value = "120", value_parser = ollama::parse_timeout)]
+    timeout: Duration,
+
+    /// Send a lightweight load request every N seconds to keep the model
+    /// resident on hosts that evict idle models
+    #[arg(long, env = "IMPROVE_WRITING_PING_INTERVAL")]
+    ping_interval: Option<u64>,
+
+    /// Comma-separated models to keep loaded alongside --model (e.g. a
+    /// fast and a quality model), pinged every --ping-interval seconds
+    /// (default 60) so switching between them doesn't wait for a load
+    #[arg(long, env = "IMPROVE_WRITING_STANDBY_MODELS", value_delimiter = ',')]
+    standby_models: Vec<String>,
+
+    /// Check every N seconds whether the model (and --standby-models) is
+    /// still loaded and on the GPU, warning when it was evicted or runs on
+    /// the CPU
+    #[arg(long, env = "IMPROVE_WRITING_RESIDENCY_CHECK")]
+    residency_check: Option<u64>,
+
+    /// Load a model the residency check finds evicted again straight away
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_RELOAD_EVICTED",
+        requires = "residency_check"
+    )]
+    reload_evicted: bool,
+
+    /// Per-action generation option as ACTION.KEY=VALUE (repeatable).
+    /// Actions: improve, command, summarize, image, shorten, expand, critic,
+    /// judge, commit, comment, reply, title, simplify, bulletize, prose,
+    /// explain.
+    /// Keys: model, temperature, max_tokens, keep_alive, think, max_chars,
+    /// overflow, stop, filter, timeout.
+    #[arg(
+        long = "action-opt",
+        env = "IMPROVE_WRITING_ACTION_OPT",
+        value_name = "ACTION.KEY=VALUE"
+    )]
+    action_opts: Vec<String>,
+
+    /// Minimum seconds between two runs of a hotkey action, as
+    /// ACTION=SECONDS (e.g. improve=5) or SECONDS for every action
+    /// (repeatable). Action names are those passed to script hooks
+    #[arg(
+        long = "cooldown",
+        env = "IMPROVE_WRITING_COOLDOWN",
+        value_name = "[ACTION=]SECONDS"
+    )]
+    cooldowns: Vec<String>,
+
+    /// Maximum hotkey actions per minute, across all actions
+    #[arg(long, env = "IMPROVE_WRITING_MAX_PER_MINUTE")]
+    max_per_minute: Option<usize>,
+
+    /// How results reach the clipboard: the system clipboard, an OSC 52
+    /// sequence on the terminal (for daemons running over SSH), or both
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_CLIPBOARD",
+        value_enum,
+        default_value_t = ClipboardBackend::System,
+    )]
+    clipboard: ClipboardBackend,
+
+    /// Where to send the model's reasoning for actions with think=true
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_REASONING",
+        value_enum,
+        default_value_t = ReasoningOutput::Off,
+    )]
+    reasoning: ReasoningOutput,
+
+    /// Stream responses and show their beginning in a desktop notification
+    /// while they are generated
+    #[arg(long, env = "IMPROVE_WRITING_PROGRESS_NOTIFICATIONS")]
+    progress_notifications: bool,
+
+    /// Show an on-screen progress bar (via wob) while a request is in flight
+    #[arg(long, env = "IMPROVE_WRITING_OSD")]
+    osd: bool,
+
+    /// Rhai script with before(ctx)/after(ctx) hooks run around each hotkey
+    /// action (see README)
+    #[arg(long, env = "IMPROVE_WRITING_SCRIPT")]
+    script: Option<PathBuf>,
+
+    /// Control socket path for JSON-RPC clients
+    /// (default: $XDG_RUNTIME_DIR/improve-writing.sock)
+    #[arg(long, env = "IMPROVE_WRITING_SOCKET")]
+    socket: Option<PathBuf>,
+
+    /// Hotkey presses kept waiting while a request is in flight; repeated
+    /// presses of a waiting hotkey are coalesced, and extra ones dropped
+    #[arg(long, env = "IMPROVE_WRITING_QUEUE_SIZE", default_value_t = 2)]
+    queue_size: usize,
+
+    /// Hotkey actions that may run at once. Beyond the first, one-shot
+    /// actions (image, URL summary, pick, title, compare) run in the
+    /// background while other hotkeys keep working
+    #[arg(long, env = "IMPROVE_WRITING_CONCURRENCY", default_value_t = 1)]
+    concurrency: usize,
+
+    /// Chunks of a long input (files, pages to summarize, sentences) sent
+    /// to the backend at once; results are still used in order
+    #[arg(long, env = "IMPROVE_WRITING_CHUNK_PARALLELISM", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..=16))]
+    chunk_parallelism: u64,
+
+    /// When a hotkey is pressed on the same selection as last time (e.g.
+    /// one already improved and typed), ask to press it again before
+    /// sending it
+    #[arg(long, env = "IMPROVE_WRITING_CONFIRM_REPEATS")]
+    confirm_repeats: bool,
+
+    /// When nothing is selected (common right after switching apps), use
+    /// the clipboard text instead
+    #[arg(long, env = "IMPROVE_WRITING_FALLBACK_TO_CLIPBOARD")]
+    fallback_to_clipboard: bool,
+
+    /// Leave the clipboard as it was before the hotkey press instead of
+    /// putting the original text there as a backup
+    #[arg(long, env = "IMPROVE_WRITING_PRESERVE_CLIPBOARD")]
+    preserve_clipboard: bool,
+
+    /// After improving, put the original on the primary selection and the
+    /// result on the clipboard, or the other way round, so both are one
+    /// paste away (the system clipboard has a primary selection on Linux
+    /// only)
+    #[arg(long, env = "IMPROVE_WRITING_CLIPBOARD_SLOTS", value_enum)]
+    clipboard_slots: Option<ClipboardSlots>,
+
+    /// Seconds an idle connection to Ollama is kept for the next request;
+    /// 0 opens a new connection for every request
+    #[arg(long, env = "IMPROVE_WRITING_HTTP_POOL_IDLE", default_value_t = 30)]
+    http_pool_idle: u64,
+
+    /// Talk HTTP/2 to Ollama on plain (non-TLS) connections, for a backend
+    /// or proxy that accepts h2c; HTTPS hosts negotiate HTTP/2 on their own
+    #[arg(long, env = "IMPROVE_WRITING_HTTP2_PRIOR_KNOWLEDGE")]
+    http2_prior_knowledge: bool,
+
+    /// Serve the control socket only, without listening for hotkeys or
+    /// typing, e.g. on a remote GPU machine used through --relay
+    #[arg(long, env = "IMPROVE_WRITING_HEADLESS")]
+    headless: bool,
+
+    /// Use the XDG desktop portals (GlobalShortcuts, RemoteDesktop,
+    /// Notification) for hotkeys, typing and notifications instead of
+    /// /dev/input and helper programs, e.g. inside a Flatpak
+    #[cfg(target_os = "linux")]
+    #[arg(long, env = "IMPROVE_WRITING_PORTAL", conflicts_with = "headless")]
+    portal: bool,
+
+    /// Have the kernel deliver only the hotkeys' keys and the modifiers to
+    /// the keyboard listener, so no other keystroke ever reaches the process
+    #[cfg(target_os = "linux")]
+    #[arg(long, env = "IMPROVE_WRITING_PARANOID", conflicts_with_all = ["headless", "portal"])]
+    paranoid: bool,
+
+    /// Restrict file access to system, config, data and runtime directories
+    /// and TCP connections to the Ollama and OTLP ports, with Landlock
+    #[cfg(target_os = "linux")]
+    #[arg(long, env = "IMPROVE_WRITING_SANDBOX")]
+    sandbox: bool,
+
+    /// More directories the sandbox may read and write, comma-separated
+    #[cfg(target_os = "linux")]
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_SANDBOX_ALLOW",
+        value_delimiter = ',',
+        requires = "sandbox"
+    )]
+    sandbox_allow: Vec<PathBuf>,
+
+    /// More TCP ports the sandbox may connect to, comma-separated
+    #[cfg(target_os = "linux")]
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_SANDBOX_ALLOW_PORT",
+        value_delimiter = ',',
+        requires = "sandbox"
+    )]
+    sandbox_allow_port: Vec<u16>,
+
+    /// Send improve and command requests to another instance's control
+    /// socket (e.g. a headless one forwarded over SSH) instead of Ollama
+    #[arg(long, env = "IMPROVE_WRITING_RELAY", conflicts_with = "headless")]
+    relay: Option<PathBuf>,
+
+    /// Also serve msgpack-RPC for the bundled Neovim plugin, on the control
+    /// socket path with a `.nvim.sock` extension
+    #[arg(long, env = "IMPROVE_WRITING_NVIM")]
+    nvim: bool,
+
+    /// Export tracing spans (selection, backend call, output) over OTLP/gRPC
+    /// to this endpoint, e.g. http://localhost:4317
+    #[arg(long, env = "IMPROVE_WRITING_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+
     /// Ollama host URL
-    #[arg(long, default_value = "http://localhost")]
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_OLLAMA_HOST",
+        default_value = "http://localhost"
+    )]
     ollama_host: String,
 
     /// Ollama port
-    #[arg(long, default_value_t = 11434)]
+    #[arg(long, env = "IMPROVE_WRITING_OLLAMA_PORT", default_value_t = 11434)]
     ollama_port: u16,
 
     /// Ollama model to use
-    #[arg(long, default_value = "qwen3:1.7b")]
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_OLLAMA_MODEL",
+        default_value = "qwen3:1.7b"
+    )]
     ollama_model: String,
 
+    /// Start with this profile from the config file's [profiles.NAME]
+    /// tables instead of the default one
+    #[arg(long, env = "IMPROVE_WRITING_PROFILE")]
+    profile: Option<String>,
+
+    /// Comma-separated models that --model-key cycles through
+    #[arg(long, env = "IMPROVE_WRITING_MODELS", value_delimiter = ',')]
+    models: Vec<String>,
+
+    /// Comma-separated models (at least two) the compare hotkey runs
+    #[arg(long, env = "IMPROVE_WRITING_COMPARE_MODELS", value_delimiter = ',')]
+    compare_models: Vec<String>,
+
+    /// Where the compare hotkey puts its results: a menu to pick one from,
+    /// or the clipboard, labeled with their models
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_COMPARE_OUTPUT",
+        value_enum,
+        default_value_t = CompareOutput::Picker,
+    )]
+    compare_output: CompareOutput,
+
+    /// Show improvements as tracked changes against the selection
+    #[arg(long, env = "IMPROVE_WRITING_CHANGES", value_enum, default_value_t = ChangeMarkup::Off)]
+    changes: ChangeMarkup,
+
+    /// Report before/after readability scores of improved text
+    #[arg(
+        long,
+        env = "IMPROVE_WRITING_READABILITY",
+        value_enum,
+        default_value_t = ReadabilityReport::Off,
+    )]
+    readability: ReadabilityReport,
+
+    /// Keep the original text when the improvement has a higher
+    /// Flesch-Kincaid grade level
+    #[arg(long, env = "IMPROVE_WRITING_REQUIRE_READABLE")]
+    require_readable: bool,
+
+    /// Check that numbers, dates, and URLs in the selection appear unchanged
+    /// in improved text; warn about changes or keep the original
+    #[arg(long, env = "IMPROVE_WRITING_CHECK_FACTS", value_enum, default_value_t = FactCheck::Off)]
+    check_facts: FactCheck,
+
+    /// How to output results: type them (replace deletes the selection
+    /// first), copy them to the clipboard (rich-clipboard also offers an
+    /// HTML version for rich-text editors), or open them in --editor and
+    /// type what was saved
+    #[arg(long, env = "IMPROVE_WRITING_OUTPUT", value_enum, default_value_t = OutputMode::Type)]
+    output: OutputMode,
+
+    /// Editor command for --output editor, given the file to edit; it must
+    /// wait until the file is closed, so run terminal editors in a terminal
+    /// (e.g. "foot nvim", "code --wait") (default: $VISUAL or $EDITOR)
+    #[arg(long, env = "IMPROVE_WRITING_EDITOR")]
+    editor: Option<String>,
+
+    /// Seconds to wait between receiving a result and typing it, to allow
+    /// clicking into the right window
+    #[arg(long, env = "IMPROVE_WRITING_TYPE_DELAY", default_value_t = 0)]
+    type_delay: u64,
+
+    /// Count --type-delay down in a desktop notification
+    #[arg(long, env = "IMPROVE_WRITING_TYPE_COUNTDOWN")]
+    type_countdown: bool,
+
+    /// Type results even if another window has taken focus since the hotkey
+    /// was pressed (by default they are copied to the clipboard instead)
+    #[arg(long, env = "IMPROVE_WRITING_IGNORE_FOCUS")]
+    ignore_focus: bool,
+
+    /// Type this at the cursor as soon as a hotkey is handled, and replace it
+    /// with the result when it arrives (e.g. ⏳)
+    #[arg(long, env = "IMPROVE_WRITING_PLACEHOLDER")]
+    placeholder: Option<String>,
+
+    /// Focus the window the hotkey was pressed in again before typing, if
+    /// another window has taken focus
+    #[arg(long, env = "IMPROVE_WRITING_REFOCUS", conflicts_with = "ignore_focus")]
+    refocus: bool,
+
+    /// Type results into password fields too (by default they are refused,
+    /// where AT-SPI or macOS Secure Event Input tells)
+    #[arg(long, env = "IMPROVE_WRITING_TYPE_INTO_PASSWORD_FIELDS")]
+    type_into_password_fields: bool,
+
+    /// Applications (app_id, window class or process name) never read from
+    /// or typed into, comma-separated, e.g. org.keepassxc.KeePassXC
+    #[arg(long, env = "IMPROVE_WRITING_BLOCK_APPS", value_delimiter = ',')]
+    block_apps: Vec<String>,
+
+    /// Window title substrings that block a window the same way,
+    /// comma-separated, e.g. "Online Banking"
+    #[arg(long, env = "IMPROVE_WRITING_BLOCK_TITLES", value_delimiter = ',')]
+    block_titles: Vec<String>,
+
+    /// Copy results to the clipboard instead of typing them while the screen
+    /// is shared or recorded (Linux: Mutter or XDG portal screen casts)
+    #[arg(long, env = "IMPROVE_WRITING_SCREEN_SHARE_GUARD")]
+    screen_share_guard: bool,
+
+    /// Ignore hotkeys and poll the keyboard listener less often while the
+    /// session is idle or locked (logind, screen saver; macOS screen lock)
+    #[arg(long, env = "IMPROVE_WRITING_SUSPEND_WHEN_AWAY")]
+    suspend_when_away: bool,
+
+    /// Language for notifications and error hints (e.g. de, fr_FR); defaults
+    /// to LC_ALL, LC_MESSAGES or LANG
+    #[arg(long, env = "IMPROVE_WRITING_LANG")]
+    lang: Option<String>,
+
     /// Enable verbose logging
-    #[arg(long)]
+    #[arg(long, env = "IMPROVE_WRITING_VERBOSE")]
     verbose: bool,
+
+    /// Install launchd agents that start improve-writing (with the other
+    /// options given) and the menu bar item at login, then exit
+    #[cfg(target_os = "macos")]
+    #[arg(long)]
+    install_launchd: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+/// Declares the optional hotkeys once: their options (flattened into
+/// `Args`), and the table `hotkey_bindings` and `check-config` go through.
+macro_rules! hotkeys {
+    ($(
+        $(#[doc = $doc:literal])*
+        $field:ident => $mode:expr, $label:literal, [$($arg:tt)*];
+    )*) => {
+        #[derive(clap::Args, Debug)]
+        struct Hotkeys {
+            $(
+                $(#[doc = $doc])*
+                #[arg(long, $($arg)*)]
+                $field: Option<String>,
+            )*
+        }
 
-    // Initialize logging
-    if args.verbose {
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
-    } else {
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+        impl Hotkeys {
+            /// `(option, label, hotkey, mode)` for each optional hotkey, in
+            /// the order the listener matches them.
+            fn table(&self) -> Vec<(String, &'static str, Option<&String>, Mode)> {
+                vec![$((
+                    stringify!($field).replace('_', "-"),
+                    $label,
+                    self.$field.as_ref(),
+                    $mode,
+                )),*]
+            }
+        }
+    };
+}
+
+hotkeys! {
+    /// Hotkey to select a screen region, OCR it, and improve the recognized text
+    ocr_key => Mode::Ocr, "OCR", [env = "IMPROVE_WRITING_OCR_KEY"];
+
+    /// Hotkey to request several alternative rewrites and pick one from a
+    /// menu (rofi/wofi on Linux, a list dialog on macOS)
+    pick_key => Mode::Pick, "Pick", [env = "IMPROVE_WRITING_PICK_KEY"];
+
+    /// Hotkey that improves the selection one sentence at a time, typing
+    /// each as soon as it is ready
+    sentences_key => Mode::Sentences, "Sentences", [env = "IMPROVE_WRITING_SENTENCES_KEY"];
+
+    /// Hotkey that improves the selection with each of --compare-models at
+    /// once, to see which model does best
+    compare_key => Mode::Compare, "Compare", [env = "IMPROVE_WRITING_COMPARE_KEY", requires = "compare_models"];
+
+    /// Hotkey to shorten the selection to --shorten-words / --shorten-chars
+    shorten_key => Mode::Shorten, "Shorten", [env = "IMPROVE_WRITING_SHORTEN_KEY"];
+
+    /// Hotkey to expand notes or bullet points into full prose
+    expand_key => Mode::Expand, "Expand", [env = "IMPROVE_WRITING_EXPAND_KEY"];
+
+    /// Hotkey to rewrite a code comment or doc comment, keeping its markers
+    /// and wrapping it at 80 columns
+    comment_key => Mode::Comment, "Comment", [env = "IMPROVE_WRITING_COMMENT_KEY"];
+
+    /// Hotkey to rewrite the selection in plain language at --simplify-level
+    simplify_key => Mode::Rewrite(Profile::Simplify), "Simplify", [env = "IMPROVE_WRITING_SIMPLIFY_KEY"];
+
+    /// Hotkey to turn prose into bullet points
+    bulletize_key => Mode::Rewrite(Profile::Bulletize), "Bulletize", [env = "IMPROVE_WRITING_BULLETIZE_KEY"];
+
+    /// Hotkey to turn bullet points into prose
+    prose_key => Mode::Rewrite(Profile::Prose), "Prose", [env = "IMPROVE_WRITING_PROSE_KEY"];
+
+    /// Hotkey to suggest subject lines or titles for the selection and type
+    /// the one picked from a menu
+    title_key => Mode::Title, "Title", [env = "IMPROVE_WRITING_TITLE_KEY"];
+
+    /// Hotkey to draft a reply to the selected email onto the clipboard
+    reply_key => Mode::Reply, "Reply", [env = "IMPROVE_WRITING_REPLY_KEY"];
+
+    /// Hotkey to switch to the next profile
+    profile_key => Mode::CycleProfile, "Profile", [env = "IMPROVE_WRITING_PROFILE_KEY"];
+
+    /// Hotkey to forget the improvements remembered with --session-memory
+    reset_session_key => Mode::ResetSession, "Reset-session", [env = "IMPROVE_WRITING_RESET_SESSION_KEY"];
+
+    /// Hotkey to explain the selected shell command part by part
+    explain_cmd_key => Mode::ExplainCommand, "Explain-command", [env = "IMPROVE_WRITING_EXPLAIN_CMD_KEY"];
+
+    /// Hotkey to speak an instruction ("make this more formal") and apply
+    /// it to the selection
+    voice_key => Mode::Voice, "Voice", [env = "IMPROVE_WRITING_VOICE_KEY", requires = "whisper_model"];
+
+    /// Hotkey that types the result read aloud
+    accept_key => Mode::Accept, "Accept", [env = "IMPROVE_WRITING_ACCEPT_KEY"];
+
+    /// Hotkey that drops the result read aloud
+    reject_key => Mode::Reject, "Reject", [env = "IMPROVE_WRITING_REJECT_KEY"];
+
+    /// Hotkey to ask a vision model about an image (see --image-source)
+    image_key => Mode::Image, "Image", [env = "IMPROVE_WRITING_IMAGE_KEY"];
+
+    /// Hotkey that pauses and resumes all other hotkeys
+    pause_key => Mode::Pause, "Pause", [env = "IMPROVE_WRITING_PAUSE_KEY"];
+
+    /// Hotkey that switches to the next model in --models
+    model_key => Mode::CycleModel, "Model", [env = "IMPROVE_WRITING_MODEL_KEY", requires = "models"];
+
+    /// Hotkey that runs the last action again on the same text, without
+    /// reading the selection (e.g. after a request failed)
+    repeat_key => Mode::Repeat, "Repeat", [env = "IMPROVE_WRITING_REPEAT_KEY"];
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run as a browser native messaging host (launched by the browser)
+    NativeHost,
+    /// Install the native messaging manifest for a companion browser extension
+    InstallNativeHost {
+        /// Browser to register the host with
+        #[arg(long, value_enum)]
+        browser: Browser,
+        /// ID of the extension allowed to connect
+        #[arg(long)]
+        extension_id: String,
+    },
+    /// Manage prompt packs
+    Prompts {
+        #[command(subcommand)]
+        action: PromptsCommand,
+    },
+    /// List WebAssembly plugins available as plugin:NAME pipeline stages
+    Plugins,
+    /// Disable the hotkeys of the running instance
+    Pause,
+    /// Re-enable the hotkeys of the running instance
+    Resume,
+    /// Print command-line widgets for a shell, which send the current
+    /// command line to the running instance and replace it with the
+    /// generated command (bound to Ctrl+X Ctrl+G)
+    ShellInit {
+        #[arg(value_enum)]
+        shell: shell::Shell,
+    },
+    /// Ask the running instance to generate a shell command from a
+    /// description and print it
+    GenerateCommand {
+        /// What the command should do
+        text: String,
+        /// Refine the previous result instead of starting over
+        #[arg(long)]
+        refine: bool,
+    },
+    /// Improve whole files (e.g. a folder of notes) without the hotkeys,
+    /// through --pipeline if one is set
+    File {
+        /// Files to improve
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Overwrite the files instead of writing copies
+        #[arg(long)]
+        in_place: bool,
+        /// Added before the extension of the copies
+        #[arg(long, default_value = ".improved", conflicts_with = "in_place")]
+        suffix: String,
+    },
+    /// Watch a folder and improve every .txt or .md file added to or changed
+    /// in it, writing the results alongside (through --pipeline if set)
+    Watch {
+        /// Folder to watch (not recursive)
+        dir: PathBuf,
+        /// Added before the extension of the results
+        #[arg(long, default_value = ".improved")]
+        suffix: String,
+        /// Seconds between checks for new or changed files
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+    /// Explain or summarize the current tmux pane and show the result in a
+    /// popup (bind it with run-shell, see README)
+    Tmux {
+        #[arg(long, value_enum, default_value_t = tmux::Task::Explain)]
+        task: tmux::Task,
+        /// Use the most recent tmux buffer (copy-mode selection) instead of
+        /// the visible pane
+        #[arg(long)]
+        buffer: bool,
+    },
+    /// Check the config file and options (hotkeys and their collisions,
+    /// prompts, Ollama settings, referenced files) without starting
+    CheckConfig,
+    /// Browse and search the results recorded with --history
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryCommand>,
+    },
+    /// Git hooks that improve text without the hotkey loop
+    Hook {
+        #[command(subcommand)]
+        action: HookCommand,
+    },
+    /// Run improve-writing at login as a systemd user service (Linux) or
+    /// launchd agents (macOS)
+    Service {
+        #[command(subcommand)]
+        action: ServiceCommand,
+    },
+    /// Show a menu bar item for the running instance: pause, model and
+    /// recent results
+    #[cfg(target_os = "macos")]
+    Menubar,
+    /// Serve a page on localhost to pick the model, browse and replay the
+    /// history, and edit the config file of the running instance
+    Ui {
+        /// Port on 127.0.0.1 to serve the page on
+        #[arg(long, default_value_t = 7655)]
+        port: u16,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceCommand {
+    /// Install and start the service, running improve-writing with the
+    /// options given before `service` (e.g. `improve-writing --history
+    /// service install`), and check permissions
+    Install,
+    /// Start the installed service
+    Start,
+    /// Stop the service until the next login or `start`
+    Stop,
+    /// Show whether the service runs, and what it is missing
+    Status,
+}
+
+/// Subcommands that need a text improver but not the hotkey loop.
+enum Oneshot {
+    CommitMsg(PathBuf),
+    Files {
+        paths: Vec<PathBuf>,
+        in_place: bool,
+        suffix: String,
+    },
+    Watch {
+        dir: PathBuf,
+        suffix: String,
+        interval: u64,
+    },
+    Tmux {
+        task: tmux::Task,
+        buffer: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Print the newest results containing every word of QUERY
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Maximum number of results
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HookCommand {
+    /// Rewrite the commit message in FILE as a Conventional Commit (run by
+    /// git as the commit-msg hook)
+    CommitMsg {
+        /// Commit message file passed by git
+        file: PathBuf,
+    },
+    /// Install the commit-msg hook into the current repository
+    Install {
+        /// Replace an existing commit-msg hook
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PromptsCommand {
+    /// Add a prompt pack from a git URL or a local directory
+    Add {
+        /// Git URL or local directory containing <name>.txt/.md prompt files
+        source: String,
+        /// Namespace for the pack (default: derived from the source)
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Pull the latest version of git-backed packs
+    Update {
+        /// Only update this pack
+        name: Option<String>,
+    },
+    /// List available prompts as namespace/name
+    List,
+    /// Remove a prompt pack
+    Remove {
+        /// Namespace of the pack to remove
+        name: String,
+    },
+}
+
+/// A configured hotkey, read only from the keyboards whose name contains
+/// `device` when one is given (`F13@macro pad`).
+pub struct Key {
+    /// `None` for numpad and media keys, which only the evdev reader knows
+    pub hotkey: Option<Hotkey>,
+    /// Canonical form (`Ctrl+Shift+F8`, `KP_Enter`), which the evdev reader
+    /// parses
+    pub name: String,
+    pub device: Option<String>,
+}
+
+impl Key {
+    /// The same key with Shift held.
+    fn with_shift(&self) -> Key {
+        let (hotkey, name) = match &self.hotkey {
+            Some(hotkey) => {
+                let hotkey = hotkey.with_shift();
+                let name = hotkey.to_string();
+                (Some(hotkey), name)
+            }
+            None if self
+                .name
+                .split('+')
+                .any(|part| part.trim().eq_ignore_ascii_case("shift")) =>
+            {
+                (None, self.name.clone())
+            }
+            None => (None, format!("Shift+{}", self.name)),
+        };
+        Key {
+            hotkey,
+            name,
+            device: self.device.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.device {
+            Some(device) => write!(f, "{}@{}", self.name, device),
+            None => write!(f, "{}", self.name),
+        }
     }
+}
 
-    // Parse hotkeys
-    let hotkey = parse_hotkey(&args.key)?;
+/// Parse a hotkey, optionally followed by `@` and part of a keyboard's name.
+fn parse_key(spec: &str) -> Result<Key> {
+    let (hotkey, device) = match spec.split_once('@') {
+        Some((hotkey, device)) if !device.trim().is_empty() => {
+            (hotkey, Some(device.trim().to_string()))
+        }
+        Some(_) => anyhow::bail!("{spec:?} names no keyboard after @"),
+        None => (spec, None),
+    };
+    #[cfg(not(target_os = "linux"))]
+    if device.is_some() {
+        anyhow::bail!("{spec:?}: hotkeys for one keyboard need Linux");
+    }
+    let hotkey = hotkey.trim();
+    let (parsed, name) = match parse_hotkey(hotkey) {
+        Ok(parsed) => {
+            let name = parsed.to_string();
+            (Some(parsed), name)
+        }
+        // Numpad and media keys, which only the evdev reader knows
+        #[cfg(target_os = "linux")]
+        Err(_) if paranoid::knows(hotkey) => (None, hotkey.to_string()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Key {
+        hotkey: parsed,
+        name,
+        device,
+    })
+}
+
+/// Parse the configured hotkeys into bindings, in the order the listener
+/// reports them.
+fn hotkey_bindings(args: &Args) -> Result<Vec<(Key, Mode)>> {
+    let hotkey = parse_key(&args.key)?;
     let show_original_hotkey = match &args.show_original_key {
-        Some(key) => parse_hotkey(key)?,
+        Some(key) => parse_key(key)?,
         None => hotkey.with_shift(),
     };
-    log::info!("Hotkey: {}", hotkey);
-    log::info!("Show-original hotkey: {}", show_original_hotkey);
+    tracing::info!("Hotkey: {}", hotkey);
+    tracing::info!("Show-original hotkey: {}", show_original_hotkey);
 
-    let cmd_hotkey = parse_hotkey(&args.cmd_key)?;
-    log::info!("Shell command hotkey: {}", cmd_hotkey);
+    let cmd_hotkey = parse_key(&args.cmd_key)?;
+    tracing::info!("Shell command hotkey: {}", cmd_hotkey);
 
-    #[cfg(target_os = "macos")]
-    log::info!("Note: You may need to grant Accessibility permissions for osascript to type text.");
-
-    // Build and start the hotkey listener
-    // Index 0 = main hotkey (improve only)
-    // Index 1 = show original hotkey (improve + show original)
-    // Index 2 = shell command hotkey (generate command)
-    let handle = HotkeyListenerBuilder::new()
-        .add_hotkey(hotkey)
-        .add_hotkey(show_original_hotkey)
-        .add_hotkey(cmd_hotkey)
+    let mut bindings = vec![
+        (hotkey, Mode::Improve),
+        (show_original_hotkey, Mode::ImproveShowOriginal),
+        (cmd_hotkey, Mode::ShellCommand),
+    ];
+
+    if args.hotkeys.compare_key.is_some() && args.compare_models.len() < 2 {
+        anyhow::bail!("--compare-models needs at least two models");
+    }
+    for (_, label, key, mode) in args.hotkeys.table() {
+        if let Some(key) = key {
+            let key = parse_key(key)?;
+            tracing::info!("{label} hotkey: {key}");
+            bindings.push((key, mode));
+        }
+    }
+
+    Ok(bindings)
+}
+
+/// The profile made of the command line, environment, and top-level config
+/// file options, which `[profiles.NAME]` tables build on.
+fn default_profile(args: &Args) -> Result<profiles::Profile> {
+    Ok(profiles::Profile {
+        name: profiles::DEFAULT.to_string(),
+        ollama_host: args.ollama_host.clone(),
+        ollama_port: args.ollama_port,
+        model: args.ollama_model.clone(),
+        improve_prompt: args.prompt.as_deref().map(prompts::resolve).transpose()?,
+        command_prompt: args
+            .cmd_prompt
+            .as_deref()
+            .map(prompts::resolve)
+            .transpose()?,
+        reply_tone: args.reply_tone.clone(),
+        output_filter: args.output_filter,
+        filter_words: match &args.filter_words {
+            Some(path) => filter::load_words(path)?,
+            None => Vec::new(),
+        },
+        local_only: false,
+        routes: Vec::new(),
+    })
+}
+
+/// Hotkeys bound to more than one action. The listener dispatches the first
+/// match, so the later action would never run. `derived_show_original` says
+/// the show-original hotkey is the Shift variant of --key rather than set
+/// explicitly, which gets a hint on how to resolve it.
+fn hotkey_conflicts(bindings: &[(Key, Mode)], derived_show_original: bool) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    for (i, (hotkey, mode)) in bindings.iter().enumerate() {
+        let key = hotkey.to_string();
+        let Some((_, first)) = bindings[..i].iter().find(|(h, _)| h.to_string() == key) else {
+            continue;
+        };
+        let shift_variant = derived_show_original
+            && [first, mode]
+                .iter()
+                .any(|mode| matches!(mode, Mode::ImproveShowOriginal));
+        let hint = match (first, shift_variant) {
+            (Mode::Improve, true) => {
+                " (--key already includes Shift; set --show-original-key explicitly)"
+            }
+            (_, true) => {
+                " (the Shift variant of --key is the show-original hotkey; set --show-original-key explicitly or pick another key)"
+            }
+            (_, false) => "",
+        };
+        conflicts.push(format!(
+            "{key} is bound to both {} and {}{hint}",
+            first.name(),
+            mode.name()
+        ));
+    }
+    conflicts
+}
+
+/// `{name}` placeholders in a prompt. Custom prompts are used as they are,
+/// so any placeholder would reach the model unfilled.
+fn placeholders(prompt: &str) -> Vec<&str> {
+    prompt
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+        .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        .collect()
+}
+
+/// `check-config`: report everything the config file and options would fail
+/// on at startup, pointing at config file lines where possible. Syntax
+/// errors, unknown options and bad values already stop the parse.
+fn check_config(args: &Args) -> Result<()> {
+    let mut problems = Vec::new();
+    let mut report = |option: Option<&str>, message: String| {
+        problems.push(match option {
+            Some(option) => match config::location(option) {
+                Some(location) => format!("{location}: {message}"),
+                None => format!("--{option}: {message}"),
+            },
+            None => message,
+        });
+    };
+
+    let mut hotkeys = vec![
+        ("key".to_string(), Some(&args.key)),
+        (
+            "show-original-key".to_string(),
+            args.show_original_key.as_ref(),
+        ),
+        ("cmd-key".to_string(), Some(&args.cmd_key)),
+    ];
+    hotkeys.extend(
+        args.hotkeys
+            .table()
+            .into_iter()
+            .map(|(option, _, key, _)| (option, key)),
+    );
+    let mut hotkeys_ok = true;
+    for (option, value) in hotkeys {
+        if let Some(value) = value
+            && let Err(e) = parse_key(value)
+        {
+            report(
+                Some(option.as_str()),
+                format!("invalid hotkey {value:?}: {e}"),
+            );
+            hotkeys_ok = false;
+        }
+    }
+    if hotkeys_ok {
+        match hotkey_bindings(args) {
+            Ok(bindings) => {
+                for conflict in hotkey_conflicts(&bindings, args.show_original_key.is_none()) {
+                    report(None, conflict);
+                }
+            }
+            Err(e) => report(None, format!("{e:#}")),
+        }
+    }
+
+    let prompts = [
+        ("prompt", args.prompt.as_ref()),
+        ("cmd-prompt", args.cmd_prompt.as_ref()),
+        ("expand-prompt", args.expand_prompt.as_ref()),
+    ];
+    for (option, reference) in prompts {
+        let Some(reference) = reference else {
+            continue;
+        };
+        match prompts::resolve(reference) {
+            Ok(prompt) => {
+                for name in placeholders(&prompt) {
+                    report(
+                        Some(option),
+                        format!(
+                            "prompt {reference} has a placeholder {{{name}}} that is never filled in"
+                        ),
+                    );
+                }
+            }
+            Err(e) => report(Some(option), format!("{e:#}")),
+        }
+    }
+    for name in placeholders(&args.image_prompt) {
+        report(
+            Some("image-prompt"),
+            format!("placeholder {{{name}}} is never filled in"),
+        );
+    }
+
+    match reqwest::Url::parse(&args.ollama_host) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        Ok(url) => report(
+            Some("ollama-host"),
+            format!(
+                "unsupported scheme {:?} (expected http or https)",
+                url.scheme()
+            ),
+        ),
+        Err(e) => report(
+            Some("ollama-host"),
+            format!("invalid URL {:?}: {e}", args.ollama_host),
+        ),
+    }
+    if let Err(e) = ollama::parse_action_options(&args.action_opts) {
+        report(Some("action-opt"), format!("{e:#}"));
+    }
+    if let Err(e) = ollama::parse_keep_alive(&args.keep_alive) {
+        report(Some("keep-alive"), format!("{e:#}"));
+    }
+    if let Err(e) = ollama::parse_model_options(&args.ollama_opts) {
+        report(Some("ollama-opt"), format!("{e:#}"));
+    }
+    if let Err(e) = spell::Spellcheck::new(&args.spellcheck_dicts) {
+        report(Some("spellcheck-dict"), format!("{e:#}"));
+    }
+    if let Err(e) = throttle::Throttle::new(&args.cooldowns, args.max_per_minute) {
+        report(Some("cooldown"), format!("{e:#}"));
+    }
+    match default_profile(args).and_then(profiles::load) {
+        Ok(profiles) => {
+            if let Some(name) = &args.profile
+                && !profiles.iter().any(|profile| &profile.name == name)
+            {
+                report(Some("profile"), format!("no profile named {name:?}"));
+            }
+        }
+        Err(e) => report(None, format!("{e:#}")),
+    }
+
+    if let Some(path) = &args.protected_terms
+        && let Err(e) = protect::load_terms(path)
+    {
+        report(Some("protected-terms"), format!("{e:#}"));
+    }
+    if let Some(path) = &args.filter_words
+        && let Err(e) = filter::load_words(path)
+    {
+        report(Some("filter-words"), format!("{e:#}"));
+    }
+    if let Some(path) = &args.script
+        && let Err(e) = script::Script::load(path)
+    {
+        report(Some("script"), format!("{e:#}"));
+    }
+    if let Some(path) = &args.whisper_model
+        && !path.is_file()
+    {
+        report(
+            Some("whisper-model"),
+            format!("{} is not a file", path.display()),
+        );
+    }
+
+    if problems.is_empty() {
+        println!("Config OK ({})", config::path().display());
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    anyhow::bail!("{} problem(s) found", problems.len())
+}
+
+/// The options that decide how hotkey presses are read.
+#[derive(Clone, Copy)]
+struct PressSource {
+    queue_size: usize,
+    #[cfg(target_os = "linux")]
+    portal: bool,
+    #[cfg(target_os = "linux")]
+    paranoid: bool,
+}
+
+impl PressSource {
+    fn new(args: &Args) -> Self {
+        Self {
+            queue_size: args.queue_size,
+            #[cfg(target_os = "linux")]
+            portal: args.portal,
+            #[cfg(target_os = "linux")]
+            paranoid: args.paranoid,
+        }
+    }
+}
+
+/// Start reading hotkey presses: from the keyboards in /dev/input (filtered
+/// by the kernel with --paranoid), or through the GlobalShortcuts portal
+/// with --portal.
+async fn start_presses(
+    source: PressSource,
+    hotkeys: Vec<Key>,
+    modes: &[Mode],
+    running: Arc<AtomicBool>,
+) -> Result<Arc<queue::PressQueue>> {
+    #[cfg(target_os = "linux")]
+    if source.portal {
+        if let Some(key) = hotkeys.iter().find(|key| key.device.is_some()) {
+            anyhow::bail!("{key}: the GlobalShortcuts portal can't bind a hotkey to one keyboard");
+        }
+        if let Some(key) = hotkeys.iter().find(|key| key.hotkey.is_none()) {
+            anyhow::bail!("{key}: numpad and media keys need the evdev reader, not --portal");
+        }
+        portal::init().await?;
+        let queue = queue::PressQueue::new(source.queue_size);
+        let shortcuts = hotkeys
+            .iter()
+            .zip(modes)
+            .map(|(hotkey, mode)| (hotkey.to_string(), mode.name().to_string()))
+            .collect();
+        portal::spawn_shortcuts(shortcuts, queue.clone());
+        return Ok(queue);
+    }
+    // Only the own evdev reader knows which keyboard a press came from, and
+    // numpad and media keys
+    #[cfg(target_os = "linux")]
+    if source.paranoid
+        || hotkeys
+            .iter()
+            .any(|key| key.device.is_some() || key.hotkey.is_none())
+    {
+        return paranoid::spawn(&hotkeys, source.queue_size, running);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = modes;
+
+    let handle = hotkeys
+        .into_iter()
+        .try_fold(HotkeyListenerBuilder::new(), |builder, key| {
+            let hotkey = key
+                .hotkey
+                .with_context(|| format!("{} needs the evdev reader", key.name))?;
+            anyhow::Ok(builder.add_hotkey(hotkey))
+        })?
         .build()?
         .start()?;
+    Ok(queue::PressQueue::spawn(handle, source.queue_size, running))
+}
+
+/// What the sandbox allows: the defaults, the control socket's directory,
+/// and the --sandbox-allow* additions.
+#[cfg(target_os = "linux")]
+fn sandbox_rules(args: &Args) -> sandbox::Rules {
+    let mut ports = vec![args.ollama_port];
+    if let Some(port) = args
+        .otlp_endpoint
+        .as_deref()
+        .and_then(|endpoint| reqwest::Url::parse(endpoint).ok())
+        .and_then(|url| url.port_or_known_default())
+    {
+        ports.push(port);
+    }
+    ports.extend(&args.sandbox_allow_port);
+
+    let mut rules = sandbox::Rules::new(ports);
+    if let Some(dir) = args.socket.as_deref().and_then(|path| path.parent()) {
+        rules.write.push(dir.to_path_buf());
+    }
+    if let Some(dir) = args.history_file.as_deref().and_then(|path| path.parent()) {
+        rules.write.push(dir.to_path_buf());
+    }
+    rules.write.extend(args.journal_dir.iter().cloned());
+    rules.write.extend(args.sandbox_allow.iter().cloned());
+    // The page edits the config file
+    if let Some(Command::Ui { port }) = &args.command {
+        rules.bind.push(*port);
+        rules
+            .write
+            .extend(config::path().parent().map(|dir| dir.to_path_buf()));
+    }
+    rules
+}
+
+fn main() -> Result<()> {
+    // Started as root to read the keyboards: use the invoking user's home and
+    // runtime directories from the start (config file, sockets, sandbox
+    // rules), while there is only this thread; privileges are dropped later
+    #[cfg(target_os = "linux")]
+    if let Some(user) = sandbox::invoking_user()? {
+        unsafe { sandbox::adopt_environment(&user) };
+    }
+
+    let args = Args::parse_from(config::merge_file::<Args>(std::env::args_os().collect())?);
+
+    // Before the runtime starts its threads, so the sandbox covers them all
+    #[cfg(target_os = "linux")]
+    let sandbox = if args.sandbox {
+        Some(format!("{:?}", sandbox::restrict(&sandbox_rules(&args))?))
+    } else {
+        None
+    };
+    #[cfg(not(target_os = "linux"))]
+    let sandbox = None;
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the tokio runtime")?
+        .block_on(run(args, sandbox))
+}
+
+/// `sandbox` describes how far the Landlock sandbox is enforced, if on.
+async fn run(mut args: Args, sandbox: Option<String>) -> Result<()> {
+    // Initialize logging
+    let level = if args.verbose { "debug" } else { "info" };
+    let _telemetry = telemetry::init(level, args.otlp_endpoint.as_deref())?;
+
+    if let Some(status) = sandbox {
+        tracing::info!("Landlock sandbox: {}", status);
+    }
+
+    i18n::init(args.lang.as_deref());
+    output::set_clipboard_backend(args.clipboard);
+    ollama::set_http_options(ollama::HttpOptions {
+        pool_idle: Duration::from_secs(args.http_pool_idle),
+        http2_prior_knowledge: args.http2_prior_knowledge,
+    });
+    let socket_path = args
+        .socket
+        .clone()
+        .unwrap_or_else(control::default_socket_path);
+
+    // Taken out so the rest of `args` stays whole for the checks below
+    let command = args.command.take();
+    if let Some(Command::CheckConfig) = command {
+        return check_config(&args);
+    }
+    #[cfg(target_os = "macos")]
+    if args.install_launchd {
+        let daemon_args = std::env::args()
+            .skip(1)
+            .filter(|arg| arg != "--install-launchd")
+            .collect();
+        return service::install(daemon_args).await;
+    }
+
+    let oneshot = match command {
+        Some(Command::NativeHost) => return native_host::run(&socket_path).await,
+        Some(Command::Ui { port }) => return ui::run(socket_path.clone(), port).await,
+        Some(Command::InstallNativeHost {
+            browser,
+            extension_id,
+        }) => return native_host::install(browser, &extension_id),
+        Some(Command::Prompts { action }) => {
+            return match action {
+                PromptsCommand::Add { source, name } => {
+                    prompts::add(&source, name.as_deref()).await
+                }
+                PromptsCommand::Update { name } => prompts::update(name.as_deref()).await,
+                PromptsCommand::List => prompts::list(),
+                PromptsCommand::Remove { name } => prompts::remove(&name),
+            };
+        }
+        Some(Command::Plugins) => return plugin::list(),
+        Some(Command::Service { action }) => {
+            return match action {
+                ServiceCommand::Install => {
+                    // The options given before the subcommand
+                    let daemon_args = std::env::args()
+                        .skip(1)
+                        .take_while(|arg| arg != "service")
+                        .collect();
+                    service::install(daemon_args).await
+                }
+                ServiceCommand::Start => service::start().await,
+                ServiceCommand::Stop => service::stop().await,
+                ServiceCommand::Status => service::status().await,
+            };
+        }
+        Some(Command::Pause) => {
+            control::call(&socket_path, "pause", serde_json::Value::Null).await?;
+            return Ok(());
+        }
+        Some(Command::Resume) => {
+            control::call(&socket_path, "resume", serde_json::Value::Null).await?;
+            return Ok(());
+        }
+        Some(Command::ShellInit { shell }) => {
+            shell::init(shell);
+            return Ok(());
+        }
+        Some(Command::GenerateCommand { text, refine }) => {
+            return shell::generate_command(&socket_path, &text, refine).await;
+        }
+        Some(Command::History { action }) => {
+            let path = args
+                .history_file
+                .clone()
+                .unwrap_or_else(history::default_path);
+            let history = history::History::open(&path)?;
+            return match action {
+                None => browse::run(history.load()?).await,
+                Some(HistoryCommand::Search { query, limit }) => {
+                    history::print(&history.search(&query.join(" "), limit)?);
+                    Ok(())
+                }
+            };
+        }
+        Some(Command::Hook { action }) => match action {
+            HookCommand::CommitMsg { file } => Some(Oneshot::CommitMsg(file)),
+            HookCommand::Install { force } => return hook::install(force).await,
+        },
+        Some(Command::File {
+            paths,
+            in_place,
+            suffix,
+        }) => Some(Oneshot::Files {
+            paths,
+            in_place,
+            suffix,
+        }),
+        Some(Command::Watch {
+            dir,
+            suffix,
+            interval,
+        }) => Some(Oneshot::Watch {
+            dir,
+            suffix,
+            interval,
+        }),
+        Some(Command::Tmux { task, buffer }) => Some(Oneshot::Tmux { task, buffer }),
+        #[cfg(target_os = "macos")]
+        Some(Command::Menubar) => return menubar::run(socket_path.clone()),
+        Some(Command::CheckConfig) => unreachable!("handled above"),
+        None => None,
+    };
+
+    let profile_base = default_profile(&args)?;
+    let profiles = profiles::load(profile_base.clone())?;
+    let improve_prompt = args.prompt.as_deref().map(prompts::resolve).transpose()?;
+    let command_prompt = args
+        .cmd_prompt
+        .as_deref()
+        .map(prompts::resolve)
+        .transpose()?;
+    let expand_prompt = args
+        .expand_prompt
+        .as_deref()
+        .map(prompts::resolve)
+        .transpose()?;
+
+    let protected_terms = match &args.protected_terms {
+        Some(path) => protect::load_terms(path)?,
+        None => Vec::new(),
+    };
+    let filter_words = match &args.filter_words {
+        Some(path) => filter::load_words(path)?,
+        None => Vec::new(),
+    };
+    let spellcheck = if args.spellcheck {
+        Some(spell::Spellcheck::new(&args.spellcheck_dicts)?)
+    } else {
+        None
+    };
+    let action_options = ollama::parse_action_options(&args.action_opts)?;
+    let keep_alive = ollama::parse_keep_alive(&args.keep_alive)?;
+    let model_options = ollama::parse_model_options(&args.ollama_opts)?;
+
+    // Stream progress into a notification if requested
+    let progress = args.progress_notifications.then(|| {
+        let (tx, rx) = tokio::sync::watch::channel(String::new());
+        notify::spawn_progress_notifier(rx);
+        tx
+    });
 
     // Create text improver
-    let improver =
-        ollama::TextImprover::new(&args.ollama_host, args.ollama_port, &args.ollama_model);
-    log::debug!(
+    let mut improver =
+        ollama::TextImprover::new(&args.ollama_host, args.ollama_port, &args.ollama_model)
+            .with_vision_model(args.vision_model.clone())
+            .with_prompts(improve_prompt, command_prompt)
+            .with_expand_prompt(expand_prompt)
+            .with_session_memory(args.session_memory)
+            .with_action_options(action_options)
+            .with_progress(progress)
+            .with_keep_alive(keep_alive)
+            .with_timeout(args.timeout)
+            .with_model_options(model_options)
+            .with_command_json(args.cmd_json)
+            .with_protect_code(!args.rewrite_code)
+            .with_preserve_quotes(args.preserve_quotes)
+            .with_protected_terms(protected_terms)
+            .with_spellcheck(spellcheck)
+            .with_relay(args.relay.clone())
+            .with_output_filter(args.output_filter, filter_words)
+            .with_shorten_limits(args.shorten_words, args.shorten_chars)
+            .with_simplify_level(args.simplify_level.clone())
+            .with_reply_style(
+                args.reply_tone.clone(),
+                args.reply_persona.clone(),
+                args.reply_signature
+                    .as_deref()
+                    .map(|s| s.replace("\\n", "\n")),
+            );
+    tracing::debug!(
         "Using Ollama at {}:{} with model {}",
         args.ollama_host,
         args.ollama_port,
         args.ollama_model
     );
 
-    // Setup Ctrl+C handler
+    // The default profile can be adjusted by [profiles.default] too
+    let name = args.profile.as_deref().unwrap_or(profiles::DEFAULT);
+    let profile = profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .with_context(|| format!("No profile named {name:?}"))?;
+    improver.apply_profile(profile)?;
+
+    match oneshot {
+        Some(Oneshot::CommitMsg(file)) => return hook::commit_msg(&improver, &file).await,
+        Some(Oneshot::Files {
+            paths,
+            in_place,
+            suffix,
+        }) => {
+            let pipeline = args.pipeline.as_ref();
+            let parallelism = args.chunk_parallelism as usize;
+            return batch::run(&improver, pipeline, parallelism, &paths, in_place, &suffix).await;
+        }
+        Some(Oneshot::Watch {
+            dir,
+            suffix,
+            interval,
+        }) => {
+            let pipeline = args.pipeline.as_ref();
+            let interval = Duration::from_secs(interval.max(1));
+            let parallelism = args.chunk_parallelism as usize;
+            return batch::watch(&improver, pipeline, parallelism, &dir, &suffix, interval).await;
+        }
+        Some(Oneshot::Tmux { task, buffer }) => return tmux::run(&improver, task, buffer).await,
+        None => {}
+    }
+
+    let mut models = vec![improver.model().to_string()];
+    for model in &args.standby_models {
+        if !models.contains(model) {
+            models.push(model.clone());
+        }
+    }
+    if let Some(secs) = args.residency_check {
+        tracing::info!("Checking where models run every {}s", secs);
+        improver.spawn_residency_monitor(
+            models.clone(),
+            Duration::from_secs(secs),
+            args.reload_evicted,
+        );
+    }
+    if !args.standby_models.is_empty() {
+        let secs = args.ping_interval.unwrap_or(60);
+        tracing::info!(
+            "Keeping {} warm with a ping every {}s",
+            models.join(", "),
+            secs
+        );
+        improver.spawn_standby(models, Duration::from_secs(secs));
+    } else if let Some(secs) = args.ping_interval {
+        tracing::info!("Keeping model warm with a ping every {}s", secs);
+        improver.spawn_keep_warm(Duration::from_secs(secs));
+    }
+
+    // Parse hotkeys
+    let bindings = hotkey_bindings(&args)?;
+    let press_source = PressSource::new(&args);
+    let conflicts = hotkey_conflicts(&bindings, args.show_original_key.is_none());
+    if !conflicts.is_empty() {
+        anyhow::bail!("Conflicting hotkeys:\n  {}", conflicts.join("\n  "));
+    }
+
+    #[cfg(target_os = "macos")]
+    tracing::info!(
+        "Note: You may need to grant Accessibility permissions for osascript to type text."
+    );
+
+    // Build and start the hotkey listener; event index i maps to modes[i]
+    let (hotkeys, modes): (Vec<_>, Vec<_>) = bindings.into_iter().unzip();
     let running = Arc::new(AtomicBool::new(true));
+    let presses = if args.headless {
+        tracing::info!("Headless: serving the control socket only");
+        None
+    } else {
+        Some(start_presses(press_source, hotkeys, &modes, running.clone()).await?)
+    };
+
+    if args.suspend_when_away
+        && let Some(presses) = &presses
+    {
+        idle::spawn_watcher(presses.clone());
+    }
+
+    // The keyboards are open; root isn't needed any more
+    #[cfg(target_os = "linux")]
+    if let Some(user) = sandbox::drop_privileges()? {
+        tracing::info!("Dropped root privileges, running as {}", user);
+    }
+
+    // Start the control socket, and the Neovim socket feeding the same queue
+    let (requests_tx, requests) = tokio::sync::mpsc::channel(16);
+    control::spawn_server(&socket_path, requests_tx.clone())?;
+    tracing::info!("Control socket: {}", socket_path.display());
+    let nvim_socket = args.nvim.then(|| nvim::socket_path(&socket_path));
+    if let Some(path) = &nvim_socket {
+        nvim::spawn_server(path, requests_tx)?;
+        tracing::info!("Neovim socket: {}", path.display());
+    }
+
+    // Setup Ctrl+C handler
     let r = running.clone();
     ctrlc::set_handler(move || {
-        log::info!("Received Ctrl+C, shutting down...");
+        tracing::info!("Received Ctrl+C, shutting down...");
         r.store(false, Release);
     })?;
 
     // Run the event loop
-    event_loop::run_event_loop(handle, improver, running).await?;
+    let settings = Settings {
+        output: args.output,
+        changes: args.changes,
+        critic: args.critic,
+        best_of: args.best_of.into(),
+        best_of_strategy: args.best_of_strategy,
+        pipeline: args.pipeline,
+        script: args
+            .script
+            .as_deref()
+            .map(script::Script::load)
+            .transpose()?,
+        candidates: args.candidates.into(),
+        readability: args.readability,
+        require_readable: args.require_readable,
+        check_facts: args.check_facts,
+        image_source: args.image_source,
+        image_prompt: args.image_prompt,
+        reasoning: args.reasoning,
+        type_delay: args.type_delay,
+        type_countdown: args.type_countdown,
+        check_focus: !args.ignore_focus,
+        refocus: args.refocus,
+        check_password_fields: !args.type_into_password_fields,
+        blocklist: window::Blocklist {
+            apps: args.block_apps.clone(),
+            titles: args.block_titles.clone(),
+        },
+        screen_share_guard: args.screen_share_guard,
+        models: args.models,
+        compare_models: args.compare_models,
+        compare_output: args.compare_output,
+        osd: args.osd.then(osd::Osd::spawn),
+        placeholder: args
+            .placeholder
+            .filter(|p| !p.is_empty())
+            .map(output::Placeholder::new),
+        journal: args.journal_dir.map(journal::Journal::new),
+        editor: args
+            .editor
+            .or_else(|| std::env::var("VISUAL").ok())
+            .or_else(|| std::env::var("EDITOR").ok())
+            .filter(|editor| !editor.trim().is_empty()),
+        history: if args.history {
+            let path = args.history_file.unwrap_or_else(history::default_path);
+            Some(history::History::open(&path)?)
+        } else {
+            None
+        },
+        throttle: throttle::Throttle::new(&args.cooldowns, args.max_per_minute)?,
+        concurrency: args.concurrency.max(1),
+        chunk_parallelism: args.chunk_parallelism as usize,
+        confirm_repeats: args.confirm_repeats,
+        fallback_to_clipboard: args.fallback_to_clipboard,
+        preserve_clipboard: args.preserve_clipboard,
+        clipboard_slots: args.clipboard_slots,
+        execute_commands: args.execute_commands,
+        review_commands: args.review_commands,
+        allow_dangerous_commands: args.allow_dangerous_commands,
+        command_history: args.cmd_history,
+        explanation_output: args.explain_cmd_output,
+        profiles: RwLock::new(profiles),
+        profile_base,
+        voice: args.whisper_model.map(|model| voice::Voice {
+            model,
+            program: args.whisper_bin,
+            seconds: args.voice_seconds,
+        }),
+        read_aloud: args.read_aloud.then(speech::ReadAloud::default),
+    };
+    event_loop::run_event_loop(presses, modes, improver, settings, requests, running).await?;
+
+    let _ = std::fs::remove_file(&socket_path);
+    if let Some(path) = nvim_socket {
+        let _ = std::fs::remove_file(path);
+    }
 
-    log::info!("Goodbye!");
+    tracing::info!("Goodbye!");
     Ok(())
 }
@@ -0,0 +1,112 @@
+/// Inline markers we understand, longest first so `**` wins over `*`.
+const MARKERS: [(&str, &str); 4] = [
+    ("**", "strong"),
+    ("__", "strong"),
+    ("`", "code"),
+    ("*", "em"),
+];
+
+/// Convert the small subset of Markdown that models typically emit (strong,
+/// emphasis, inline code, bullet lists and paragraphs) to an HTML fragment.
+pub fn to_html(text: &str) -> String {
+    let mut html = String::new();
+
+    for block in text.split("\n\n") {
+        let lines: Vec<&str> = block
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+
+        if lines.iter().all(|l| bullet_body(l).is_some()) {
+            html.push_str("<ul>");
+            for line in lines {
+                html.push_str("<li>");
+                html.push_str(&inline(bullet_body(line).unwrap_or(line), true));
+                html.push_str("</li>");
+            }
+            html.push_str("</ul>");
+        } else {
+            let body = lines
+                .iter()
+                .map(|l| inline(l, true))
+                .collect::<Vec<_>>()
+                .join("<br>");
+            html.push_str("<p>");
+            html.push_str(&body);
+            html.push_str("</p>");
+        }
+    }
+
+    html
+}
+
+/// Strip inline Markdown markers, leaving line structure intact.
+pub fn to_plain(text: &str) -> String {
+    text.lines()
+        .map(|l| inline(l, false))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn bullet_body(line: &str) -> Option<&str> {
+    line.strip_prefix("- ").or_else(|| line.strip_prefix("* "))
+}
+
+/// Render inline markers either as HTML tags or by dropping them. Markers
+/// without a matching close are kept as literal text.
+fn inline(text: &str, html: bool) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    'outer: while let Some(ch) = rest.chars().next() {
+        for (marker, tag) in MARKERS {
+            let Some(body) = rest.strip_prefix(marker) else {
+                continue;
+            };
+            let Some(end) = body.find(marker) else {
+                continue;
+            };
+            if end == 0 {
+                continue;
+            }
+
+            let inner = &body[..end];
+            let rendered = if tag == "code" {
+                if html {
+                    escape(inner)
+                } else {
+                    inner.to_string()
+                }
+            } else {
+                inline(inner, html)
+            };
+            if html {
+                out.push_str(&format!("<{tag}>{rendered}</{tag}>"));
+            } else {
+                out.push_str(&rendered);
+            }
+            rest = &body[end + marker.len()..];
+            continue 'outer;
+        }
+
+        if html {
+            out.push_str(&escape(ch.encode_utf8(&mut [0; 4])));
+        } else {
+            out.push(ch);
+        }
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,262 @@
+use anyhow::{Context, Result};
+use fluent_bundle::FluentArgs;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, NSObject, NSObjectProtocol, ProtocolObject, Sel};
+use objc2::{ClassType, DeclaredClass, declare_class, msg_send_id, mutability, sel};
+use objc2_app_kit::{
+    NSApplication, NSApplicationActivationPolicy, NSControlStateValueOn, NSMenu, NSMenuDelegate,
+    NSMenuItem, NSStatusBar, NSVariableStatusItemLength,
+};
+use objc2_foundation::{MainThreadMarker, NSString, ns_string};
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use tokio::runtime::Handle;
+
+use crate::control;
+use crate::i18n;
+use crate::output::copy_to_clipboard;
+
+/// Results listed under Recent.
+const RECENT: usize = 10;
+
+/// Characters of a recent result shown as its menu item.
+const TITLE_CHARS: usize = 40;
+
+struct Ivars {
+    socket: PathBuf,
+    /// Runs control socket calls; the main thread belongs to AppKit
+    runtime: Handle,
+    /// What the model and recent items stand for, by tag
+    models: RefCell<Vec<String>>,
+    recent: RefCell<Vec<String>>,
+}
+
+declare_class!(
+    /// Builds the menu each time it opens, from the running instance's
+    /// status, and sends the chosen item back over the control socket.
+    struct Menu;
+
+    unsafe impl ClassType for Menu {
+        type Super = NSObject;
+        type Mutability = mutability::MainThreadOnly;
+        const NAME: &'static str = "ImproveWritingMenu";
+    }
+
+    impl DeclaredClass for Menu {
+        type Ivars = Ivars;
+    }
+
+    unsafe impl Menu {
+        #[method(togglePause:)]
+        fn toggle_pause(&self, sender: &NSMenuItem) {
+            let paused = unsafe { sender.state() } == NSControlStateValueOn;
+            self.send(if paused { "resume" } else { "pause" }, Value::Null);
+        }
+
+        #[method(selectModel:)]
+        fn select_model(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() } as usize;
+            let model = self.ivars().models.borrow().get(tag).cloned();
+            if let Some(name) = model {
+                self.send("set_model", json!({ "name": name }));
+            }
+        }
+
+        #[method(copyRecent:)]
+        fn copy_recent(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() } as usize;
+            let Some(text) = self.ivars().recent.borrow().get(tag).cloned() else {
+                return;
+            };
+            self.ivars().runtime.spawn(async move {
+                if let Err(e) = copy_to_clipboard(&text).await {
+                    tracing::warn!("Failed to copy result to clipboard: {}", e);
+                }
+            });
+        }
+
+        #[method(quit:)]
+        fn quit(&self, _sender: &NSMenuItem) {
+            std::process::exit(0);
+        }
+    }
+
+    unsafe impl NSObjectProtocol for Menu {}
+
+    unsafe impl NSMenuDelegate for Menu {
+        #[method(menuNeedsUpdate:)]
+        fn menu_needs_update(&self, menu: &NSMenu) {
+            self.rebuild(menu);
+        }
+    }
+);
+
+impl Menu {
+    fn new(mtm: MainThreadMarker, socket: PathBuf, runtime: Handle) -> Retained<Self> {
+        let this = mtm.alloc().set_ivars(Ivars {
+            socket,
+            runtime,
+            models: RefCell::default(),
+            recent: RefCell::default(),
+        });
+        unsafe { msg_send_id![super(this), init] }
+    }
+
+    /// Call a control socket method, blocking the main thread until the
+    /// (local, quick) answer arrives.
+    fn call(&self, method: &'static str, params: Value) -> Result<Value> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let socket = self.ivars().socket.clone();
+        self.ivars().runtime.spawn(async move {
+            let _ = tx.send(control::call(&socket, method, params).await);
+        });
+        rx.recv().context("Control request was dropped")?
+    }
+
+    fn send(&self, method: &'static str, params: Value) {
+        if let Err(e) = self.call(method, params) {
+            tracing::warn!("Failed to {}: {:#}", method, e);
+        }
+    }
+
+    fn rebuild(&self, menu: &NSMenu) {
+        let mtm = MainThreadMarker::from(self);
+        menu.removeAllItems();
+
+        let status = match self.call("status", Value::Null) {
+            Ok(status) => status,
+            Err(e) => {
+                tracing::debug!("No running instance: {:#}", e);
+                menu.addItem(&self.item(mtm, &i18n::t("menu-not-running"), None, 0));
+                self.add_quit(mtm, menu);
+                return;
+            }
+        };
+
+        let pause = self.item(mtm, &i18n::t("menu-paused"), Some(sel!(togglePause:)), 0);
+        if status["paused"].as_bool().unwrap_or(false) {
+            unsafe { pause.setState(NSControlStateValueOn) };
+        }
+        menu.addItem(&pause);
+
+        // Models: the --models list, plus the current model if it isn't in it
+        let current = status["model"].as_str().unwrap_or_default().to_string();
+        let mut models: Vec<String> = status["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|model| model.as_str().map(str::to_string))
+            .collect();
+        if !models.contains(&current) {
+            models.insert(0, current.clone());
+        }
+        let submenu = NSMenu::new(mtm);
+        for (i, model) in models.iter().enumerate() {
+            let item = self.item(mtm, model, Some(sel!(selectModel:)), i);
+            if *model == current {
+                unsafe { item.setState(NSControlStateValueOn) };
+            }
+            submenu.addItem(&item);
+        }
+        *self.ivars().models.borrow_mut() = models;
+        let args = FluentArgs::from_iter([("model", current.as_str())]);
+        let item = self.item(mtm, &i18n::t_args("menu-model", Some(&args)), None, 0);
+        item.setSubmenu(Some(&submenu));
+        menu.addItem(&item);
+
+        // Recent results, copied to the clipboard when chosen
+        let recent: Vec<String> = match self.call("recent", json!({ "limit": RECENT })) {
+            Ok(result) => result["entries"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry["improved"].as_str().map(str::to_string))
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Failed to get recent results: {:#}", e);
+                Vec::new()
+            }
+        };
+        let submenu = NSMenu::new(mtm);
+        if recent.is_empty() {
+            submenu.addItem(&self.item(mtm, &i18n::t("menu-no-recent"), None, 0));
+        }
+        for (i, text) in recent.iter().enumerate() {
+            submenu.addItem(&self.item(mtm, &title(text), Some(sel!(copyRecent:)), i));
+        }
+        *self.ivars().recent.borrow_mut() = recent;
+        let item = self.item(mtm, &i18n::t("menu-recent"), None, 0);
+        item.setSubmenu(Some(&submenu));
+        menu.addItem(&item);
+
+        self.add_quit(mtm, menu);
+    }
+
+    fn add_quit(&self, mtm: MainThreadMarker, menu: &NSMenu) {
+        menu.addItem(&NSMenuItem::separatorItem(mtm));
+        menu.addItem(&self.item(mtm, &i18n::t("menu-quit"), Some(sel!(quit:)), 0));
+    }
+
+    /// A menu item sending `action` to this menu. Items without an action
+    /// are disabled unless they get a submenu.
+    fn item(
+        &self,
+        mtm: MainThreadMarker,
+        title: &str,
+        action: Option<Sel>,
+        tag: usize,
+    ) -> Retained<NSMenuItem> {
+        let item = unsafe {
+            NSMenuItem::initWithTitle_action_keyEquivalent(
+                mtm.alloc(),
+                &NSString::from_str(title),
+                action,
+                ns_string!(""),
+            )
+        };
+        if action.is_some() {
+            let target: &AnyObject = self;
+            unsafe {
+                item.setTarget(Some(target));
+                item.setTag(tag as isize);
+            }
+        }
+        item
+    }
+}
+
+/// A result on one line, cut to fit a menu item.
+fn title(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() <= TITLE_CHARS {
+        return line;
+    }
+    let cut: String = line.chars().take(TITLE_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+/// Show the menu bar item until quit. Runs AppKit's event loop on the main
+/// thread, so it must be called from there; control socket calls go to the
+/// tokio runtime's worker threads.
+pub fn run(socket: PathBuf) -> Result<()> {
+    let mtm = MainThreadMarker::new().context("The menu bar must run on the main thread")?;
+    let app = NSApplication::sharedApplication(mtm);
+    // A menu bar item only: no Dock icon or app menu
+    app.setActivationPolicy(NSApplicationActivationPolicy::Accessory);
+
+    let target = Menu::new(mtm, socket, Handle::current());
+    let menu = NSMenu::new(mtm);
+    menu.setDelegate(Some(ProtocolObject::from_ref(&*target)));
+
+    let status_bar = unsafe { NSStatusBar::systemStatusBar() };
+    let item = unsafe { status_bar.statusItemWithLength(NSVariableStatusItemLength) };
+    if let Some(button) = unsafe { item.button(mtm) } {
+        unsafe { button.setTitle(ns_string!("✎")) };
+    }
+    unsafe { item.setMenu(Some(&menu)) };
+
+    tracing::info!("Menu bar item shown");
+    unsafe { app.run() };
+    Ok(())
+}
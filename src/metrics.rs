@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::chunk;
+
+/// Where before/after readability scores are reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReadabilityReport {
+    /// Don't compute scores
+    Off,
+    /// Write scores to the log
+    Log,
+    /// Show scores in a desktop notification
+    Notify,
+}
+
+/// Readability statistics for a piece of English text.
+#[derive(Clone, Copy, Debug)]
+pub struct Readability {
+    pub words: usize,
+    pub sentences: usize,
+    /// Flesch reading ease: higher is easier (60–70 is plain English)
+    pub reading_ease: f64,
+    /// Flesch-Kincaid grade level: roughly the US school grade needed
+    pub grade: f64,
+}
+
+impl Readability {
+    pub fn of(text: &str) -> Self {
+        let sentences = chunk::sentences(text).len().max(1);
+        let words: Vec<&str> = text
+            .split_whitespace()
+            .filter(|w| w.chars().any(char::is_alphanumeric))
+            .collect();
+        let syllables: usize = words.iter().map(|w| syllables(w)).sum();
+
+        let word_count = words.len().max(1) as f64;
+        let words_per_sentence = word_count / sentences as f64;
+        let syllables_per_word = syllables as f64 / word_count;
+
+        Self {
+            words: words.len(),
+            sentences,
+            reading_ease: 206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word,
+            grade: 0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59,
+        }
+    }
+
+    pub fn avg_sentence_length(&self) -> f64 {
+        self.words as f64 / self.sentences as f64
+    }
+}
+
+impl fmt::Display for Readability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ease {:.0}, grade {:.1}, {:.1} words/sentence",
+            self.reading_ease,
+            self.grade,
+            self.avg_sentence_length()
+        )
+    }
+}
+
+/// One-line before → after summary.
+pub fn report(before: &Readability, after: &Readability) -> String {
+    format!(
+        "Readability ease {:.0} → {:.0}, grade {:.1} → {:.1}, words/sentence {:.1} → {:.1}",
+        before.reading_ease,
+        after.reading_ease,
+        before.grade,
+        after.grade,
+        before.avg_sentence_length(),
+        after.avg_sentence_length()
+    )
+}
+
+/// Estimate syllables by counting vowel groups, ignoring a silent final "e".
+fn syllables(word: &str) -> usize {
+    let word: Vec<char> = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+
+    let mut count = 0;
+    let mut prev_vowel = false;
+    for &c in &word {
+        let vowel = is_vowel(c);
+        if vowel && !prev_vowel {
+            count += 1;
+        }
+        prev_vowel = vowel;
+    }
+
+    if count > 1 && word.ends_with(&['e']) && !word.ends_with(&['l', 'e']) {
+        count -= 1;
+    }
+    count.max(1)
+}
@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::control;
+use crate::shell;
+
+/// Host name registered with the browser; extensions connect to this.
+const HOST_NAME: &str = "improve_writing";
+
+/// Browsers cap host-to-extension messages at 1 MB. Messages from the
+/// extension are held to the same limit, so a bad length can't make the
+/// host allocate gigabytes.
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Browser {
+    Firefox,
+    Chrome,
+    Chromium,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Serve the browser's native messaging protocol on stdin/stdout: each
+/// message is a 32-bit length in native byte order (little-endian on every
+/// platform we support) followed by that much JSON.
+/// Requests are forwarded to the running daemon over the control socket.
+pub async fn run(socket: &Path) -> Result<()> {
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+
+    loop {
+        let len = match stdin.read_u32_le().await {
+            Ok(len) => len as usize,
+            // The browser closes stdin when the extension disconnects
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e).context("Failed to read message length"),
+        };
+
+        let reply = if len > MAX_MESSAGE_BYTES {
+            tokio::io::copy(&mut (&mut stdin).take(len as u64), &mut tokio::io::sink())
+                .await
+                .context("Failed to read message")?;
+            json!({ "error": "Message too large" })
+        } else {
+            let mut buf = vec![0; len];
+            stdin
+                .read_exact(&mut buf)
+                .await
+                .context("Failed to read message")?;
+
+            match serde_json::from_slice::<Message>(&buf) {
+                Ok(message) => match control::call(socket, &message.method, message.params).await {
+                    Ok(result) => json!({ "result": result }),
+                    Err(e) => json!({ "error": format!("{e:#}") }),
+                },
+                Err(e) => json!({ "error": format!("Invalid message: {e}") }),
+            }
+        };
+
+        let mut out = serde_json::to_vec(&reply)?;
+        if out.len() > MAX_MESSAGE_BYTES {
+            out = serde_json::to_vec(&json!({ "error": "Response too large" }))?;
+        }
+        stdout.write_u32_le(out.len() as u32).await?;
+        stdout.write_all(&out).await?;
+        stdout.flush().await?;
+    }
+}
+
+/// Install the native messaging manifest (and a launcher script, since
+/// browsers can't pass arguments to the host) for the given extension.
+pub fn install(browser: Browser, extension_id: &str) -> Result<()> {
+    let home = PathBuf::from(std::env::var_os("HOME").context("HOME is not set")?);
+    let exe = std::env::current_exe().context("Failed to locate improve-writing binary")?;
+
+    let launcher = home.join(".local/share/improve-writing/native-host.sh");
+    std::fs::create_dir_all(launcher.parent().unwrap_or(&home))?;
+    std::fs::write(
+        &launcher,
+        format!(
+            "#!/bin/sh\nexec {} native-host\n",
+            shell::quote(&exe.display().to_string())
+        ),
+    )?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&launcher, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    let mut manifest = json!({
+        "name": HOST_NAME,
+        "description": "Improve selected text via Ollama",
+        "path": launcher,
+        "type": "stdio",
+    });
+    match browser {
+        Browser::Firefox => manifest["allowed_extensions"] = json!([extension_id]),
+        Browser::Chrome | Browser::Chromium => {
+            manifest["allowed_origins"] = json!([format!("chrome-extension://{extension_id}/")])
+        }
+    }
+
+    let dir = home.join(manifest_dir(browser));
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{HOST_NAME}.json"));
+    std::fs::write(&path, serde_json::to_string_pretty(&manifest)?)?;
+
+    println!("Installed launcher: {}", launcher.display());
+    println!("Installed manifest: {}", path.display());
+    Ok(())
+}
+
+/// Per-user manifest directory, relative to `$HOME`.
+#[cfg(target_os = "linux")]
+fn manifest_dir(browser: Browser) -> &'static str {
+    match browser {
+        Browser::Firefox => ".mozilla/native-messaging-hosts",
+        Browser::Chrome => ".config/google-chrome/NativeMessagingHosts",
+        Browser::Chromium => ".config/chromium/NativeMessagingHosts",
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn manifest_dir(browser: Browser) -> &'static str {
+    match browser {
+        Browser::Firefox => "Library/Application Support/Mozilla/NativeMessagingHosts",
+        Browser::Chrome => "Library/Application Support/Google/Chrome/NativeMessagingHosts",
+        Browser::Chromium => "Library/Application Support/Chromium/NativeMessagingHosts",
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use fluent_bundle::FluentArgs;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::watch;
+
+use crate::i18n;
+
+/// How much of an in-progress response the progress notification shows.
+const PREVIEW_CHARS: usize = 100;
+
+/// Minimum time between progress notification updates.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A desktop notification that can be updated in place where the platform
+/// allows it.
+///
+/// - Linux: `notify-send`, replacing the previous notification by id; the
+///   Notification portal with --portal
+/// - macOS: `osascript` `display notification` (cannot be updated; every
+///   `show` posts a new notification)
+#[derive(Default)]
+pub struct Notification {
+    id: Option<u32>,
+    shown: bool,
+}
+
+impl Notification {
+    #[cfg(target_os = "linux")]
+    pub async fn show(&mut self, summary: &str, body: &str) -> Result<()> {
+        if crate::portal::enabled() {
+            let id = *self.id.get_or_insert_with(crate::portal::notification_id);
+            crate::portal::notify(id, summary, body).await?;
+            self.shown = true;
+            return Ok(());
+        }
+
+        let mut cmd = Command::new("notify-send");
+        cmd.args(["--app-name=improve-writing", "--print-id"]);
+        if let Some(id) = self.id {
+            cmd.arg(format!("--replace-id={id}"));
+        }
+
+        // The body is interpreted as markup
+        let body = body
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let output = cmd.arg(summary).arg(body).output().await.with_context(|| {
+            format!(
+                "Failed to show notification ({})",
+                i18n::missing_tool("libnotify")
+            )
+        })?;
+
+        if !output.status.success() {
+            anyhow::bail!("notify-send failed: {:?}", output.status);
+        }
+
+        self.id = String::from_utf8_lossy(&output.stdout).trim().parse().ok();
+        self.shown = true;
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    pub async fn show(&mut self, summary: &str, body: &str) -> Result<()> {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let script = format!(
+            r#"display notification "{}" with title "{}""#,
+            escape(body),
+            escape(summary)
+        );
+
+        Command::new("osascript")
+            .arg("-e")
+            .arg(&script)
+            .status()
+            .await
+            .context("Failed to show notification via osascript")?;
+
+        self.shown = true;
+        Ok(())
+    }
+
+    /// Whether `show` replaces the existing notification rather than
+    /// posting another one.
+    fn updates_in_place() -> bool {
+        cfg!(target_os = "linux")
+    }
+}
+
+/// Show a one-off notification.
+pub async fn notify(summary: &str, body: &str) -> Result<()> {
+    Notification::default().show(summary, body).await
+}
+
+/// Have the screen reader announce a result.
+///
+/// - Linux: a notification, which Orca speaks and shows on a braille
+///   display as it arrives (read from AT-SPI)
+/// - macOS: VoiceOver's `output` command, falling back to a notification
+///   when VoiceOver isn't running or doesn't allow AppleScript control
+#[cfg(target_os = "linux")]
+pub async fn announce(text: &str) -> Result<()> {
+    notify(&i18n::t("result"), text).await
+}
+
+#[cfg(target_os = "macos")]
+pub async fn announce(text: &str) -> Result<()> {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let script = format!(
+        r#"if application "VoiceOver" is running then
+    tell application "VoiceOver" to output "{}"
+    return "spoken"
+end if"#,
+        escape(text)
+    );
+
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(&script)
+        .output()
+        .await
+        .context("Failed to announce result via osascript")?;
+
+    if output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "spoken" {
+        return Ok(());
+    }
+    tracing::debug!(
+        "VoiceOver didn't take the announcement: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    notify(&i18n::t("result"), text).await
+}
+
+/// Wait `seconds`, counting down in a notification. Where notifications
+/// can't be updated in place only the first one is shown.
+pub async fn countdown(summary: &str, seconds: u64) {
+    let mut notification = Notification::default();
+    for remaining in (1..=seconds).rev() {
+        if remaining == seconds || Notification::updates_in_place() {
+            let args = FluentArgs::from_iter([("seconds", remaining)]);
+            let body = i18n::t_args("typing-countdown-remaining", Some(&args));
+            if let Err(e) = notification.show(summary, &body).await {
+                tracing::warn!("Failed to show countdown: {}", e);
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Show the start of each streamed response in a notification as it arrives.
+///
+/// The sender side publishes the accumulated response text; an empty string
+/// marks the start of a new request. Where notifications can't be updated in
+/// place, a single notification is posted once the preview is full.
+pub fn spawn_progress_notifier(mut rx: watch::Receiver<String>) {
+    tokio::spawn(async move {
+        let mut notification = Notification::default();
+
+        while rx.changed().await.is_ok() {
+            let text = rx.borrow_and_update().clone();
+            if text.is_empty() {
+                notification = Notification::default();
+                continue;
+            }
+
+            let preview: String = text.chars().take(PREVIEW_CHARS).collect();
+            let full = preview.len() < text.len();
+            if !Notification::updates_in_place() && (notification.shown || !full) {
+                continue;
+            }
+
+            let preview = if full {
+                format!("{preview}…")
+            } else {
+                preview
+            };
+            if let Err(e) = notification.show(&i18n::t("generating"), &preview).await {
+                tracing::warn!("Failed to update progress notification: {}", e);
+            }
+
+            tokio::time::sleep(PROGRESS_INTERVAL).await;
+        }
+    });
+}
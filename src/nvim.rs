@@ -0,0 +1,137 @@
+use anyhow::Result;
+use rmpv::Value;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+
+use crate::control::{self, Envelope};
+
+// msgpack-RPC message types
+const REQUEST: u64 = 0;
+const RESPONSE: u64 = 1;
+
+/// The Neovim socket sits next to the control socket:
+/// `improve-writing.sock` -> `improve-writing.nvim.sock`.
+pub fn socket_path(control_socket: &Path) -> PathBuf {
+    control_socket.with_extension("nvim.sock")
+}
+
+/// Bind the Neovim socket and serve msgpack-RPC clients in the background.
+/// Requests go to `tx`, the same queue as the control socket, so they share
+/// the daemon's model and settings. See docs/protocol.md.
+pub fn spawn_server(path: &Path, tx: mpsc::Sender<Envelope>) -> Result<()> {
+    let listener = control::bind(path)?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve_connection(stream, tx.clone()));
+                }
+                Err(e) => tracing::warn!("Neovim socket accept failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Serve msgpack-RPC messages on one connection. Messages aren't delimited,
+/// so bytes are buffered until a whole value decodes.
+async fn serve_connection(stream: UnixStream, tx: mpsc::Sender<Envelope>) {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    loop {
+        loop {
+            let mut cursor = std::io::Cursor::new(&buf[..]);
+            let message = match rmpv::decode::read_value(&mut cursor) {
+                Ok(message) => message,
+                Err(e) if incomplete(&e) => break,
+                Err(e) => {
+                    tracing::debug!("Invalid msgpack from Neovim client: {}", e);
+                    return;
+                }
+            };
+            let used = cursor.position() as usize;
+            buf.drain(..used);
+
+            let Some(response) = handle_message(message, &tx).await else {
+                continue;
+            };
+            let mut out = Vec::new();
+            if rmpv::encode::write_value(&mut out, &response).is_err() {
+                continue;
+            }
+            if let Err(e) = writer.write_all(&out).await {
+                tracing::debug!("Neovim client went away: {}", e);
+                return;
+            }
+        }
+
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+/// Handle one message. Returns `None` for anything but a request
+/// (`[0, msgid, method, params]`), e.g. notifications.
+async fn handle_message(message: Value, tx: &mpsc::Sender<Envelope>) -> Option<Value> {
+    let Value::Array(fields) = message else {
+        return None;
+    };
+    let [Value::Integer(kind), id, method, Value::Array(params)] = fields.as_slice() else {
+        return None;
+    };
+    if kind.as_u64() != Some(REQUEST) {
+        return None;
+    }
+
+    let result = match method.as_str() {
+        Some(method) => match json_params(params) {
+            Ok(params) => control::forward(method, params, tx)
+                .await
+                .map_err(|(_, message)| message),
+            Err(message) => Err(message),
+        },
+        None => Err("Method must be a string".to_string()),
+    };
+
+    let (error, result) = match result.map(|result| rmpv::ext::to_value(&result)) {
+        Ok(Ok(result)) => (Value::Nil, result),
+        Ok(Err(e)) => (Value::from(e.to_string()), Value::Nil),
+        Err(message) => (Value::from(message), Value::Nil),
+    };
+    Some(Value::Array(vec![
+        Value::from(RESPONSE),
+        id.clone(),
+        error,
+        result,
+    ]))
+}
+
+/// Neovim passes call arguments as an array: `rpcrequest(chan, "improve",
+/// {text = ...})` arrives as `[{text = ...}]`. A lone string is shorthand for
+/// `{text = ...}`, and no arguments means no params.
+fn json_params(params: &[Value]) -> Result<serde_json::Value, String> {
+    match params {
+        [] => Ok(serde_json::Value::Null),
+        [Value::String(text)] => Ok(serde_json::json!({
+            "text": text.as_str().ok_or("Text must be valid UTF-8")?,
+        })),
+        [params] => rmpv::ext::from_value(params.clone()).map_err(|e| e.to_string()),
+        _ => Err("Expected at most one argument".to_string()),
+    }
+}
+
+/// Whether decoding failed only because the rest of the value hasn't arrived.
+fn incomplete(e: &rmpv::decode::Error) -> bool {
+    match e {
+        rmpv::decode::Error::InvalidMarkerRead(e) | rmpv::decode::Error::InvalidDataRead(e) => {
+            e.kind() == std::io::ErrorKind::UnexpectedEof
+        }
+        _ => false,
+    }
+}
@@ -1,10 +1,29 @@
 use anyhow::{Context, Result};
 use ollama_rs::{
     Ollama,
-    generation::chat::{ChatMessage, request::ChatMessageRequest},
-    generation::parameters::KeepAlive,
+    generation::chat::{ChatMessage, ChatMessageResponse, request::ChatMessageRequest},
+    generation::images::Image,
+    generation::parameters::{FormatType, KeepAlive, TimeUnit},
+    models::ModelOptions,
 };
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio_stream::StreamExt;
+
+use crate::comment::Comment;
+use crate::control;
+use crate::filter::{self, OutputFilter};
+use crate::postprocess::{self, Limit};
+use crate::profiles;
+use crate::protect::{self, Protected};
+use crate::residency;
+use crate::select;
+use crate::spell::Spellcheck;
 
 const DEFAULT_PROMPT: &str = r#"Improve the following text for clarity, grammar, and style.
 Keep the original meaning and tone.
@@ -16,39 +35,1664 @@ Output only the command, nothing else.
 Do not add explanations, commentary, or markdown formatting.
 If multiple commands are needed, combine them on a single line using && or pipes."#;
 
+/// Appended to the command prompt in JSON mode.
+const COMMAND_JSON_SUFFIX: &str = r#"Respond only with a JSON object of the form
+{"command": "<the shell command>", "explanation": "<one short sentence on what it does>"}"#;
+
+const SUMMARY_PROMPT: &str = r#"Summarize the following web page content.
+Focus on the main points and key facts.
+Only output the summary, nothing else.
+Do not add explanations or commentary."#;
+
+const EXPAND_PROMPT: &str = r#"Expand the following notes, bullet points, or one-liner into clear, well-structured prose.
+Keep every point and do not invent facts that are not implied by the notes.
+Use paragraphs where it helps the structure.
+Only output the expanded text, nothing else.
+Do not add explanations or commentary."#;
+
+const CRITIC_PROMPT: &str = r#"You review a rewrite of a text against its original.
+Check that the rewrite keeps the original meaning, facts, names, numbers, and tone,
+and that it is clear, grammatical, and free of awkward phrasing.
+Leave code and quoted passages exactly as they are in the original.
+Output only the corrected final version of the rewrite, nothing else.
+If the rewrite needs no changes, output it unchanged.
+Do not add explanations or commentary."#;
+
+const JUDGE_PROMPT: &str = r#"You compare numbered rewrites of a text against its original.
+Pick the rewrite that best keeps the original meaning, facts, names, numbers, and tone
+while being the clearest, most grammatical, and most natural.
+Output only the number of the best rewrite, nothing else."#;
+
+/// `{words}` and `{chars}` are replaced with the configured limits.
+const SHORTEN_PROMPT: &str = r#"Shorten the following text to at most {words} words and {chars} characters,
+for example for a social media post.
+Keep the key message and the original tone.
+Only output the shortened text, nothing else.
+Do not add explanations or commentary."#;
+
+const COMMENT_PROMPT: &str = r#"Improve the following code comment or doc comment for clarity, grammar, and concision.
+Keep every identifier, path, and piece of code (including anything in backticks) exactly as written.
+Keep the meaning, and keep lists, examples, and doc tags such as @param.
+Write plain text without comment markers; lines are wrapped afterwards.
+Only output the improved comment, nothing else.
+Do not add explanations or commentary."#;
+
+/// `{instruction}` is replaced with the transcribed voice instruction.
+const VOICE_PROMPT: &str = r#"Rewrite the following text as instructed: {instruction}
+Change only what the instruction asks for and keep the meaning.
+Only output the rewritten text, nothing else.
+Do not add explanations or commentary."#;
+
+/// `{tone}` is replaced with the configured tone.
+const REPLY_PROMPT: &str = r#"The following is an email I received. Draft my reply to it.
+Write in a {tone} tone and answer every question it asks.
+Do not invent facts, dates, or commitments; put details I need to fill in in [brackets].
+Do not include a subject line, a greeting to myself, or a signature.
+Only output the reply, nothing else.
+Do not add explanations or commentary."#;
+
+/// `{level}` is replaced with the configured reading level.
+const SIMPLIFY_PROMPT: &str = r#"Rewrite the following text in plain language for readers at reading level {level}.
+Use short sentences and common words, and explain any technical term you have to keep.
+Keep every fact and do not add new ones.
+Only output the rewritten text, nothing else.
+Do not add explanations or commentary."#;
+
+const BULLETIZE_PROMPT: &str = r#"Turn the following text into concise bullet points.
+Keep every fact, name, and number; drop filler words, not information.
+Start each bullet with "- " on a line of its own.
+Only output the bullet points, nothing else.
+Do not add explanations or commentary."#;
+
+const PROSE_PROMPT: &str = r#"Turn the following bullet points into flowing prose.
+Keep every fact, name, and number, and do not add new ones.
+Connect related points into sentences and group them into paragraphs.
+Only output the prose, nothing else.
+Do not add explanations or commentary."#;
+
+/// `{count}` is replaced with the number of titles wanted.
+const TITLE_PROMPT: &str = r#"Suggest {count} different subject lines or titles for the following text,
+for example for an email or a commit.
+Each is at most 10 words, specific to the text, and without a trailing period.
+Output one title per line, without numbering, quotes, or anything else.
+Do not add explanations or commentary."#;
+
+const COMMIT_PROMPT: &str = r#"Rewrite the following git commit message as a Conventional Commit.
+The first line is "type(scope): summary": type is one of feat, fix, docs, style, refactor,
+perf, test, build, ci, chore or revert, the scope is optional, and the summary is in the
+imperative mood, starts lower case, has no trailing period, and is at most 72 characters.
+If the message has a body, keep its meaning, put it after a blank line, and wrap it at 72 characters.
+Keep trailer lines such as "Signed-off-by:" exactly as they are.
+Only output the commit message, nothing else.
+Do not add explanations, commentary, or markdown formatting."#;
+
+const EXPLAIN_PROMPT: &str = r#"The following is output copied from a terminal.
+Explain what it means in a few short paragraphs. If it shows an error, say what most likely
+caused it and how to fix it, with the exact commands if there are any.
+Be concise and do not repeat the output back."#;
+
+const EXPLAIN_COMMAND_PROMPT: &str = r#"The following is a shell command.
+Explain it in plain English for someone who does not know these tools: one line per part
+(each program, option, argument, pipe and redirection), as "part - what it does",
+followed by one sentence on what the whole command does.
+If it deletes or overwrites anything, say so.
+Do not use markdown formatting. Only output the explanation, nothing else."#;
+
+const FIX_COMMAND_PROMPT: &str = r#"The following is the end of a terminal session.
+Find the last command that was run and failed, work out from its output why it failed,
+and write a corrected command that does what was intended.
+Only output the corrected command on a single line, nothing else.
+Do not add explanations, commentary, or markdown formatting."#;
+
+const TRANSLATE_PROMPT: &str = r#"Translate the following text into {language}.
+Keep the meaning, tone, and formatting (line breaks, lists, markdown) of the original.
+Only output the translation, nothing else."#;
+
+const REVIEW_COMMAND_PROMPT: &str = r#"The following is a shell command that is about to be run.
+Explain what it does in one or two short sentences.
+If it deletes, overwrites, or changes files, disks, permissions, processes, or remote state,
+or downloads and runs code, start your answer with "WARNING:" and say what could be lost.
+Only output the explanation, nothing else."#;
+
+/// How many times the model is asked again when a shortened text is still
+/// over the limits, before it is truncated.
+const SHORTEN_RETRIES: usize = 2;
+
+/// The kinds of request we send, each of which can carry its own
+/// generation options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Improve,
+    Command,
+    Summarize,
+    Image,
+    Shorten,
+    Expand,
+    Critic,
+    Judge,
+    Commit,
+    Comment,
+    Reply,
+    Title,
+    Simplify,
+    Bulletize,
+    Prose,
+    Explain,
+    ExplainCommand,
+    ReviewCommand,
+    FixCommand,
+    Translate,
+    Voice,
+}
+
+/// Names of the actions, as used by `--action-opt NAME.key=value`.
+const ACTIONS: [(&str, Action); 21] = [
+    ("improve", Action::Improve),
+    ("command", Action::Command),
+    ("summarize", Action::Summarize),
+    ("image", Action::Image),
+    ("shorten", Action::Shorten),
+    ("expand", Action::Expand),
+    ("critic", Action::Critic),
+    ("judge", Action::Judge),
+    ("commit", Action::Commit),
+    ("comment", Action::Comment),
+    ("reply", Action::Reply),
+    ("title", Action::Title),
+    ("simplify", Action::Simplify),
+    ("bulletize", Action::Bulletize),
+    ("prose", Action::Prose),
+    ("explain", Action::Explain),
+    ("explain-command", Action::ExplainCommand),
+    ("review-command", Action::ReviewCommand),
+    ("fix-command", Action::FixCommand),
+    ("translate", Action::Translate),
+    ("voice", Action::Voice),
+];
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some((_, action)) = ACTIONS.iter().find(|(name, _)| *name == s) {
+            return Ok(*action);
+        }
+        let names = ACTIONS.map(|(name, _)| name);
+        anyhow::bail!(
+            "Unknown action {s:?} (expected {} or {})",
+            names[..names.len() - 1].join(", "),
+            names[names.len() - 1]
+        )
+    }
+}
+
+/// Rewrites that are nothing but a preset prompt over the selection, run
+/// through `TextImprover::rewrite`. Each has an action of its own, so it can
+/// carry its own generation options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Plain language at the configured reading level
+    Simplify,
+    /// Prose into bullet points
+    Bulletize,
+    /// Bullet points into prose
+    Prose,
+}
+
+impl Profile {
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Simplify => "simplify",
+            Profile::Bulletize => "bulletize",
+            Profile::Prose => "prose",
+        }
+    }
+
+    fn action(self) -> Action {
+        match self {
+            Profile::Simplify => Action::Simplify,
+            Profile::Bulletize => Action::Bulletize,
+            Profile::Prose => Action::Prose,
+        }
+    }
+
+    /// Whether the result's line breaks are part of it, so they are typed
+    /// rather than flattened.
+    pub fn keeps_newlines(self) -> bool {
+        matches!(self, Profile::Bulletize | Profile::Prose)
+    }
+}
+
+const COMPRESS_PROMPT: &str = r#"Rewrite the following text in at most {chars} characters.
+Keep the key message, the original tone, and the language.
+Only output the rewritten text, nothing else."#;
+
+/// What to do when a response exceeds an action's `max_chars`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Ask the model once to shorten it, then truncate if still too long
+    #[default]
+    Retry,
+    /// Truncate straight away
+    Truncate,
+}
+
+/// Per-action overrides for how a request is generated. Unset fields fall
+/// back to the global defaults.
+#[derive(Clone, Debug, Default)]
+pub struct GenerationOptions {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub keep_alive: Option<KeepAlive>,
+    pub think: Option<bool>,
+    pub max_chars: Option<Limit>,
+    pub overflow: Overflow,
+    pub stop: Vec<String>,
+    pub filter: Option<OutputFilter>,
+    pub timeout: Option<Duration>,
+}
+
+impl GenerationOptions {
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "model" => self.model = Some(value.to_string()),
+            "temperature" => self.temperature = Some(value.parse().context("Invalid temperature")?),
+            "max_tokens" | "num_predict" => {
+                self.max_tokens = Some(value.parse().context("Invalid max_tokens")?)
+            }
+            "keep_alive" => self.keep_alive = Some(parse_keep_alive(value)?),
+            "think" => self.think = Some(value.parse().context("think must be true or false")?),
+            // Repeatable; `\n` and `\t` escapes allow e.g. stopping at a blank line
+            "stop" => self
+                .stop
+                .push(value.replace("\\n", "\n").replace("\\t", "\t")),
+            "max_chars" => self.max_chars = Some(value.parse().context("Invalid max_chars")?),
+            "overflow" => {
+                self.overflow = match value {
+                    "retry" => Overflow::Retry,
+                    "truncate" => Overflow::Truncate,
+                    _ => anyhow::bail!("overflow must be retry or truncate"),
+                }
+            }
+            "filter" => self.filter = Some(value.parse()?),
+            "timeout" => self.timeout = Some(parse_timeout(value)?),
+            _ => anyhow::bail!(
+                "Unknown option {key:?} (expected model, temperature, max_tokens, keep_alive, think, max_chars, overflow, stop, filter or timeout)"
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Parse a request timeout in seconds (`10`, `2.5`), which must be positive.
+pub fn parse_timeout(value: &str) -> Result<Duration> {
+    value
+        .trim_end_matches('s')
+        .parse()
+        .ok()
+        .filter(|seconds: &f64| *seconds > 0.0)
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .with_context(|| format!("Invalid timeout {value:?} (expected seconds, e.g. 10)"))
+}
+
+/// A request that got no complete response in time. Not retried: a model
+/// that is stuck or still loading won't answer the same request faster.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No response from Ollama within {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// A `--cmd-json` reply that isn't the expected JSON. Not typed: it may be
+/// prose, or JSON with the command somewhere inside.
+#[derive(Debug)]
+pub struct InvalidCommandReply;
+
+impl std::fmt::Display for InvalidCommandReply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Command response was not the expected JSON")
+    }
+}
+
+impl std::error::Error for InvalidCommandReply {}
+
+/// One non-streaming request, given up on after `timeout`. The HTTP client
+/// has no overall timeout, so every request outside `send_with_retry` goes
+/// through here.
+pub async fn send_within(
+    ollama: &Ollama,
+    request: ChatMessageRequest,
+    timeout: Duration,
+) -> Result<ChatMessageResponse> {
+    match tokio::time::timeout(timeout, ollama.send_chat_messages(request)).await {
+        Ok(response) => Ok(response?),
+        Err(_) => Err(TimedOut { timeout }.into()),
+    }
+}
+
+/// Parse `ACTION.KEY=VALUE` specs (e.g. `command.think=true`) into
+/// per-action options.
+pub fn parse_action_options(specs: &[String]) -> Result<HashMap<Action, GenerationOptions>> {
+    let mut options: HashMap<Action, GenerationOptions> = HashMap::new();
+    for spec in specs {
+        let (target, value) = spec
+            .split_once('=')
+            .with_context(|| format!("Expected ACTION.KEY=VALUE, got {spec:?}"))?;
+        let (action, key) = target
+            .split_once('.')
+            .with_context(|| format!("Expected ACTION.KEY=VALUE, got {spec:?}"))?;
+        options
+            .entry(action.parse()?)
+            .or_default()
+            .set(key, value)
+            .with_context(|| format!("Invalid action option {spec:?}"))?;
+    }
+    Ok(options)
+}
+
+/// Strip a leading bullet or number ("- ", "2. ", "3) "), which models add
+/// to lists whatever they are told.
+fn strip_list_marker(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix(['-', '*', '•']) {
+        return rest.trim_start();
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match line[digits..].strip_prefix(['.', ')']) {
+        Some(rest) if digits > 0 && rest.starts_with(' ') => rest.trim_start(),
+        _ => line,
+    }
+}
+
+/// Parse a keep-alive duration: `-1`/`forever` keeps the model loaded, `0`
+/// unloads it after each request, and `30s`/`10m`/`2h` keep it that long.
+pub fn parse_keep_alive(value: &str) -> Result<KeepAlive> {
+    match value {
+        "-1" | "forever" => return Ok(KeepAlive::Indefinitely),
+        "0" => return Ok(KeepAlive::UnloadOnCompletion),
+        _ => {}
+    }
+
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], TimeUnit::Seconds),
+        Some((i, 'm')) => (&value[..i], TimeUnit::Minutes),
+        Some((i, 'h')) => (&value[..i], TimeUnit::Hours),
+        _ => anyhow::bail!("Invalid keep-alive {value:?} (expected -1, 0, or e.g. 30s, 10m, 2h)"),
+    };
+    let time = number
+        .parse()
+        .with_context(|| format!("Invalid keep-alive {value:?}"))?;
+    Ok(KeepAlive::Until { time, unit })
+}
+
+/// Parse `KEY=VALUE` Ollama model options (e.g. `num_ctx=8192`) into a
+/// `ModelOptions`. Values are typed as integer, float, or bool when they
+/// parse as such; `stop` may be repeated to add several stop sequences.
+pub fn parse_model_options(specs: &[String]) -> Result<ModelOptions> {
+    let mut map = serde_json::Map::new();
+    for spec in specs {
+        let (key, value) = spec
+            .split_once('=')
+            .with_context(|| format!("Expected KEY=VALUE, got {spec:?}"))?;
+
+        if key == "stop" {
+            let stops = map
+                .entry("stop")
+                .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+            if let serde_json::Value::Array(stops) = stops {
+                stops.push(value.into());
+            }
+            continue;
+        }
+
+        let value = if let Ok(n) = value.parse::<i64>() {
+            n.into()
+        } else if let Ok(f) = value.parse::<f64>() {
+            f.into()
+        } else if let Ok(b) = value.parse::<bool>() {
+            b.into()
+        } else {
+            value.into()
+        };
+        map.insert(key.to_string(), value);
+    }
+
+    let requested = map.clone();
+    let options: ModelOptions = serde_json::from_value(serde_json::Value::Object(map))
+        .context("Invalid Ollama option value")?;
+
+    // Options the client doesn't know are silently dropped by serde; catch
+    // them by checking what survives a round trip
+    let accepted = serde_json::to_value(&options)?;
+    for key in requested.keys() {
+        if accepted.get(key).is_none_or(|v| v.is_null()) {
+            anyhow::bail!("Unsupported Ollama option {key:?}");
+        }
+    }
+
+    Ok(options)
+}
+
+/// A generated shell command, with the model's explanation in JSON mode.
+#[derive(Debug, Deserialize)]
+pub struct GeneratedCommand {
+    pub command: String,
+    #[serde(default)]
+    pub explanation: Option<String>,
+}
+
+/// `generate_command` result from a relay.
+#[derive(Deserialize)]
+struct RelayedCommand {
+    text: String,
+    #[serde(default)]
+    explanation: Option<String>,
+}
+
+#[tracing::instrument(name = "relay", skip(text))]
+async fn relay_call(
+    socket: &Path,
+    method: &str,
+    text: &str,
+    refine: bool,
+) -> Result<serde_json::Value> {
+    tracing::debug!("Relaying {} to {}", method, socket.display());
+    control::call(
+        socket,
+        method,
+        serde_json::json!({ "text": text, "refine": refine }),
+    )
+    .await
+    .context("Relay request failed")
+}
+
 pub struct TextImprover {
     ollama: Ollama,
     model: String,
+    vision_model: Option<String>,
+    improve_prompt: String,
+    command_prompt: String,
+    expand_prompt: String,
+    command_json: bool,
+    protect_code: bool,
+    preserve_quotes: bool,
+    shorten_words: usize,
+    shorten_chars: usize,
+    reply_tone: String,
+    reply_persona: Option<String>,
+    reply_signature: Option<String>,
+    simplify_level: String,
+    protected_terms: Vec<String>,
+    spellcheck: Option<Spellcheck>,
+    output_filter: OutputFilter,
+    filter_words: Vec<String>,
+    options: HashMap<Action, GenerationOptions>,
+    keep_alive: KeepAlive,
+    /// Time limit for a request, unless an action sets its own
+    timeout: Duration,
+    model_options: ModelOptions,
     history: Vec<ChatMessage>,
+    /// Recent (original, improved) pairs, oldest first, given to new improve
+    /// requests as examples
+    session: VecDeque<(String, String)>,
+    session_size: usize,
+    /// Session entry for the improve conversation in `history`, which a
+    /// refinement replaces
+    refined: Option<(String, String)>,
+    instructions: Option<String>,
+    last_reasoning: Mutex<Option<String>>,
+    progress: Option<watch::Sender<String>>,
+    relay: Option<PathBuf>,
+    /// Name of the active profile
+    profile: String,
+    /// Host and port of the profile's backend, which routes fill in from
+    backend: (String, u16),
+    /// Backend and model to go back to after a routed request
+    unrouted: Option<(Ollama, String)>,
+    /// Clients for the other backends routes have used, so routed requests
+    /// reuse their pooled connections
+    routed: HashMap<(String, u16), Ollama>,
+}
+
+/// How connections to Ollama are kept and reused.
+#[derive(Clone, Copy, Debug)]
+pub struct HttpOptions {
+    /// How long an idle connection is kept for reuse; zero disables pooling
+    pub pool_idle: Duration,
+    /// Speak HTTP/2 without negotiating it first (h2c), for backends that
+    /// accept it on plain connections. HTTPS backends negotiate HTTP/2 anyway.
+    pub http2_prior_knowledge: bool,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            pool_idle: Duration::from_secs(30),
+            http2_prior_knowledge: false,
+        }
+    }
+}
+
+static HTTP_OPTIONS: OnceLock<HttpOptions> = OnceLock::new();
+
+/// Choose how Ollama connections are pooled, once at startup, before the
+/// first `TextImprover` is created.
+pub fn set_http_options(options: HttpOptions) {
+    let _ = HTTP_OPTIONS.set(options);
+}
+
+/// Pooled connections are probed this often, so one the server or a NAT
+/// dropped is noticed before a request goes out on it.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+fn http_client() -> reqwest::Client {
+    let options = HTTP_OPTIONS.get().copied().unwrap_or_default();
+    let builder = reqwest::Client::builder()
+        // Requests are timed per action, see `send_with_retry`
+        .connect_timeout(Duration::from_secs(10))
+        .tcp_keepalive(KEEPALIVE_INTERVAL);
+    let builder = if options.pool_idle.is_zero() {
+        builder.pool_max_idle_per_host(0)
+    } else {
+        builder
+            .pool_idle_timeout(options.pool_idle)
+            .http2_keep_alive_interval(KEEPALIVE_INTERVAL)
+            .http2_keep_alive_timeout(Duration::from_secs(5))
+            .http2_keep_alive_while_idle(true)
+    };
+    let builder = if options.http2_prior_knowledge {
+        builder.http2_prior_knowledge()
+    } else {
+        builder
+    };
+    builder.build().expect("Failed to create HTTP client")
+}
+
+/// Whether a request failed because the pooled connection it went out on
+/// had been closed by the other end, rather than because of the server.
+fn is_stale_connection(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_request() && !e.is_timeout() && !e.is_connect();
+        }
+        cause.downcast_ref::<std::io::Error>().is_some_and(|e| {
+            matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            )
+        })
+    })
 }
 
 impl TextImprover {
     pub fn new(host: &str, port: u16, model: &str) -> Self {
-        let client = reqwest::Client::builder()
-            .connect_timeout(Duration::from_secs(10))
-            .timeout(Duration::from_secs(120))
-            .pool_idle_timeout(Duration::from_secs(60))
-            .pool_max_idle_per_host(0) // Disable connection pooling
-            .build()
-            .expect("Failed to create HTTP client");
-
         Self {
-            ollama: Ollama::new_with_client(host.to_string(), port, client),
+            ollama: Ollama::new_with_client(host.to_string(), port, http_client()),
             model: model.to_string(),
+            vision_model: None,
+            improve_prompt: DEFAULT_PROMPT.to_string(),
+            command_prompt: COMMAND_PROMPT.to_string(),
+            expand_prompt: EXPAND_PROMPT.to_string(),
+            command_json: false,
+            protect_code: true,
+            preserve_quotes: false,
+            shorten_words: 25,
+            shorten_chars: 280,
+            reply_tone: "friendly, professional".to_string(),
+            reply_persona: None,
+            reply_signature: None,
+            simplify_level: "CEFR B1".to_string(),
+            protected_terms: Vec::new(),
+            spellcheck: None,
+            output_filter: OutputFilter::Off,
+            filter_words: Vec::new(),
+            options: HashMap::new(),
+            keep_alive: KeepAlive::Indefinitely,
+            timeout: Duration::from_secs(120),
+            model_options: ModelOptions::default(),
             history: Vec::new(),
+            session: VecDeque::new(),
+            session_size: 0,
+            refined: None,
+            instructions: None,
+            last_reasoning: Mutex::new(None),
+            progress: None,
+            relay: None,
+            profile: profiles::DEFAULT.to_string(),
+            backend: (host.to_string(), port),
+            unrouted: None,
+            routed: HashMap::new(),
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// A copy with the same configuration but no conversation history or
+    /// progress notifier, for one-shot requests run alongside this one.
+    pub fn fork(&self) -> Self {
+        Self {
+            ollama: self.ollama.clone(),
+            model: self.model.clone(),
+            vision_model: self.vision_model.clone(),
+            improve_prompt: self.improve_prompt.clone(),
+            command_prompt: self.command_prompt.clone(),
+            expand_prompt: self.expand_prompt.clone(),
+            command_json: self.command_json,
+            protect_code: self.protect_code,
+            preserve_quotes: self.preserve_quotes,
+            shorten_words: self.shorten_words,
+            shorten_chars: self.shorten_chars,
+            reply_tone: self.reply_tone.clone(),
+            reply_persona: self.reply_persona.clone(),
+            reply_signature: self.reply_signature.clone(),
+            simplify_level: self.simplify_level.clone(),
+            protected_terms: self.protected_terms.clone(),
+            spellcheck: self.spellcheck.clone(),
+            output_filter: self.output_filter,
+            filter_words: self.filter_words.clone(),
+            options: self.options.clone(),
+            keep_alive: self.keep_alive.clone(),
+            timeout: self.timeout,
+            model_options: self.model_options.clone(),
+            history: Vec::new(),
+            session: self.session.clone(),
+            session_size: self.session_size,
+            refined: None,
+            instructions: self.instructions.clone(),
+            last_reasoning: Mutex::new(None),
+            progress: None,
+            relay: self.relay.clone(),
+            profile: self.profile.clone(),
+            backend: self.backend.clone(),
+            unrouted: None,
+            routed: self.routed.clone(),
+        }
+    }
+
+    /// Name of the active profile.
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Switch to `profile`'s backend, model, prompts, reply tone, and output
+    /// filter. The conversation and session memory are dropped, so no text
+    /// carries over from one profile to another.
+    pub fn apply_profile(&mut self, profile: &profiles::Profile) -> Result<()> {
+        profile.check()?;
+        if profile.local_only && self.relay.is_some() {
+            anyhow::bail!(
+                "Profile {:?} is local-only and can't be used with --relay",
+                profile.name
+            );
+        }
+
+        self.route(None);
+        self.ollama = Ollama::new_with_client(
+            profile.ollama_host.clone(),
+            profile.ollama_port,
+            http_client(),
+        );
+        self.backend = (profile.ollama_host.clone(), profile.ollama_port);
+        self.routed.clear();
+        self.model = profile.model.clone();
+        self.improve_prompt = profile
+            .improve_prompt
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PROMPT.to_string());
+        self.command_prompt = profile
+            .command_prompt
+            .clone()
+            .unwrap_or_else(|| COMMAND_PROMPT.to_string());
+        self.reply_tone = profile.reply_tone.clone();
+        self.output_filter = profile.output_filter;
+        self.filter_words = profile.filter_words.clone();
+        self.history.clear();
+        self.session.clear();
+        self.refined = None;
+        self.profile = profile.name.clone();
+        tracing::info!(
+            "Profile {}: {} at {}:{}",
+            profile.name,
+            profile.model,
+            profile.ollama_host,
+            profile.ollama_port
+        );
+        Ok(())
+    }
+
+    /// Send requests to `route`'s backend and model until the next call;
+    /// `None` goes back to the profile's.
+    pub fn route(&mut self, route: Option<&profiles::Route>) {
+        if let Some((ollama, model)) = self.unrouted.take() {
+            self.ollama = ollama;
+            self.model = model;
+        }
+        let Some(route) = route else {
+            return;
+        };
+
+        let ollama = if route.ollama_host.is_some() || route.ollama_port.is_some() {
+            let (host, port) = &self.backend;
+            let backend = (
+                route.ollama_host.clone().unwrap_or_else(|| host.clone()),
+                route.ollama_port.unwrap_or(*port),
+            );
+            self.routed
+                .entry(backend)
+                .or_insert_with_key(|(host, port)| {
+                    Ollama::new_with_client(host.clone(), *port, http_client())
+                })
+                .clone()
+        } else {
+            self.ollama.clone()
+        };
+        let model = route.model.clone().unwrap_or_else(|| self.model.clone());
+        self.unrouted = Some((
+            std::mem::replace(&mut self.ollama, ollama),
+            std::mem::replace(&mut self.model, model),
+        ));
+    }
+
+    /// Switch the model used for new requests. A running keep-warm ping
+    /// keeps pinging the model it started with.
+    pub fn set_model(&mut self, model: &str) {
+        self.model = model.to_string();
+    }
+
+    pub fn shorten_words(&self) -> usize {
+        self.shorten_words
+    }
+
+    /// Replace the built-in system prompts (e.g. with ones from a prompt pack).
+    pub fn with_prompts(mut self, improve: Option<String>, command: Option<String>) -> Self {
+        if let Some(prompt) = improve {
+            self.improve_prompt = prompt;
         }
+        if let Some(prompt) = command {
+            self.command_prompt = prompt;
+        }
+        self
+    }
+
+    /// Give each new improve request the last `size` improvements as
+    /// examples, to keep tone and terminology consistent within a session.
+    pub fn with_session_memory(mut self, size: usize) -> Self {
+        self.session_size = size;
+        self
+    }
+
+    /// Forget the improvements remembered for the session.
+    pub fn reset_session(&mut self) {
+        self.session.clear();
+        self.refined = None;
+    }
+
+    /// Number of improvements remembered for the session.
+    pub fn session_len(&self) -> usize {
+        self.session.len()
+    }
+
+    /// Replace the built-in expand prompt.
+    pub fn with_expand_prompt(mut self, prompt: Option<String>) -> Self {
+        if let Some(prompt) = prompt {
+            self.expand_prompt = prompt;
+        }
+        self
+    }
+
+    /// Extra instructions appended to the system prompt of new requests
+    /// until cleared (set by script hooks for a single hotkey press).
+    pub fn set_instructions(&mut self, instructions: Option<String>) {
+        self.instructions = instructions;
+    }
+
+    /// The instructions `set_instructions` added, if any.
+    pub fn instructions(&self) -> Option<&str> {
+        self.instructions.as_deref()
+    }
+
+    /// Take the reasoning from the most recent response, if the action had
+    /// thinking enabled and the model produced any.
+    pub fn take_reasoning(&self) -> Option<String> {
+        self.last_reasoning.lock().ok()?.take()
+    }
+
+    /// How long Ollama keeps the model loaded after a request, unless an
+    /// action overrides it.
+    pub fn with_keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// How long a request may take before it fails, unless an action sets
+    /// its own `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Base Ollama model options (num_ctx, num_gpu, ...) sent with every
+    /// request; per-action options are applied on top.
+    pub fn with_model_options(mut self, options: ModelOptions) -> Self {
+        self.model_options = options;
+        self
+    }
+
+    /// Periodically ask Ollama to load the model (an empty chat request), so
+    /// hosts that evict idle models don't make the next press pay the reload.
+    pub fn spawn_keep_warm(&self, interval: Duration) {
+        let ollama = self.ollama.clone();
+        let request = ChatMessageRequest::new(self.model.clone(), Vec::new())
+            .keep_alive(self.keep_alive.clone());
+        let timeout = self.timeout;
+
+        tokio::spawn(async move {
+            loop {
+                match send_within(&ollama, request.clone(), timeout).await {
+                    Ok(_) => tracing::debug!("Keep-warm ping succeeded"),
+                    Err(e) => tracing::warn!("Keep-warm ping failed: {}", e),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Keep several models loaded at once (e.g. a fast one and a quality
+    /// one), pinging each every `interval` so switching between them never
+    /// waits for a load. A model found evicted is loaded again by its ping,
+    /// which is logged. Ollama must be allowed to hold them all
+    /// (`OLLAMA_MAX_LOADED_MODELS`).
+    pub fn spawn_standby(&self, models: Vec<String>, interval: Duration) {
+        let ollama = self.ollama.clone();
+        let keep_alive = self.keep_alive.clone();
+        let timeout = self.timeout;
+
+        tokio::spawn(async move {
+            loop {
+                for model in &models {
+                    let request = ChatMessageRequest::new(model.clone(), Vec::new())
+                        .keep_alive(keep_alive.clone());
+                    let start = Instant::now();
+                    match send_within(&ollama, request, timeout).await {
+                        // A resident model answers an empty request at once
+                        Ok(_) if start.elapsed() > Duration::from_secs(1) => {
+                            tracing::info!(
+                                "Loaded standby model {} in {:?}",
+                                model,
+                                start.elapsed()
+                            )
+                        }
+                        Ok(_) => tracing::debug!("Standby model {} is loaded", model),
+                        Err(e) => tracing::warn!("Standby ping for {} failed: {}", model, e),
+                    }
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Check every `interval` where `models` run on the Ollama host
+    /// (`/api/ps`), warning when one was evicted or runs on the CPU, and
+    /// loading evicted ones again when `reload` is set.
+    pub fn spawn_residency_monitor(&self, models: Vec<String>, interval: Duration, reload: bool) {
+        let (host, port) = &self.backend;
+        let monitor = residency::Monitor {
+            client: http_client(),
+            base: format!("{host}:{port}"),
+            ollama: self.ollama.clone(),
+            keep_alive: self.keep_alive.clone(),
+            timeout: self.timeout,
+            reload,
+        };
+        tokio::spawn(monitor.run(models, interval));
+    }
+
+    /// Stream responses, publishing the accumulated text to `progress` as it
+    /// arrives (an empty string marks the start of each request).
+    pub fn with_progress(mut self, progress: Option<watch::Sender<String>>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Send improve and command requests to another instance's control
+    /// socket (e.g. a headless one on a GPU machine, forwarded over SSH)
+    /// instead of Ollama.
+    pub fn with_relay(mut self, socket: Option<PathBuf>) -> Self {
+        self.relay = socket;
+        self
+    }
+
+    /// Request commands as JSON (`{"command", "explanation"}`) using Ollama's
+    /// JSON format, so no markdown or chatter needs stripping.
+    pub fn with_command_json(mut self, enabled: bool) -> Self {
+        self.command_json = enabled;
+        self
+    }
+
+    /// Whether code spans in text to improve are hidden from the model.
+    pub fn with_protect_code(mut self, enabled: bool) -> Self {
+        self.protect_code = enabled;
+        self
+    }
+
+    /// Whether quoted passages in text to improve are kept verbatim.
+    pub fn with_preserve_quotes(mut self, enabled: bool) -> Self {
+        self.preserve_quotes = enabled;
+        self
+    }
+
+    /// Names, product terms, and phrases the improve action must keep
+    /// verbatim. They are listed in the prompt and checked afterwards.
+    pub fn with_protected_terms(mut self, terms: Vec<String>) -> Self {
+        self.protected_terms = terms;
+        self
+    }
+
+    /// Fix unambiguous typos locally before improve requests, so the model
+    /// gets cleaner text.
+    pub fn with_spellcheck(mut self, spellcheck: Option<Spellcheck>) -> Self {
+        self.spellcheck = spellcheck;
+        self
+    }
+
+    /// Mask or block profanity (the built-in list plus `words`) and injected
+    /// personal data in responses, unless an action sets its own `filter`.
+    pub fn with_output_filter(mut self, filter: OutputFilter, words: Vec<String>) -> Self {
+        self.output_filter = filter;
+        self.filter_words = words;
+        self
+    }
+
+    /// Word and character limits for the shorten action.
+    pub fn with_shorten_limits(mut self, words: usize, chars: usize) -> Self {
+        self.shorten_words = words;
+        self.shorten_chars = chars;
+        self
+    }
+
+    /// Tone, a description of the sender, and the sign-off for email replies.
+    pub fn with_reply_style(
+        mut self,
+        tone: String,
+        persona: Option<String>,
+        signature: Option<String>,
+    ) -> Self {
+        self.reply_tone = tone;
+        self.reply_persona = persona;
+        self.reply_signature = signature;
+        self
+    }
+
+    /// Target reading level of the simplify profile, e.g. `CEFR B1` or
+    /// `grade 6`.
+    pub fn with_simplify_level(mut self, level: String) -> Self {
+        self.simplify_level = level;
+        self
+    }
+
+    /// Override generation options per action.
+    pub fn with_action_options(mut self, options: HashMap<Action, GenerationOptions>) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Use a different (vision-capable) model for image requests.
+    pub fn with_vision_model(mut self, model: Option<String>) -> Self {
+        self.vision_model = model;
+        self
     }
 
     pub async fn improve(&mut self, text: &str, refine: bool) -> Result<String> {
-        self.send_chat(DEFAULT_PROMPT, text, refine).await
+        if let Some(relay) = &self.relay {
+            let result = relay_call(relay, "improve", text, refine).await?;
+            return result["text"]
+                .as_str()
+                .map(str::to_string)
+                .context("Relay returned no text");
+        }
+
+        let improved = self.improve_checked(text, refine).await?;
+        if self.session_size > 0 {
+            self.remember(text, &improved, refine);
+        }
+        Ok(improved)
+    }
+
+    /// Remember an improvement for the session. A refinement replaces the
+    /// entry of the result it refines, keeping that entry's original text
+    /// rather than the refining instruction.
+    fn remember(&mut self, text: &str, improved: &str, refine: bool) {
+        let original = match (refine, self.refined.take()) {
+            (true, Some(entry)) => {
+                if let Some(index) = self.session.iter().position(|e| *e == entry) {
+                    self.session.remove(index);
+                }
+                entry.0
+            }
+            // Refining a conversation that didn't start as an improvement
+            (true, None) => return,
+            (false, _) => text.to_string(),
+        };
+
+        let entry = (original, improved.to_string());
+        if entry.0 != entry.1 {
+            self.session.push_back(entry.clone());
+            if self.session.len() > self.session_size {
+                self.session.pop_front();
+            }
+        }
+        self.refined = Some(entry);
+    }
+
+    /// Improve `text`, making sure placeholders and protected terms survive.
+    async fn improve_checked(&mut self, text: &str, refine: bool) -> Result<String> {
+        let (mut protected, prompt) = self.protect(text);
+        // A refinement is an instruction about the last result, not new text
+        if !refine && let Some(spellcheck) = &self.spellcheck {
+            match spellcheck
+                .correct(&protected.text, &self.protected_terms)
+                .await
+            {
+                Ok(corrected) => protected.text = corrected,
+                Err(e) => tracing::warn!("Spellcheck failed: {:#}", e),
+            }
+        }
+        let mut improved = self
+            .send_chat(Action::Improve, &prompt, &protected.text, refine)
+            .await?;
+
+        // Give the model one chance to put back placeholders it dropped
+        let missing = protected.missing(&improved);
+        if !missing.is_empty() {
+            tracing::info!(
+                "Response is missing {} placeholder(s), retrying",
+                missing.len()
+            );
+            let reminder = format!(
+                "Your answer is missing these placeholders: {}. \
+                 Output the full text again with every placeholder in place.",
+                missing.join(", ")
+            );
+            improved = self
+                .send_chat(Action::Improve, &prompt, &reminder, true)
+                .await?;
+        }
+        let improved = match protected.restore(&improved) {
+            Ok(improved) => improved,
+            Err(e) => {
+                tracing::warn!("{:#}, keeping the original", e);
+                return Ok(text.to_string());
+            }
+        };
+
+        // Likewise for protected terms, falling back to the original text
+        let missing = self.missing_terms(text, &improved);
+        if missing.is_empty() {
+            return Ok(improved);
+        }
+        tracing::info!("Response changed protected terms {:?}, retrying", missing);
+        let reminder = format!(
+            "Your answer changed or dropped these terms: {}. Output the full text again \
+             with each of them written exactly as in the original.",
+            missing.join(", ")
+        );
+        let retried = self
+            .send_chat(Action::Improve, &prompt, &reminder, true)
+            .await?;
+        let retried = match protected.restore(&retried) {
+            Ok(retried) => retried,
+            Err(e) => {
+                tracing::warn!("{:#}, keeping the original", e);
+                return Ok(text.to_string());
+            }
+        };
+
+        let missing = self.missing_terms(text, &retried);
+        if !missing.is_empty() {
+            tracing::warn!(
+                "Response still changes protected terms {:?}, keeping the original",
+                missing
+            );
+            return Ok(text.to_string());
+        }
+        Ok(retried)
+    }
+
+    /// Protected terms in `input` that `output` no longer contains verbatim.
+    pub fn missing_terms(&self, input: &str, output: &str) -> Vec<&str> {
+        protect::missing_terms(&self.protected_terms, input, output)
+    }
+
+    /// Request `count` alternative rewrites concurrently, in one-shot
+    /// requests that leave the refinement history untouched. Candidates
+    /// whose request fails are left out.
+    pub async fn improve_candidates(&self, text: &str, count: usize) -> Result<Vec<String>> {
+        let (protected, prompt) = self.protect(text);
+        let messages = vec![
+            self.system_message(&prompt),
+            ChatMessage::user(protected.text.clone()),
+        ];
+
+        let timeout = self.timeout_for(Action::Improve);
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..count {
+            let ollama = self.ollama.clone();
+            let request = self.build_request(Action::Improve, messages.clone());
+            tasks.spawn(async move { send_within(&ollama, request, timeout).await });
+        }
+
+        let mut candidates = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok(Ok(response)) => {
+                    let candidate = protected
+                        .restore(response.message.content.trim())
+                        .and_then(|candidate| self.filter(Action::Improve, &messages, candidate));
+                    let candidate = match candidate {
+                        Ok(candidate) => candidate,
+                        Err(e) => {
+                            tracing::warn!("Candidate dropped: {}", e);
+                            continue;
+                        }
+                    };
+                    if !candidate.is_empty() && !candidates.contains(&candidate) {
+                        candidates.push(candidate);
+                    }
+                }
+                Ok(Err(e)) => tracing::warn!("Candidate request failed: {}", e),
+                Err(e) => tracing::warn!("Candidate task failed: {}", e),
+            }
+        }
+
+        if candidates.is_empty() {
+            anyhow::bail!("No candidate requests succeeded");
+        }
+        Ok(candidates)
+    }
+
+    /// Improve `text` with each of `models` concurrently, to compare them.
+    /// Results are in the order of `models`; like candidates, the requests
+    /// leave the refinement history untouched.
+    pub async fn improve_with_models(
+        &self,
+        text: &str,
+        models: &[String],
+    ) -> Vec<(String, Result<String>)> {
+        let (protected, prompt) = self.protect(text);
+        let messages = vec![
+            self.system_message(&prompt),
+            ChatMessage::user(protected.text.clone()),
+        ];
+
+        let timeout = self.timeout_for(Action::Improve);
+        let mut tasks = tokio::task::JoinSet::new();
+        for (i, model) in models.iter().enumerate() {
+            let ollama = self.ollama.clone();
+            let request = self.build_request_for(model.clone(), Action::Improve, messages.clone());
+            tasks.spawn(async move { (i, send_within(&ollama, request, timeout).await) });
+        }
+
+        let mut results: Vec<Option<Result<String>>> = models.iter().map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((i, Ok(response))) => {
+                    let improved = protected.restore(response.message.content.trim());
+                    results[i] =
+                        Some(improved.and_then(|improved| {
+                            self.filter(Action::Improve, &messages, improved)
+                        }));
+                }
+                Ok((i, Err(e))) => results[i] = Some(Err(e)),
+                Err(e) => tracing::warn!("Comparison task failed: {}", e),
+            }
+        }
+
+        models
+            .iter()
+            .cloned()
+            .zip(results)
+            .map(|(model, result)| {
+                (
+                    model,
+                    result.unwrap_or_else(|| Err(anyhow::anyhow!("Request task failed"))),
+                )
+            })
+            .collect()
+    }
+
+    pub async fn generate_command(
+        &mut self,
+        description: &str,
+        refine: bool,
+    ) -> Result<GeneratedCommand> {
+        if let Some(relay) = &self.relay {
+            let result = relay_call(relay, "generate_command", description, refine).await?;
+            return serde_json::from_value::<RelayedCommand>(result)
+                .map(|relayed| GeneratedCommand {
+                    command: relayed.text,
+                    explanation: relayed.explanation,
+                })
+                .context("Relay returned no command");
+        }
+
+        if !self.command_json {
+            let prompt = self.command_prompt.clone();
+            let command = self
+                .send_chat(Action::Command, &prompt, description, refine)
+                .await?;
+            return Ok(GeneratedCommand {
+                command,
+                explanation: None,
+            });
+        }
+
+        let prompt = format!("{}\n{}", self.command_prompt, COMMAND_JSON_SUFFIX);
+        let reply = self
+            .send_chat(Action::Command, &prompt, description, refine)
+            .await?;
+        match serde_json::from_str::<GeneratedCommand>(&reply) {
+            Ok(generated) => Ok(GeneratedCommand {
+                command: generated.command.trim().to_string(),
+                explanation: generated
+                    .explanation
+                    .map(|e| e.trim().to_string())
+                    .filter(|e| !e.is_empty()),
+            }),
+            // Never type a reply that may be prose, or JSON with a command in it
+            Err(e) => {
+                tracing::debug!("Unparsed command response: {:?}", reply);
+                Err(anyhow::Error::new(e).context(InvalidCommandReply))
+            }
+        }
+    }
+
+    /// Shorten text to the configured word and character limits, asking the
+    /// model again when it overshoots and truncating as a last resort.
+    pub async fn shorten(&mut self, text: &str, refine: bool) -> Result<String> {
+        self.shorten_to(text, refine, self.shorten_words, self.shorten_chars)
+            .await
+    }
+
+    /// Shorten text to explicit word and character limits.
+    pub async fn shorten_to(
+        &mut self,
+        text: &str,
+        refine: bool,
+        max_words: usize,
+        max_chars: usize,
+    ) -> Result<String> {
+        // Shortening never returns more than it was given
+        let max_chars = self
+            .options
+            .get(&Action::Shorten)
+            .and_then(|opts| opts.max_chars)
+            .map_or(max_chars, |limit| max_chars.min(limit.resolve(text)))
+            .min(text.chars().count());
+        let prompt = SHORTEN_PROMPT
+            .replace("{words}", &max_words.to_string())
+            .replace("{chars}", &max_chars.to_string());
+
+        let mut result = self
+            .send_chat(Action::Shorten, &prompt, text, refine)
+            .await?;
+
+        for _ in 0..SHORTEN_RETRIES {
+            let words = result.split_whitespace().count();
+            let chars = result.chars().count();
+            if words <= max_words && chars <= max_chars {
+                return Ok(result);
+            }
+
+            tracing::info!(
+                "Shortened text is {} words and {} characters, over the {}/{} limit - asking again",
+                words,
+                chars,
+                max_words,
+                max_chars
+            );
+            let reminder = format!(
+                "That is {words} words and {chars} characters. Rewrite it in at most \
+                 {max_words} words and {max_chars} characters. Only output the rewritten text."
+            );
+            result = self
+                .send_chat(Action::Shorten, &prompt, &reminder, true)
+                .await?;
+        }
+
+        let truncated =
+            postprocess::truncate(&postprocess::truncate_words(&result, max_words), max_chars);
+        if truncated != result {
+            tracing::warn!(
+                "Truncating shortened text to {} words and {} characters",
+                max_words,
+                max_chars
+            );
+        }
+        Ok(truncated)
+    }
+
+    /// Ask the model to rewrite `text` in at most `max_chars` characters if it
+    /// is longer, truncating what still overshoots.
+    pub async fn compress(&self, text: &str, max_chars: usize) -> Result<String> {
+        let len = text.chars().count();
+        if len <= max_chars {
+            return Ok(text.to_string());
+        }
+        tracing::info!(
+            "Text is {} characters, over the {} limit - asking for a shorter one",
+            len,
+            max_chars
+        );
+        let prompt = COMPRESS_PROMPT.replace("{chars}", &max_chars.to_string());
+        let result = self.complete(Action::Shorten, &prompt, text).await?;
+        if result.chars().count() <= max_chars {
+            return Ok(result);
+        }
+        tracing::warn!("Truncating compressed text to {} characters", max_chars);
+        Ok(postprocess::truncate(&result, max_chars))
+    }
+
+    /// Rewrite a code comment. Comment markers are stripped before the
+    /// request and put back afterwards, with paragraphs reflowed to
+    /// `comment::WIDTH` columns; code spans are always kept verbatim.
+    pub async fn rewrite_comment(&mut self, text: &str, refine: bool) -> Result<String> {
+        let (comment, body) = match Comment::strip(text) {
+            Some((comment, body)) => (Some(comment), body),
+            None => (None, text.to_string()),
+        };
+
+        let mut protected = Protected::new(&body);
+        protected.protect_code();
+        let prompt = if protected.has_placeholders() {
+            format!("{}\n{}", COMMENT_PROMPT, protect::PLACEHOLDER_RULE)
+        } else {
+            COMMENT_PROMPT.to_string()
+        };
+
+        let rewritten = self
+            .send_chat(Action::Comment, &prompt, &protected.text, refine)
+            .await?;
+        let rewritten = protected.restore(&rewritten)?;
+        Ok(match comment {
+            Some(comment) => comment.apply(&rewritten),
+            None => rewritten,
+        })
+    }
+
+    /// Draft a reply to a received email, ending with the configured
+    /// signature.
+    pub async fn draft_reply(&mut self, email: &str, refine: bool) -> Result<String> {
+        let mut prompt = REPLY_PROMPT.replace("{tone}", &self.reply_tone);
+        if let Some(persona) = &self.reply_persona {
+            prompt.push_str(&format!("\nAbout me: {persona}"));
+        }
+
+        let reply = self
+            .send_chat(Action::Reply, &prompt, email, refine)
+            .await?;
+        Ok(match &self.reply_signature {
+            Some(signature) if !reply.is_empty() => format!("{reply}\n\n{signature}"),
+            _ => reply,
+        })
     }
 
-    pub async fn generate_command(&mut self, description: &str, refine: bool) -> Result<String> {
-        self.send_chat(COMMAND_PROMPT, description, refine).await
+    /// Rewrite text with a profile's preset prompt.
+    pub async fn rewrite(&mut self, profile: Profile, text: &str, refine: bool) -> Result<String> {
+        let prompt = match profile {
+            Profile::Simplify => SIMPLIFY_PROMPT.replace("{level}", &self.simplify_level),
+            Profile::Bulletize => BULLETIZE_PROMPT.to_string(),
+            Profile::Prose => PROSE_PROMPT.to_string(),
+        };
+        self.send_chat(profile.action(), &prompt, text, refine)
+            .await
+    }
+
+    /// Rewrite text as a spoken instruction says.
+    pub async fn instruct(
+        &mut self,
+        instruction: &str,
+        text: &str,
+        refine: bool,
+    ) -> Result<String> {
+        let prompt = VOICE_PROMPT.replace("{instruction}", instruction);
+        self.send_chat(Action::Voice, &prompt, text, refine).await
+    }
+
+    /// Expand terse notes into full prose.
+    pub async fn expand(&mut self, text: &str, refine: bool) -> Result<String> {
+        let prompt = self.expand_prompt.clone();
+        self.send_chat(Action::Expand, &prompt, text, refine).await
+    }
+
+    /// Second pass: have the model review a rewrite against the original and
+    /// return a corrected final version. One-shot, so REDO still refines the
+    /// first pass.
+    pub async fn critique(&self, original: &str, rewrite: &str) -> Result<String> {
+        let text = format!("Original:\n{original}\n\nRewrite:\n{rewrite}");
+        self.complete(Action::Critic, CRITIC_PROMPT, &text).await
+    }
+
+    /// Ask the model which of several rewrites of `original` is best,
+    /// returning its index. One-shot.
+    pub async fn judge(&self, original: &str, candidates: &[String]) -> Result<usize> {
+        let mut text = format!("Original:\n{original}");
+        for (i, candidate) in candidates.iter().enumerate() {
+            text.push_str(&format!("\n\nRewrite {}:\n{candidate}", i + 1));
+        }
+        let reply = self.complete(Action::Judge, JUDGE_PROMPT, &text).await?;
+        select::parse_choice(&reply, candidates.len())
+            .with_context(|| format!("Judge didn't name a rewrite: {reply:?}"))
+    }
+
+    /// Suggest up to `count` distinct titles for a text. One-shot.
+    pub async fn suggest_titles(&self, text: &str, count: usize) -> Result<Vec<String>> {
+        let prompt = TITLE_PROMPT.replace("{count}", &count.to_string());
+        let reply = self.complete(Action::Title, &prompt, text).await?;
+
+        let mut titles: Vec<String> = Vec::new();
+        for line in reply.lines() {
+            let title = strip_list_marker(line.trim())
+                .trim_matches(['"', '“', '”', '\''])
+                .trim_end_matches('.')
+                .trim();
+            if !title.is_empty() && !titles.iter().any(|t| t == title) {
+                titles.push(title.to_string());
+            }
+        }
+        titles.truncate(count);
+        Ok(titles)
+    }
+
+    /// Rewrite a git commit message in Conventional Commits style. One-shot,
+    /// for the commit-msg hook.
+    pub async fn commit_message(&self, message: &str) -> Result<String> {
+        self.complete(Action::Commit, COMMIT_PROMPT, message).await
+    }
+
+    /// Summarize text in a one-shot request that leaves the refinement
+    /// history untouched.
+    pub async fn summarize(&self, text: &str) -> Result<String> {
+        self.complete(Action::Summarize, SUMMARY_PROMPT, text).await
+    }
+
+    /// Explain terminal output (e.g. an error) in a one-shot request, for
+    /// the tmux integration.
+    pub async fn explain(&self, text: &str) -> Result<String> {
+        self.complete(Action::Explain, EXPLAIN_PROMPT, text).await
+    }
+
+    /// Explain a shell command part by part in a one-shot request, for the
+    /// explain-command hotkey.
+    pub async fn explain_command(&self, command: &str) -> Result<String> {
+        self.complete(Action::ExplainCommand, EXPLAIN_COMMAND_PROMPT, command)
+            .await
+    }
+
+    /// Translate `text` into `language` (a name such as "German") in a
+    /// one-shot request, for the control socket's `translate` method.
+    pub async fn translate(&self, text: &str, language: &str) -> Result<String> {
+        let prompt = TRANSLATE_PROMPT.replace("{language}", language);
+        self.complete(Action::Translate, &prompt, text).await
+    }
+
+    /// Correct the last failed command in `terminal` (a capture of the end
+    /// of a terminal session, command and output) in a one-shot request.
+    pub async fn fix_command(&self, terminal: &str) -> Result<String> {
+        let reply = self
+            .complete(Action::FixCommand, FIX_COMMAND_PROMPT, terminal)
+            .await?;
+        // Small models still wrap it in a code block or repeat the prompt sign
+        let command = reply
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with("```"))
+            .unwrap_or_default();
+        let command = command.trim_matches('`');
+        let command = command.strip_prefix("$ ").unwrap_or(command);
+        Ok(command.trim().to_string())
+    }
+
+    /// Explain a generated shell command before it is output, flagging
+    /// destructive ones with a leading "WARNING:". A second, one-shot pass.
+    pub async fn review_command(&self, command: &str) -> Result<String> {
+        self.complete(Action::ReviewCommand, REVIEW_COMMAND_PROMPT, command)
+            .await
+    }
+
+    /// Send a single system + user exchange, without conversation history.
+    async fn complete(
+        &self,
+        action: Action,
+        system_prompt: &str,
+        user_text: &str,
+    ) -> Result<String> {
+        let messages = vec![
+            self.system_message(system_prompt),
+            ChatMessage::user(user_text.to_string()),
+        ];
+        let request = self.build_request(action, messages.clone());
+
+        let result = self.send_with_retry(action, request, user_text).await?;
+        let result = self
+            .enforce_max_chars(action, messages.clone(), user_text, result)
+            .await?;
+        self.filter(action, &messages, result)
+    }
+
+    /// Ask the vision model about an image (base64-encoded PNG). Image
+    /// requests are one-shot and leave the refinement history untouched.
+    pub async fn describe_image(&self, prompt: &str, image_base64: String) -> Result<String> {
+        let message = ChatMessage::user(prompt.to_string())
+            .with_images(vec![Image::from_base64(image_base64)]);
+        let request = self.build_request(Action::Image, vec![message.clone()]);
+
+        let result = self.send_with_retry(Action::Image, request, prompt).await?;
+        self.filter(Action::Image, &[message], result)
+    }
+
+    /// Hide spans the model must not touch, returning them with the improve
+    /// prompt extended to explain the placeholders if there are any.
+    fn protect(&self, text: &str) -> (Protected, String) {
+        let mut protected = Protected::new(text);
+        if self.protect_code {
+            protected.protect_code();
+        }
+        if self.preserve_quotes {
+            protected.protect_quotes();
+        }
+
+        let mut prompt = if protected.has_placeholders() {
+            format!("{}\n{}", self.improve_prompt, protect::PLACEHOLDER_RULE)
+        } else {
+            self.improve_prompt.clone()
+        };
+
+        let terms: Vec<&str> = self
+            .protected_terms
+            .iter()
+            .map(String::as_str)
+            .filter(|term| text.contains(term))
+            .collect();
+        if !terms.is_empty() {
+            prompt.push_str(&format!(
+                "\nKeep these terms exactly as written: {}.",
+                terms.join(", ")
+            ));
+        }
+        (protected, prompt)
+    }
+
+    fn system_message(&self, prompt: &str) -> ChatMessage {
+        match &self.instructions {
+            Some(instructions) => ChatMessage::system(format!("{prompt}\n{instructions}")),
+            None => ChatMessage::system(prompt.to_string()),
+        }
+    }
+
+    /// Build a chat request using the action's generation options.
+    fn build_request(&self, action: Action, messages: Vec<ChatMessage>) -> ChatMessageRequest {
+        let model = self
+            .options
+            .get(&action)
+            .and_then(|opts| opts.model.clone())
+            .unwrap_or_else(|| match action {
+                Action::Image => self
+                    .vision_model
+                    .clone()
+                    .unwrap_or_else(|| self.model.clone()),
+                _ => self.model.clone(),
+            });
+        self.build_request_for(model, action, messages)
+    }
+
+    /// Build a chat request for a specific model, using the action's other
+    /// generation options.
+    fn build_request_for(
+        &self,
+        model: String,
+        action: Action,
+        messages: Vec<ChatMessage>,
+    ) -> ChatMessageRequest {
+        let opts = self.options.get(&action).cloned().unwrap_or_default();
+
+        let mut model_options = self.model_options.clone();
+        if let Some(temperature) = opts.temperature {
+            model_options = model_options.temperature(temperature);
+        }
+        if let Some(max_tokens) = opts.max_tokens {
+            model_options = model_options.num_predict(max_tokens);
+        }
+        if !opts.stop.is_empty() {
+            model_options = model_options.stop(opts.stop);
+        }
+
+        let request = ChatMessageRequest::new(model, messages)
+            .options(model_options)
+            .think(opts.think.unwrap_or(false))
+            .keep_alive(opts.keep_alive.unwrap_or_else(|| self.keep_alive.clone()));
+
+        if action == Action::Command && self.command_json {
+            request.format(FormatType::Json)
+        } else {
+            request
+        }
     }
 
     async fn send_chat(
         &mut self,
+        action: Action,
         system_prompt: &str,
         user_text: &str,
         refine: bool,
@@ -57,39 +1701,149 @@ impl TextImprover {
             self.history.push(ChatMessage::user(user_text.to_string()));
         } else {
             self.history.clear();
-            self.history
-                .push(ChatMessage::system(system_prompt.to_string()));
+            self.refined = None;
+            self.history.push(self.system_message(system_prompt));
+            if action == Action::Improve {
+                for (original, improved) in &self.session {
+                    self.history.push(ChatMessage::user(original.clone()));
+                    self.history.push(ChatMessage::assistant(improved.clone()));
+                }
+            }
             self.history.push(ChatMessage::user(user_text.to_string()));
         }
 
-        let request = ChatMessageRequest::new(self.model.clone(), self.history.clone())
-            .think(false)
-            .keep_alive(KeepAlive::Indefinitely);
+        let request = self.build_request(action, self.history.clone());
+
+        let result = self.send_with_retry(action, request, user_text).await?;
+        let result = self
+            .enforce_max_chars(action, self.history.clone(), user_text, result)
+            .await?;
+        let result = self.filter(action, &self.history, result)?;
+        self.history.push(ChatMessage::assistant(result.clone()));
+        Ok(result)
+    }
+
+    /// Apply the action's output filter to a response, treating everything
+    /// in `messages` as text the model was given.
+    fn filter(&self, action: Action, messages: &[ChatMessage], result: String) -> Result<String> {
+        let output_filter = self
+            .options
+            .get(&action)
+            .and_then(|opts| opts.filter)
+            .unwrap_or(self.output_filter);
+        if output_filter == OutputFilter::Off {
+            return Ok(result);
+        }
+        let known = messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        filter::apply(output_filter, &self.filter_words, &known, &result)
+    }
+
+    /// Keep a response to `input` within the action's `max_chars`, asking
+    /// the model to shorten it (continuing the conversation in `messages`)
+    /// before falling back to truncation. Shortening applies its own limits.
+    async fn enforce_max_chars(
+        &self,
+        action: Action,
+        mut messages: Vec<ChatMessage>,
+        input: &str,
+        result: String,
+    ) -> Result<String> {
+        if action == Action::Shorten {
+            return Ok(result);
+        }
+        let Some(opts) = self.options.get(&action) else {
+            return Ok(result);
+        };
+        let Some(max) = opts.max_chars.map(|limit| limit.resolve(input)) else {
+            return Ok(result);
+        };
+
+        let len = result.chars().count();
+        if len <= max {
+            return Ok(result);
+        }
+
+        let mut result = result;
+        if opts.overflow == Overflow::Retry {
+            tracing::info!(
+                "Response is {} characters, over the {} limit - asking for a shorter one",
+                len,
+                max
+            );
+            messages.push(ChatMessage::assistant(result.clone()));
+            messages.push(ChatMessage::user(format!(
+                "That is {len} characters. Rewrite it in at most {max} characters. \
+                 Only output the rewritten text."
+            )));
+            let request = self.build_request(action, messages);
+            result = self.send_with_retry(action, request, "(shorten)").await?;
+            if result.chars().count() <= max {
+                return Ok(result);
+            }
+        }
 
-        // Retry logic for stale connections
+        tracing::warn!("Truncating response to {} characters", max);
+        Ok(postprocess::truncate(&result, max))
+    }
+
+    /// The action's own timeout, or the default one.
+    fn timeout_for(&self, action: Action) -> Duration {
+        self.options
+            .get(&action)
+            .and_then(|opts| opts.timeout)
+            .unwrap_or(self.timeout)
+    }
+
+    async fn send_with_retry(
+        &self,
+        action: Action,
+        request: ChatMessageRequest,
+        user_text: &str,
+    ) -> Result<String> {
+        let timeout = self.timeout_for(action);
         let mut last_error = None;
         for attempt in 1..=3 {
             let start = Instant::now();
-            log::debug!(
+            tracing::debug!(
                 "Ollama request attempt {} for text: {:?}",
                 attempt,
                 user_text
             );
 
-            match self.ollama.send_chat_messages(request.clone()).await {
-                Ok(response) => {
-                    let result = response.message.content.trim().to_string();
-                    log::debug!(
+            let Ok(result) = tokio::time::timeout(timeout, self.send_once(request.clone())).await
+            else {
+                tracing::warn!("Ollama attempt {} timed out after {:?}", attempt, timeout);
+                return Err(TimedOut { timeout }.into());
+            };
+            match result {
+                Ok((content, thinking)) => {
+                    let result = content.trim().to_string();
+                    let reasoning = thinking
+                        .map(|t| t.trim().to_string())
+                        .filter(|t| !t.is_empty());
+                    if let Ok(mut last) = self.last_reasoning.lock() {
+                        *last = reasoning;
+                    }
+                    tracing::debug!(
                         "Ollama response in {:?}: {:?} -> {:?}",
                         start.elapsed(),
                         user_text,
                         result
                     );
-                    self.history.push(ChatMessage::assistant(result.clone()));
                     return Ok(result);
                 }
+                // A dropped pooled connection is retried on a new one at
+                // once; anything else gets a moment to recover
+                Err(e) if is_stale_connection(&e) => {
+                    tracing::debug!("Ollama attempt {} hit a closed connection: {}", attempt, e);
+                    last_error = Some(e);
+                }
                 Err(e) => {
-                    log::warn!("Ollama attempt {} failed: {}", attempt, e);
+                    tracing::warn!("Ollama attempt {} failed: {}", attempt, e);
                     last_error = Some(e);
                     if attempt < 3 {
                         tokio::time::sleep(Duration::from_secs(1)).await;
@@ -100,4 +1854,56 @@ impl TextImprover {
 
         Err(last_error.unwrap()).context("All Ollama retry attempts failed")
     }
+
+    /// Send one request, returning the response content and any reasoning.
+    /// Streams the response when a progress channel is attached.
+    #[tracing::instrument(name = "backend", skip_all, fields(streaming = self.progress.is_some()))]
+    async fn send_once(&self, request: ChatMessageRequest) -> Result<(String, Option<String>)> {
+        let Some(progress) = &self.progress else {
+            let response = self.ollama.send_chat_messages(request).await?;
+            return Ok((response.message.content, response.message.thinking));
+        };
+
+        progress.send_replace(String::new());
+        let mut stream = self.ollama.send_chat_messages_stream(request).await?;
+        let mut content = String::new();
+        let mut thinking = String::new();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|_| anyhow::anyhow!("Ollama response stream failed"))?;
+            content.push_str(&chunk.message.content);
+            if let Some(t) = chunk.message.thinking {
+                thinking.push_str(&t);
+            }
+            progress.send_replace(content.clone());
+            if chunk.done {
+                break;
+            }
+        }
+
+        Ok((content, Some(thinking)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keep_alive() {
+        assert!(matches!(
+            parse_keep_alive("10m"),
+            Ok(KeepAlive::Until {
+                time: 10,
+                unit: TimeUnit::Minutes
+            })
+        ));
+        assert!(matches!(
+            parse_keep_alive("-1"),
+            Ok(KeepAlive::Indefinitely)
+        ));
+        assert!(parse_keep_alive("").is_err());
+        assert!(parse_keep_alive("5x").is_err());
+        assert!(parse_keep_alive("m").is_err());
+    }
 }
@@ -0,0 +1,106 @@
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+
+/// How often the bar is redrawn while a request is in flight.
+const TICK: Duration = Duration::from_millis(250);
+
+/// Elapsed time at which the bar is full.
+const FULL_SCALE: Duration = Duration::from_secs(30);
+
+/// An on-screen progress bar shown while a request is in flight.
+///
+/// The bar is drawn by `wob`, fed the elapsed time as a percentage of
+/// `FULL_SCALE`, so it appears as soon as a hotkey is handled and its length
+/// shows roughly how long the request has taken. wob hides it by itself once
+/// updates stop.
+pub struct Osd {
+    busy: watch::Sender<bool>,
+}
+
+/// Marks a request as in flight until dropped.
+pub struct Busy<'a> {
+    osd: &'a Osd,
+}
+
+impl Drop for Busy<'_> {
+    fn drop(&mut self) {
+        self.osd.busy.send_replace(false);
+    }
+}
+
+impl Osd {
+    /// Start the overlay task. wob is launched on first use.
+    pub fn spawn() -> Self {
+        let (busy, rx) = watch::channel(false);
+        tokio::spawn(run(rx));
+        Self { busy }
+    }
+
+    /// Show the bar until the returned guard is dropped.
+    pub fn busy(&self) -> Busy<'_> {
+        self.busy.send_replace(true);
+        Busy { osd: self }
+    }
+}
+
+async fn run(mut rx: watch::Receiver<bool>) {
+    let mut wob: Option<Child> = None;
+
+    loop {
+        // Wait for a request to start
+        while !*rx.borrow_and_update() {
+            if rx.changed().await.is_err() {
+                return;
+            }
+        }
+
+        let started = Instant::now();
+        while *rx.borrow_and_update() {
+            let elapsed = started.elapsed().as_millis();
+            let percent = (elapsed * 100 / FULL_SCALE.as_millis()).min(100);
+            if !draw(&mut wob, percent).await {
+                return;
+            }
+
+            tokio::select! {
+                changed = rx.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+                _ = tokio::time::sleep(TICK) => {}
+            }
+        }
+    }
+}
+
+/// Send one value to wob, starting it if needed. Returns false if wob can't
+/// be run at all.
+async fn draw(wob: &mut Option<Child>, percent: u128) -> bool {
+    if wob.is_none() {
+        match Command::new("wob")
+            .stdin(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => *wob = Some(child),
+            Err(e) => {
+                tracing::warn!("Failed to start wob, disabling the OSD: {}", e);
+                return false;
+            }
+        }
+    }
+
+    let Some(stdin) = wob.as_mut().and_then(|child| child.stdin.as_mut()) else {
+        return false;
+    };
+    if let Err(e) = stdin.write_all(format!("{percent}\n").as_bytes()).await {
+        // wob exited; start a new one next time
+        tracing::debug!("wob went away: {}", e);
+        *wob = None;
+    }
+    true
+}
@@ -1,24 +1,188 @@
 use anyhow::{Context, Result};
 use std::process::Stdio;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
+#[cfg(target_os = "linux")]
+use crate::i18n;
+use crate::markdown;
+use crate::notify;
+#[cfg(target_os = "linux")]
+use crate::portal;
+use crate::private::TempFile;
+
+/// Where results end up once the model has produced them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputMode {
+    /// Type the result at the cursor position
+    Type,
+    /// Delete the selection, then type the result in its place
+    Replace,
+    /// Copy the result to the clipboard as plain text
+    Clipboard,
+    /// Copy the result to the clipboard as both text/plain and text/html
+    RichClipboard,
+    /// Open the result in --editor, then type what was saved
+    Editor,
+    /// Have the screen reader announce the result, and copy it to the
+    /// clipboard
+    Announce,
+}
+
+/// Where the model's reasoning goes when an action has thinking enabled.
+/// Only the final answer is ever typed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReasoningOutput {
+    /// Discard the reasoning
+    Off,
+    /// Write the reasoning to the log
+    Log,
+    /// Copy the reasoning to the clipboard
+    Clipboard,
+}
+
+/// How text reaches the clipboard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ClipboardBackend {
+    /// The system clipboard (wl-copy on Linux, pbcopy on macOS)
+    System,
+    /// An OSC 52 escape sequence on the controlling terminal, which the
+    /// terminal puts on its (possibly local) clipboard, e.g. over SSH
+    Osc52,
+    /// Both; failing only if both fail
+    Both,
+}
+
+/// Which clipboard text is copied to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipboardTarget {
+    /// The regular clipboard, pasted with Ctrl+V
+    Clipboard,
+    /// The primary selection, pasted with a middle click (not on macOS)
+    Primary,
+}
+
+/// Where the original and the improved text go after an improvement, so
+/// both are one paste away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ClipboardSlots {
+    /// The original on the primary selection, the result on the clipboard
+    OriginalPrimary,
+    /// The result on the primary selection, the original on the clipboard
+    ImprovedPrimary,
+}
+
+static CLIPBOARD_BACKEND: OnceLock<ClipboardBackend> = OnceLock::new();
+
+/// Terminals commonly drop OSC 52 sequences longer than this.
+const OSC52_MAX_BYTES: usize = 100_000;
+
+/// Choose the clipboard backend, once at startup. Defaults to the system
+/// clipboard.
+pub fn set_clipboard_backend(backend: ClipboardBackend) {
+    let _ = CLIPBOARD_BACKEND.set(backend);
+}
+
+fn clipboard_backend() -> ClipboardBackend {
+    CLIPBOARD_BACKEND
+        .get()
+        .copied()
+        .unwrap_or(ClipboardBackend::System)
+}
+
+impl OutputMode {
+    /// Whether results are typed as keystrokes.
+    pub fn types(self) -> bool {
+        matches!(self, OutputMode::Type | OutputMode::Replace)
+    }
+
+    /// Whether results end up typed into the window the hotkey was pressed
+    /// in, now or after editing.
+    pub fn types_eventually(self) -> bool {
+        self.types() || self == OutputMode::Editor
+    }
+}
+
+/// Deliver a result using the configured output mode. Editor results are
+/// expected to have been edited already, and are typed.
+#[tracing::instrument(name = "output", skip(text), fields(chars = text.len()))]
+pub async fn deliver(mode: OutputMode, text: &str) -> Result<()> {
+    match mode {
+        OutputMode::Type | OutputMode::Editor => type_text(text).await,
+        OutputMode::Replace => {
+            delete_selection().await?;
+            type_text(text).await
+        }
+        OutputMode::Clipboard => copy_to_clipboard(text).await,
+        OutputMode::Announce => {
+            copy_to_clipboard(text).await?;
+            notify::announce(&markdown::to_plain(text)).await
+        }
+        OutputMode::RichClipboard => {
+            let plain = markdown::to_plain(text);
+            // Terminals only take plain text over OSC 52
+            match clipboard_backend() {
+                ClipboardBackend::System => {
+                    copy_rich_to_clipboard(&plain, &markdown::to_html(text)).await
+                }
+                ClipboardBackend::Osc52 => copy_with_osc52(&plain),
+                ClipboardBackend::Both => {
+                    if let Err(e) = copy_with_osc52(&plain) {
+                        tracing::debug!("OSC 52 failed: {:#}", e);
+                    }
+                    copy_rich_to_clipboard(&plain, &markdown::to_html(text)).await
+                }
+            }
+        }
+    }
+}
+
+/// Let the user edit `text` in `editor` (a shell command, given the file as
+/// its last argument), returning what was saved once the editor exits.
+/// Terminal editors need a terminal, e.g. `foot nvim`.
+pub async fn edit(editor: &str, text: &str) -> Result<String> {
+    let file = TempFile::create(".md", text.as_bytes())?;
+    let path = file.path();
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("improve-writing")
+        .arg(path)
+        .status()
+        .await
+        .with_context(|| format!("Failed to run editor {editor:?}"));
+    let edited = std::fs::read_to_string(path);
+
+    let status = status?;
+    if !status.success() {
+        anyhow::bail!("Editor {editor:?} exited with {status}");
+    }
+    edited.with_context(|| format!("Failed to read {}", path.display()))
+}
+
 /// Type text at the cursor position.
 ///
-/// - Linux: uses `wtype` (Wayland)
+/// - Linux: uses `wtype` (Wayland), or the RemoteDesktop portal with
+///   --portal
 /// - macOS: uses `osascript` with AppleScript `keystroke`
 #[cfg(target_os = "linux")]
 pub async fn type_text(text: &str) -> Result<()> {
     if text.is_empty() {
         return Ok(());
     }
+    if portal::enabled() {
+        return portal::type_text(text).await;
+    }
 
     Command::new("wtype")
         .arg(text)
         .status()
         .await
-        .context("Failed to type text (is wtype installed?)")?;
+        .with_context(|| format!("Failed to type text ({})", i18n::missing_tool("wtype")))?;
 
     Ok(())
 }
@@ -47,8 +211,9 @@ pub async fn type_text(text: &str) -> Result<()> {
 }
 
 /// Pipe text into a command's stdin.
-async fn run_stdin_command(cmd: &str, input: &str, err_ctx: &str) -> Result<()> {
+async fn run_stdin_command(cmd: &str, args: &[&str], input: &str, err_ctx: &str) -> Result<()> {
     let mut child = Command::new(cmd)
+        .args(args)
         .stdin(Stdio::piped())
         .spawn()
         .context(err_ctx.to_string())?;
@@ -61,36 +226,200 @@ async fn run_stdin_command(cmd: &str, input: &str, err_ctx: &str) -> Result<()>
     Ok(())
 }
 
-/// Copy text to the system clipboard.
+/// Copy text to the clipboard, through the configured backend.
+pub async fn copy_to_clipboard(text: &str) -> Result<()> {
+    copy_to(ClipboardTarget::Clipboard, text).await
+}
+
+/// Copy text to the clipboard or the primary selection, through the
+/// configured backend.
+pub async fn copy_to(target: ClipboardTarget, text: &str) -> Result<()> {
+    match clipboard_backend() {
+        ClipboardBackend::System => copy_to_system_clipboard(target, text).await,
+        ClipboardBackend::Osc52 => copy_with_osc52(target, text),
+        ClipboardBackend::Both => {
+            let system = copy_to_system_clipboard(target, text).await;
+            let osc52 = copy_with_osc52(target, text);
+            match (system, osc52) {
+                (Err(e), Err(osc52)) => Err(e.context(format!("OSC 52 also failed: {osc52:#}"))),
+                (Err(e), Ok(())) => {
+                    tracing::debug!("System clipboard failed, sent OSC 52 only: {:#}", e);
+                    Ok(())
+                }
+                (Ok(()), Err(e)) => {
+                    tracing::debug!("OSC 52 failed, used the system clipboard only: {:#}", e);
+                    Ok(())
+                }
+                (Ok(()), Ok(())) => Ok(()),
+            }
+        }
+    }
+}
+
+/// Write an OSC 52 "set clipboard" sequence to the controlling terminal.
+/// Inside tmux this needs `set -g set-clipboard on`.
+fn copy_with_osc52(target: ClipboardTarget, text: &str) -> Result<()> {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use std::io::Write;
+
+    let encoded = STANDARD.encode(text);
+    if encoded.len() > OSC52_MAX_BYTES {
+        tracing::warn!(
+            "OSC 52 payload is {} bytes; some terminals drop sequences this long",
+            encoded.len()
+        );
+    }
+    let mut tty = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .context("Failed to open the controlling terminal for OSC 52")?;
+    let selection = match target {
+        ClipboardTarget::Clipboard => 'c',
+        ClipboardTarget::Primary => 'p',
+    };
+    write!(tty, "\x1b]52;{selection};{encoded}\x07")?;
+    tty.flush()?;
+    Ok(())
+}
+
+/// Copy text to the system clipboard or primary selection.
 ///
-/// - Linux: uses `wl-copy`
-/// - macOS: uses `pbcopy`
+/// - Linux: uses `wl-copy` (`--primary` for the primary selection), or
+///   `wl-clipboard-rs` in-process with --portal
+/// - macOS: uses `pbcopy`; there is no primary selection
 #[cfg(target_os = "linux")]
-pub async fn copy_to_clipboard(text: &str) -> Result<()> {
+async fn copy_to_system_clipboard(target: ClipboardTarget, text: &str) -> Result<()> {
+    if portal::enabled() {
+        use wl_clipboard_rs::copy::{ClipboardType, MimeType, Options, Source};
+
+        let clipboard = match target {
+            ClipboardTarget::Clipboard => ClipboardType::Regular,
+            ClipboardTarget::Primary => ClipboardType::Primary,
+        };
+        let source = Source::Bytes(text.as_bytes().into());
+        // Serving blocks until another client takes ownership
+        std::thread::spawn(move || {
+            let mut opts = Options::new();
+            opts.foreground(true).clipboard(clipboard);
+            if let Err(e) = opts.copy(source, MimeType::Text) {
+                tracing::error!("Failed to serve clipboard: {}", e);
+            }
+        });
+        return Ok(());
+    }
+
+    let args: &[&str] = match target {
+        ClipboardTarget::Clipboard => &[],
+        ClipboardTarget::Primary => &["--primary"],
+    };
     run_stdin_command(
         "wl-copy",
+        args,
         text,
-        "Failed to run wl-copy (is wl-clipboard installed?)",
+        &format!(
+            "Failed to run wl-copy ({})",
+            i18n::missing_tool("wl-clipboard")
+        ),
     )
     .await
 }
 
 #[cfg(target_os = "macos")]
-pub async fn copy_to_clipboard(text: &str) -> Result<()> {
-    run_stdin_command("pbcopy", text, "Failed to run pbcopy").await
+async fn copy_to_system_clipboard(target: ClipboardTarget, text: &str) -> Result<()> {
+    if target == ClipboardTarget::Primary {
+        anyhow::bail!("macOS has no primary selection");
+    }
+    run_stdin_command("pbcopy", &[], text, "Failed to run pbcopy").await
+}
+
+/// Copy text to the system clipboard, offering both a plain-text and an HTML
+/// flavour so rich-text editors keep the formatting.
+///
+/// - Linux: serves both MIME types via `wl-clipboard-rs` (`wl-copy` can only
+///   offer one type per invocation)
+/// - macOS: writes both pasteboard types via JavaScript for Automation
+#[cfg(target_os = "linux")]
+pub async fn copy_rich_to_clipboard(plain: &str, html: &str) -> Result<()> {
+    use wl_clipboard_rs::copy::{MimeSource, MimeType, Options, Source};
+
+    let sources = vec![
+        MimeSource {
+            source: Source::Bytes(plain.as_bytes().into()),
+            mime_type: MimeType::Text,
+        },
+        MimeSource {
+            source: Source::Bytes(html.as_bytes().into()),
+            mime_type: MimeType::Specific("text/html".to_string()),
+        },
+    ];
+
+    // Serving blocks until another client takes ownership of the clipboard,
+    // so only the offer is waited for
+    let (offered, offer) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let mut opts = Options::new();
+        opts.foreground(true);
+        let prepared = match opts.prepare_copy_multi(sources) {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                let _ = offered.send(Err(e));
+                return;
+            }
+        };
+        let _ = offered.send(Ok(()));
+        if let Err(e) = prepared.serve() {
+            tracing::error!("Failed to serve rich-text clipboard: {}", e);
+        }
+    });
+
+    offer
+        .await
+        .context("Rich-text clipboard thread exited")?
+        .context("Failed to set rich-text clipboard")
+}
+
+#[cfg(target_os = "macos")]
+pub async fn copy_rich_to_clipboard(plain: &str, html: &str) -> Result<()> {
+    const SCRIPT: &str = r#"ObjC.import('AppKit');
+function run(argv) {
+    const pb = $.NSPasteboard.generalPasteboard;
+    pb.clearContents;
+    pb.setStringForType($(argv[0]), $.NSPasteboardTypeString);
+    pb.setStringForType($(argv[1]), $.NSPasteboardTypeHTML);
+}"#;
+
+    let status = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", SCRIPT, plain, html])
+        .status()
+        .await
+        .context("Failed to run osascript")?;
+    if !status.success() {
+        anyhow::bail!("Failed to set rich-text clipboard via osascript: {status}");
+    }
+
+    Ok(())
 }
 
 /// Get selected text.
 ///
-/// - Linux: reads the Wayland primary selection via `wl-paste --primary`
+/// - Linux: reads the Wayland primary selection via `wl-paste --primary`, or
+///   `wl-clipboard-rs` in-process with --portal
 /// - macOS: simulates Cmd+C to copy highlighted text, then reads via `pbpaste`
 #[cfg(target_os = "linux")]
 pub async fn get_primary_selection() -> Result<String> {
+    if portal::enabled() {
+        return paste(wl_clipboard_rs::paste::ClipboardType::Primary).await;
+    }
     let output = Command::new("wl-paste")
         .arg("--primary")
         .output()
         .await
-        .context("Failed to get primary selection (is wl-clipboard installed?)")?;
+        .with_context(|| {
+            format!(
+                "Failed to get primary selection ({})",
+                i18n::missing_tool("wl-clipboard")
+            )
+        })?;
 
     if !output.status.success() {
         anyhow::bail!("wl-paste failed: {:?}", output.status);
@@ -126,17 +455,238 @@ pub async fn get_primary_selection() -> Result<String> {
     Ok(text)
 }
 
+/// Get the clipboard text (the system clipboard, whatever the backend).
+/// Content without a text flavour, such as an image, reads as empty.
+///
+/// - Linux: `wl-paste`, or `wl-clipboard-rs` in-process with --portal
+/// - macOS: `pbpaste`
+#[cfg(target_os = "linux")]
+pub async fn get_clipboard() -> Result<String> {
+    if portal::enabled() {
+        return Ok(paste(wl_clipboard_rs::paste::ClipboardType::Regular)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::debug!("Failed to read clipboard: {:#}", e);
+                String::new()
+            }));
+    }
+    let output = Command::new("wl-paste")
+        .args(["--no-newline", "--type", "text"])
+        .output()
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to get clipboard contents ({})",
+                i18n::missing_tool("wl-clipboard")
+            )
+        })?;
+
+    // wl-paste fails when the clipboard is empty
+    if !output.status.success() {
+        tracing::debug!("wl-paste failed: {:?}", output.status);
+        return Ok(String::new());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Read text from the clipboard or primary selection without running
+/// `wl-paste`.
+#[cfg(target_os = "linux")]
+async fn paste(clipboard: wl_clipboard_rs::paste::ClipboardType) -> Result<String> {
+    use std::io::Read;
+    use wl_clipboard_rs::paste::{MimeType, Seat, get_contents};
+
+    tokio::task::spawn_blocking(move || {
+        let (mut pipe, _) = get_contents(clipboard, Seat::Unspecified, MimeType::Text)
+            .context("Failed to read the Wayland clipboard")?;
+        let mut text = Vec::new();
+        pipe.read_to_end(&mut text)?;
+        Ok(String::from_utf8_lossy(&text).to_string())
+    })
+    .await?
+}
+
+#[cfg(target_os = "macos")]
+pub async fn get_clipboard() -> Result<String> {
+    let output = Command::new("pbpaste")
+        .output()
+        .await
+        .context("Failed to get clipboard contents (is pbpaste available?)")?;
+
+    if !output.status.success() {
+        anyhow::bail!("pbpaste failed: {:?}", output.status);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Text typed at the cursor while a request is in flight, then erased with
+/// one Backspace per character before the result is typed. Keep it to
+/// characters that a single Backspace removes (no combining marks or emoji
+/// sequences).
+pub struct Placeholder {
+    text: String,
+    shown: AtomicBool,
+}
+
+impl Placeholder {
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            shown: AtomicBool::new(false),
+        }
+    }
+
+    pub async fn show(&self) {
+        match type_text(&self.text).await {
+            Ok(()) => self.shown.store(true, Relaxed),
+            Err(e) => tracing::warn!("Failed to type placeholder: {}", e),
+        }
+    }
+
+    /// Erase the placeholder if it is showing.
+    pub async fn erase(&self) {
+        if !self.shown.swap(false, Relaxed) {
+            return;
+        }
+        if let Err(e) = erase(self.text.chars().count()).await {
+            tracing::warn!("Failed to erase placeholder: {}", e);
+        }
+    }
+
+    /// Forget the placeholder without erasing it, when focus has moved away.
+    pub fn abandon(&self) {
+        self.shown.store(false, Relaxed);
+    }
+}
+
+/// Delete `count` characters before the cursor.
+///
+/// - Linux: uses `wtype` to simulate Backspace, or the RemoteDesktop portal
+///   with --portal
+/// - macOS: uses `osascript` to simulate Delete
+#[cfg(target_os = "linux")]
+pub async fn erase(count: usize) -> Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    if portal::enabled() {
+        for _ in 0..count {
+            portal::press(&[portal::BACKSPACE]).await?;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        return Ok(());
+    }
+
+    let mut cmd = Command::new("wtype");
+    for _ in 0..count {
+        cmd.args(["-k", "BackSpace"]);
+    }
+    let status = cmd
+        .status()
+        .await
+        .with_context(|| format!("Failed to erase text ({})", i18n::missing_tool("wtype")))?;
+    if !status.success() {
+        anyhow::bail!("wtype failed to erase text: {status}");
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn erase(count: usize) -> Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+
+    let script = format!(
+        r#"tell application "System Events"
+    repeat {count} times
+        key code 51
+    end repeat
+end tell"#
+    );
+    let status = Command::new("osascript")
+        .arg("-e")
+        .arg(&script)
+        .status()
+        .await
+        .context("Failed to run osascript")?;
+    if !status.success() {
+        anyhow::bail!(
+            "Failed to erase text via osascript (check Accessibility permissions): {status}"
+        );
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    Ok(())
+}
+
+/// Delete the selected text by sending Backspace.
+///
+/// - Linux: uses `wtype` to simulate Backspace
+/// - macOS: uses `osascript` to simulate Delete
+#[cfg(target_os = "linux")]
+pub async fn delete_selection() -> Result<()> {
+    if portal::enabled() {
+        portal::press(&[portal::BACKSPACE]).await?;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        return Ok(());
+    }
+    let status = Command::new("wtype")
+        .args(["-k", "BackSpace"])
+        .status()
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to delete selection ({})",
+                i18n::missing_tool("wtype")
+            )
+        })?;
+    if !status.success() {
+        anyhow::bail!("wtype failed to delete the selection: {status}");
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn delete_selection() -> Result<()> {
+    let status = Command::new("osascript")
+        .arg("-e")
+        .arg(r#"tell application "System Events" to key code 51"#)
+        .status()
+        .await
+        .context("Failed to run osascript")?;
+    if !status.success() {
+        anyhow::bail!(
+            "Failed to delete selection via osascript (check Accessibility permissions): {status}"
+        );
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    Ok(())
+}
+
 /// Clear the current terminal line by sending Ctrl+U.
 ///
 /// - Linux: uses `wtype` to simulate Ctrl+U
 /// - macOS: uses `osascript` to simulate Ctrl+U
 #[cfg(target_os = "linux")]
 pub async fn clear_line() -> Result<()> {
+    if portal::enabled() {
+        portal::press(&[portal::CONTROL, 'u' as i32]).await?;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        return Ok(());
+    }
     Command::new("wtype")
         .args(["-M", "ctrl", "-k", "u", "-m", "ctrl"])
         .status()
         .await
-        .context("Failed to clear line (is wtype installed?)")?;
+        .with_context(|| format!("Failed to clear line ({})", i18n::missing_tool("wtype")))?;
 
     tokio::time::sleep(Duration::from_millis(50)).await;
     Ok(())
@@ -0,0 +1,544 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Read;
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::AcqRel, Ordering::Acquire};
+
+use crate::Key;
+use crate::queue::PressQueue;
+
+/// Event types (`linux/input-event-codes.h`).
+const EV_KEY: u16 = 0x01;
+const EV_MSC: u16 = 0x04;
+const KEY_MAX: usize = 0x2ff;
+const MSC_MAX: usize = 0x07;
+
+/// Modifier bits of a hotkey.
+const CTRL: u8 = 1;
+const SHIFT: u8 = 2;
+const ALT: u8 = 4;
+const SUPER: u8 = 8;
+
+/// Left and right modifier keys and their bits.
+const MODIFIER_KEYS: [(u16, u8); 8] = [
+    (29, CTRL),
+    (97, CTRL),
+    (42, SHIFT),
+    (54, SHIFT),
+    (56, ALT),
+    (100, ALT),
+    (125, SUPER),
+    (126, SUPER),
+];
+
+/// A hotkey as the key code and modifiers the kernel reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Binding {
+    code: u16,
+    modifiers: u8,
+}
+
+/// The kernel's `struct input_mask`, for `EVIOCSMASK`.
+#[repr(C)]
+struct InputMask {
+    kind: u32,
+    codes_size: u32,
+    codes_ptr: u64,
+}
+
+/// A keyboard and the hotkeys read from it.
+struct Keyboard {
+    path: PathBuf,
+    name: String,
+    file: File,
+    /// Hotkey indices and bindings, those bound to this keyboard by name
+    /// first
+    bindings: Vec<(usize, Binding)>,
+}
+
+/// Read `keys` from every keyboard that has their keys (and, for a key bound
+/// to a device, whose name matches), asking the kernel to deliver only those
+/// keys and the modifiers: no other key event, scan code included, ever
+/// reaches this process. Presses go to the returned queue, by hotkey index.
+pub fn spawn(keys: &[Key], capacity: usize, running: Arc<AtomicBool>) -> Result<Arc<PressQueue>> {
+    let bindings: Vec<Binding> = keys
+        .iter()
+        .map(|key| binding(&key.name))
+        .collect::<Result<_>>()?;
+
+    let keyboards = keyboards(keys, &bindings)?;
+    if keyboards.is_empty() {
+        anyhow::bail!("No readable keyboard in /dev/input has the hotkeys' keys");
+    }
+    for (i, key) in keys.iter().enumerate() {
+        if key.device.is_some()
+            && !keyboards
+                .iter()
+                .any(|keyboard| keyboard.bindings.iter().any(|&(j, _)| i == j))
+        {
+            tracing::warn!("No keyboard matches {}; it will never fire", key);
+        }
+    }
+
+    let queue = PressQueue::new(capacity);
+    let readers = Arc::new(AtomicUsize::new(keyboards.len()));
+    for keyboard in keyboards {
+        let Keyboard {
+            path,
+            name,
+            file,
+            bindings,
+        } = keyboard;
+        let mut allowed: Vec<u16> = bindings.iter().map(|(_, binding)| binding.code).collect();
+        allowed.extend(MODIFIER_KEYS.iter().map(|&(code, _)| code));
+        mask(&file, EV_KEY, &allowed, KEY_MAX)
+            .and_then(|()| mask(&file, EV_MSC, &[], MSC_MAX))
+            .with_context(|| format!("Failed to filter events of {}", path.display()))?;
+        tracing::info!("Reading hotkeys only from {} ({})", path.display(), name);
+
+        let (queue, running, readers) = (queue.clone(), running.clone(), readers.clone());
+        std::thread::spawn(move || {
+            if let Err(e) = read_presses(file, &bindings, &allowed, &queue, &running) {
+                tracing::warn!("Stopped reading {}: {}", path.display(), e);
+            }
+            if readers.fetch_sub(1, AcqRel) == 1 {
+                queue.disconnect();
+            }
+        });
+    }
+    Ok(queue)
+}
+
+fn read_presses(
+    mut file: impl Read,
+    bindings: &[(usize, Binding)],
+    allowed: &[u16],
+    queue: &PressQueue,
+    running: &AtomicBool,
+) -> Result<()> {
+    let mut buf = [0u8; size_of::<libc::input_event>()];
+    // Modifier keys held down, by code
+    let mut held: Vec<u16> = Vec::new();
+    while running.load(Acquire) {
+        file.read_exact(&mut buf)?;
+        let event: libc::input_event = unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+        // The kernel already filters; never look at anything else regardless
+        if event.type_ != EV_KEY || !allowed.contains(&event.code) {
+            continue;
+        }
+
+        if modifier(event.code).is_some() {
+            match event.value {
+                0 => held.retain(|&code| code != event.code),
+                1 if !held.contains(&event.code) => held.push(event.code),
+                _ => {}
+            }
+            continue;
+        }
+        // Releases and auto-repeats
+        if event.value != 1 {
+            continue;
+        }
+        let modifiers = held
+            .iter()
+            .filter_map(|&code| modifier(code))
+            .fold(0, |a, b| a | b);
+        if let Some(&(i, _)) = bindings
+            .iter()
+            .find(|(_, binding)| binding.code == event.code && binding.modifiers == modifiers)
+        {
+            queue.push(i);
+        }
+    }
+    Ok(())
+}
+
+fn modifier(code: u16) -> Option<u8> {
+    MODIFIER_KEYS
+        .iter()
+        .find(|&&(key, _)| key == code)
+        .map(|&(_, bit)| bit)
+}
+
+/// Whether the evdev reader can read `hotkey`, for the numpad and media keys
+/// `parse_hotkey` doesn't know.
+pub fn knows(hotkey: &str) -> bool {
+    binding(hotkey).is_ok()
+}
+
+/// The evdev binding of a hotkey, from its canonical form (`Ctrl+Shift+F8`,
+/// as `parse_hotkey` accepts and `Display` prints it, or `KP_Enter`).
+fn binding(hotkey: &str) -> Result<Binding> {
+    let mut modifiers = 0;
+    let mut code = None;
+    for part in hotkey.split('+').map(str::trim) {
+        let bit = match part.to_lowercase().as_str() {
+            "ctrl" | "control" => CTRL,
+            "shift" => SHIFT,
+            "alt" => ALT,
+            "super" | "meta" | "cmd" | "logo" => SUPER,
+            _ => 0,
+        };
+        if bit != 0 {
+            modifiers |= bit;
+            continue;
+        }
+        let key = key_code(part)
+            .with_context(|| format!("{hotkey}: {part} is not supported with --paranoid"))?;
+        if code.replace(key).is_some() {
+            anyhow::bail!("{hotkey} has more than one key");
+        }
+    }
+    let code = code.with_context(|| format!("{hotkey} has no key"))?;
+    Ok(Binding { code, modifiers })
+}
+
+/// evdev codes of the numpad digits 0-9.
+const KP_DIGITS: [u16; 10] = [82, 79, 80, 81, 75, 76, 77, 71, 72, 73];
+
+/// evdev code of a key name: letters, digits, F1-F24, common named keys,
+/// numpad keys (`KP_1`, `KP_Enter`) and media keys, by their X name
+/// (`XF86Calculator`) or kernel name (`KEY_CALC`, `KEY_PROG1`).
+fn key_code(name: &str) -> Option<u16> {
+    const LETTERS: &[u8; 26] = b"abcdefghijklmnopqrstuvwxyz";
+    const LETTER_CODES: [u16; 26] = [
+        30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38, 50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17,
+        45, 21, 44,
+    ];
+
+    let name = name.to_lowercase();
+    let name = name.strip_prefix("key_").unwrap_or(&name);
+    if let [c] = name.as_bytes() {
+        return match c {
+            b'a'..=b'z' => LETTERS.iter().position(|l| l == c).map(|i| LETTER_CODES[i]),
+            b'0' => Some(11),
+            b'1'..=b'9' => Some(2 + u16::from(c - b'1')),
+            _ => None,
+        };
+    }
+    if let Some(n) = name
+        .strip_prefix("kp_")
+        .or_else(|| name.strip_prefix("kp"))
+        .and_then(|n| n.parse::<u16>().ok())
+    {
+        return KP_DIGITS.get(usize::from(n)).copied();
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+        return match n {
+            1..=10 => Some(58 + n),
+            11 => Some(87),
+            12 => Some(88),
+            13..=24 => Some(170 + n),
+            _ => None,
+        };
+    }
+    let code = match name {
+        "esc" | "escape" => 1,
+        "backspace" => 14,
+        "tab" => 15,
+        "enter" | "return" => 28,
+        "space" => 57,
+        "capslock" => 58,
+        "scrolllock" => 70,
+        "print" | "printscreen" | "sysrq" => 99,
+        "home" => 102,
+        "up" => 103,
+        "pageup" => 104,
+        "left" => 105,
+        "right" => 106,
+        "end" => 107,
+        "down" => 108,
+        "pagedown" => 109,
+        "insert" => 110,
+        "delete" => 111,
+        "pause" => 119,
+        "menu" | "compose" => 127,
+        "numlock" | "num_lock" => 69,
+        "kp_enter" | "kpenter" => 96,
+        "kp_add" | "kpplus" => 78,
+        "kp_subtract" | "kpminus" => 74,
+        "kp_multiply" | "kpasterisk" => 55,
+        "kp_divide" | "kpslash" => 98,
+        "kp_decimal" | "kpdot" => 83,
+        "kp_equal" | "kpequal" => 117,
+        "xf86audiomute" | "mute" => 113,
+        "xf86audiolowervolume" | "volumedown" => 114,
+        "xf86audioraisevolume" | "volumeup" => 115,
+        "xf86calculator" | "calc" => 140,
+        "xf86launch1" | "prog1" => 148,
+        "xf86launch2" | "prog2" => 149,
+        "xf86www" | "www" => 150,
+        "xf86mail" | "mail" => 155,
+        "xf86favorites" | "bookmarks" => 156,
+        "xf86mycomputer" | "computer" => 157,
+        "xf86back" | "back" => 158,
+        "xf86forward" | "forward" => 159,
+        "xf86audionext" | "nextsong" => 163,
+        "xf86audioplay" | "playpause" => 164,
+        "xf86audioprev" | "previoussong" => 165,
+        "xf86audiostop" | "stopcd" => 166,
+        "xf86homepage" | "homepage" => 172,
+        "xf86reload" | "refresh" => 173,
+        "xf86launch3" | "prog3" => 202,
+        "xf86launch4" | "prog4" => 203,
+        "xf86search" | "search" => 217,
+        "xf86audiomicmute" | "micmute" => 248,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Readable `/dev/input/event*` devices with at least one hotkey's key, each
+/// with the hotkeys it should be read for. A key bound to a device is read
+/// from the keyboards whose name contains it (ignoring case), and wins over
+/// the same key bound everywhere.
+fn keyboards(keys: &[Key], bindings: &[Binding]) -> Result<Vec<Keyboard>> {
+    let mut keyboards = Vec::new();
+    for entry in std::fs::read_dir("/dev/input").context("Failed to list /dev/input")? {
+        let path = entry?.path();
+        if !path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with("event"))
+        {
+            continue;
+        }
+        let Ok(file) = File::open(&path) else {
+            continue;
+        };
+        let Ok(codes) = key_bits(&file) else {
+            continue;
+        };
+        let has = |code: u16| codes[code as usize / 8] & (1 << (code % 8)) != 0;
+        let name = device_name(&file).unwrap_or_default();
+        let lower = name.to_lowercase();
+        let mut read: Vec<(usize, Binding)> = keys
+            .iter()
+            .zip(bindings)
+            .enumerate()
+            .filter(|(_, (key, binding))| {
+                has(binding.code)
+                    && key
+                        .device
+                        .as_ref()
+                        .is_none_or(|device| lower.contains(&device.to_lowercase()))
+            })
+            .map(|(i, (_, &binding))| (i, binding))
+            .collect();
+        if read.is_empty() {
+            continue;
+        }
+        read.sort_by_key(|&(i, _)| keys[i].device.is_none());
+        keyboards.push(Keyboard {
+            path,
+            name,
+            file,
+            bindings: read,
+        });
+    }
+    Ok(keyboards)
+}
+
+/// `_IOC` for the evdev (`'E'`) ioctls.
+const fn ioc(dir: u32, nr: u32, size: usize) -> u32 {
+    (dir << 30) | ((size as u32) << 16) | ((b'E' as u32) << 8) | nr
+}
+
+const IOC_WRITE: u32 = 1;
+const IOC_READ: u32 = 2;
+
+/// The keys a device has (`EVIOCGBIT(EV_KEY)`), as a bitmap.
+fn key_bits(file: &File) -> std::io::Result<Vec<u8>> {
+    let mut bits = vec![0u8; KEY_MAX / 8 + 1];
+    let request = ioc(IOC_READ, 0x20 + u32::from(EV_KEY), bits.len());
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, bits.as_mut_ptr()) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(bits)
+}
+
+/// The device's name (`EVIOCGNAME`), e.g. `AT Translated Set 2 keyboard`.
+fn device_name(file: &File) -> std::io::Result<String> {
+    let mut name = [0u8; 256];
+    let request = ioc(IOC_READ, 0x06, name.len());
+    let len = unsafe { libc::ioctl(file.as_raw_fd(), request as _, name.as_mut_ptr()) };
+    if len < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let name = &name[..len as usize];
+    let name = name.split(|&b| b == 0).next().unwrap_or_default();
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Have the kernel deliver only `codes` of events of `kind` to this file
+/// descriptor (`EVIOCSMASK`, Linux 4.4).
+fn mask(file: &File, kind: u16, codes: &[u16], max: usize) -> Result<()> {
+    let mut bits = vec![0u8; max / 8 + 1];
+    for &code in codes {
+        bits[code as usize / 8] |= 1 << (code % 8);
+    }
+    let mask = InputMask {
+        kind: u32::from(kind),
+        codes_size: bits.len() as u32,
+        codes_ptr: bits.as_ptr() as u64,
+    };
+    let request = ioc(IOC_WRITE, 0x93, size_of::<InputMask>());
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, &mask) } < 0 {
+        return Err(std::io::Error::last_os_error()).context("EVIOCSMASK failed");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hotkey_listener::parse_hotkey;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    fn parsed(hotkey: &str) -> Binding {
+        binding(&parse_hotkey(hotkey).unwrap().to_string()).unwrap()
+    }
+
+    #[test]
+    fn binds_parsed_hotkeys() {
+        assert_eq!(
+            parsed("F9"),
+            Binding {
+                code: 67,
+                modifiers: 0
+            }
+        );
+        assert_eq!(
+            parsed("Ctrl+Shift+F8"),
+            Binding {
+                code: 66,
+                modifiers: CTRL | SHIFT
+            }
+        );
+        assert_eq!(
+            parsed("Alt+F12"),
+            Binding {
+                code: 88,
+                modifiers: ALT
+            }
+        );
+    }
+
+    #[test]
+    fn maps_key_names() {
+        assert_eq!(key_code("a"), Some(30));
+        assert_eq!(key_code("Z"), Some(44));
+        assert_eq!(key_code("0"), Some(11));
+        assert_eq!(key_code("1"), Some(2));
+        assert_eq!(key_code("F1"), Some(59));
+        assert_eq!(key_code("F10"), Some(68));
+        assert_eq!(key_code("F11"), Some(87));
+        assert_eq!(key_code("F13"), Some(183));
+        assert_eq!(key_code("F24"), Some(194));
+        assert_eq!(key_code("Escape"), Some(1));
+        assert_eq!(key_code("F25"), None);
+        assert_eq!(key_code("KeypadPlus"), None);
+    }
+
+    #[test]
+    fn maps_numpad_and_media_keys() {
+        assert_eq!(key_code("KP_0"), Some(82));
+        assert_eq!(key_code("KP_1"), Some(79));
+        assert_eq!(key_code("KP9"), Some(73));
+        assert_eq!(key_code("KP_10"), None);
+        assert_eq!(key_code("KP_Enter"), Some(96));
+        assert_eq!(key_code("KEY_KPENTER"), Some(96));
+        assert_eq!(key_code("KEY_PROG1"), Some(148));
+        assert_eq!(key_code("KEY_CALC"), Some(140));
+        assert_eq!(key_code("XF86Calculator"), Some(140));
+        assert_eq!(key_code("XF86AudioPlay"), Some(164));
+        assert_eq!(
+            binding("Ctrl+KP_Add").unwrap(),
+            Binding {
+                code: 78,
+                modifiers: CTRL
+            }
+        );
+        assert!(knows("KEY_PROG1"));
+        assert!(!knows("KP_Banana"));
+    }
+
+    fn event(code: u16, value: i32) -> Vec<u8> {
+        let event = libc::input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_: EV_KEY,
+            code,
+            value,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                (&event as *const libc::input_event).cast::<u8>(),
+                size_of::<libc::input_event>(),
+            )
+        };
+        bytes.to_vec()
+    }
+
+    /// Hotkey indices `read_presses` pushes for `events`.
+    fn presses(bindings: &[Binding], allowed: &[u16], events: &[(u16, i32)]) -> Vec<usize> {
+        let input: Vec<u8> = events
+            .iter()
+            .flat_map(|&(code, value)| event(code, value))
+            .collect();
+        // The queue spawns its busy notifications on the runtime
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let queue = PressQueue::new(16);
+        let running = AtomicBool::new(true);
+        let bindings: Vec<(usize, Binding)> = bindings.iter().copied().enumerate().collect();
+        // Ends with the input
+        let _ = read_presses(input.as_slice(), &bindings, allowed, &queue, &running);
+        std::iter::from_fn(|| queue.recv_timeout(Duration::from_millis(10)).ok()).collect()
+    }
+
+    #[test]
+    fn pushes_only_allowed_presses() {
+        let bindings = [parsed("Ctrl+F8"), parsed("F9")];
+        let allowed = [66, 67, 29];
+        // Ctrl down, F8 down and up, Ctrl up, then F9 with a repeat
+        let pushed = presses(
+            &bindings,
+            &allowed,
+            &[
+                (29, 1),
+                (66, 1),
+                (66, 0),
+                (29, 0),
+                (67, 1),
+                (67, 2),
+                (67, 0),
+            ],
+        );
+        assert_eq!(pushed, vec![0, 1]);
+
+        // F8 without Ctrl matches nothing
+        assert!(presses(&bindings, &allowed, &[(66, 1), (66, 0)]).is_empty());
+    }
+
+    #[test]
+    fn never_looks_at_codes_outside_the_mask() {
+        // A binding for A that the mask leaves out is never matched
+        let bindings = [
+            parsed("F9"),
+            Binding {
+                code: 30,
+                modifiers: 0,
+            },
+        ];
+        let allowed = [67];
+        assert!(presses(&bindings, &allowed, &[(30, 1), (30, 0)]).is_empty());
+        // Nor does a modifier outside the mask count as held
+        let bindings = [parsed("Ctrl+F9")];
+        assert!(presses(&bindings, &allowed, &[(29, 1), (67, 1)]).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use zbus::{Connection, zvariant::OwnedObjectPath};
+
+/// How long the AT-SPI tree may take to search; applications answer one
+/// call at a time, and a large browser tree takes a while.
+#[cfg(target_os = "linux")]
+const SEARCH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Accessibles looked at before giving up.
+#[cfg(target_os = "linux")]
+const MAX_NODES: usize = 2000;
+
+/// AT-SPI states and roles (`atspi-constants.h`).
+#[cfg(target_os = "linux")]
+const STATE_ACTIVE: u32 = 1;
+#[cfg(target_os = "linux")]
+const STATE_FOCUSED: u32 = 12;
+#[cfg(target_os = "linux")]
+const STATE_SHOWING: u32 = 25;
+#[cfg(target_os = "linux")]
+const ROLE_PASSWORD_TEXT: u32 = 40;
+
+/// An accessible: its application's bus name and object path.
+#[cfg(target_os = "linux")]
+type Accessible = (String, OwnedObjectPath);
+
+/// Whether the focused field takes a password, as far as the platform
+/// tells.
+///
+/// - Linux: the focused AT-SPI accessible in the active window has the
+///   `password text` role (GTK, Qt, Firefox and Chromium report it once
+///   accessibility is on)
+/// - macOS: Secure Event Input is enabled, as it is while a password field
+///   (or a terminal's secure keyboard entry) has focus
+#[cfg(target_os = "linux")]
+pub async fn focused() -> Result<bool> {
+    let session = Connection::session().await?;
+    let address: String = session
+        .call_method(
+            Some("org.a11y.Bus"),
+            "/org/a11y/bus",
+            Some("org.a11y.Bus"),
+            "GetAddress",
+            &(),
+        )
+        .await
+        .context("The AT-SPI bus is not available")?
+        .body()
+        .deserialize()?;
+    let bus = zbus::connection::Builder::address(address.as_str())?
+        .build()
+        .await
+        .context("Failed to connect to the AT-SPI bus")?;
+
+    let Ok(role) = tokio::time::timeout(SEARCH_TIMEOUT, focused_role(&bus)).await else {
+        tracing::debug!("Gave up looking for the focused accessible");
+        return Ok(false);
+    };
+    Ok(role? == Some(ROLE_PASSWORD_TEXT))
+}
+
+#[cfg(target_os = "macos")]
+pub async fn focused() -> Result<bool> {
+    #[link(name = "Carbon", kind = "framework")]
+    unsafe extern "C" {
+        fn IsSecureEventInputEnabled() -> u8;
+    }
+    Ok(unsafe { IsSecureEventInputEnabled() } != 0)
+}
+
+/// Role of the focused accessible in the active window: a breadth-first
+/// search through the showing accessibles of the applications' active
+/// windows.
+#[cfg(target_os = "linux")]
+async fn focused_role(bus: &Connection) -> Result<Option<u32>> {
+    let root: Accessible = (
+        "org.a11y.atspi.Registry".to_string(),
+        OwnedObjectPath::try_from("/org/a11y/atspi/accessible/root")?,
+    );
+    let mut queue = std::collections::VecDeque::new();
+    for app in children(bus, &root).await? {
+        for window in children(bus, &app).await.unwrap_or_default() {
+            let active = states(bus, &window)
+                .await
+                .is_ok_and(|states| has_state(&states, STATE_ACTIVE));
+            if active {
+                queue.push_back(window);
+            }
+        }
+    }
+
+    let mut seen = 0;
+    while let Some(accessible) = queue.pop_front() {
+        seen += 1;
+        if seen > MAX_NODES {
+            break;
+        }
+        let Ok(states) = states(bus, &accessible).await else {
+            continue;
+        };
+        if has_state(&states, STATE_FOCUSED) {
+            let role: u32 = call(bus, &accessible, "GetRole").await?;
+            return Ok(Some(role));
+        }
+        if has_state(&states, STATE_SHOWING) {
+            queue.extend(children(bus, &accessible).await.unwrap_or_default());
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(target_os = "linux")]
+async fn children(bus: &Connection, accessible: &Accessible) -> Result<Vec<Accessible>> {
+    call(bus, accessible, "GetChildren").await
+}
+
+#[cfg(target_os = "linux")]
+async fn states(bus: &Connection, accessible: &Accessible) -> Result<Vec<u32>> {
+    call(bus, accessible, "GetState").await
+}
+
+/// States come as a bit set in 32-bit words.
+#[cfg(target_os = "linux")]
+fn has_state(states: &[u32], state: u32) -> bool {
+    states
+        .get((state / 32) as usize)
+        .is_some_and(|word| word & (1 << (state % 32)) != 0)
+}
+
+#[cfg(target_os = "linux")]
+async fn call<T>(bus: &Connection, (name, path): &Accessible, method: &str) -> Result<T>
+where
+    T: for<'de> serde::Deserialize<'de> + zbus::zvariant::Type,
+{
+    Ok(bus
+        .call_method(
+            Some(name.as_str()),
+            path,
+            Some("org.a11y.atspi.Accessible"),
+            method,
+            &(),
+        )
+        .await?
+        .body()
+        .deserialize()?)
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+#[cfg(target_os = "linux")]
+use std::process::Stdio;
+#[cfg(target_os = "linux")]
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Entries longer than this are cut short in the picker list.
+const MAX_ENTRY_CHARS: usize = 200;
+
+/// Let the user choose one of several options. Returns the index of the
+/// chosen option, or `None` if the picker was dismissed.
+///
+/// - Linux: `rofi -dmenu`, falling back to `wofi --dmenu`
+/// - macOS: `osascript` `choose from list`
+pub async fn pick(options: &[String]) -> Result<Option<usize>> {
+    let entries: Vec<String> = options
+        .iter()
+        .enumerate()
+        .map(|(i, option)| entry(i, option))
+        .collect();
+
+    let Some(chosen) = choose(&entries).await? else {
+        return Ok(None);
+    };
+    Ok(entries.iter().position(|e| *e == chosen))
+}
+
+/// A numbered single-line label for an option.
+fn entry(i: usize, option: &str) -> String {
+    let line = option.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut label: String = line.chars().take(MAX_ENTRY_CHARS).collect();
+    if label.len() < line.len() {
+        label.push('…');
+    }
+    format!("{}. {}", i + 1, label)
+}
+
+/// Ask a yes/no question about `detail`. Only choosing `action` counts as
+/// yes; cancelling or dismissing the dialog is no.
+///
+/// - Linux: `rofi -dmenu` (with `detail` as its message), falling back to
+///   `wofi --dmenu` (with `detail` as its prompt); "Cancel" is listed first
+/// - macOS: `osascript` `display dialog` with "Cancel" as the default button
+#[cfg(target_os = "linux")]
+pub async fn confirm(question: &str, detail: &str, action: &str) -> Result<bool> {
+    let cancel = crate::i18n::t("cancel");
+    let entries = format!("{cancel}\n{action}");
+    let markup = detail
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    let line = detail.split_whitespace().collect::<Vec<_>>().join(" ");
+    let menus: [(&str, Vec<&str>); 2] = [
+        (
+            "rofi",
+            vec!["-dmenu", "-no-custom", "-p", question, "-mesg", &markup],
+        ),
+        ("wofi", vec!["--dmenu", "--prompt", &line]),
+    ];
+
+    for (program, args) in menus {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to run {program}")),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(entries.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        let chosen = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return Ok(output.status.success() && chosen == action);
+    }
+
+    anyhow::bail!("No confirmation dialog found (install rofi or wofi)")
+}
+
+#[cfg(target_os = "macos")]
+pub async fn confirm(question: &str, detail: &str, action: &str) -> Result<bool> {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let cancel = escape(&crate::i18n::t("cancel"));
+    let script = format!(
+        r#"display dialog "{}" with title "{}" buttons {{"{cancel}", "{action}"}} default button "{cancel}" cancel button "{cancel}""#,
+        escape(detail),
+        escape(question),
+        action = escape(action),
+    );
+
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(&script)
+        .output()
+        .await
+        .context("Failed to show confirmation via osascript")?;
+
+    // Cancelling makes `display dialog` fail
+    let chosen = String::from_utf8_lossy(&output.stdout);
+    Ok(output.status.success() && chosen.trim() == format!("button returned:{action}"))
+}
+
+#[cfg(target_os = "linux")]
+async fn choose(entries: &[String]) -> Result<Option<String>> {
+    let menus: [(&str, &[&str]); 2] = [
+        ("rofi", &["-dmenu", "-i", "-p", "Pick"]),
+        ("wofi", &["--dmenu", "--prompt", "Pick"]),
+    ];
+
+    for (program, args) in menus {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to run {program}")),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(entries.join("\n").as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+
+        // Both exit non-zero when dismissed
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let chosen = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return Ok(Some(chosen).filter(|c| !c.is_empty()));
+    }
+
+    anyhow::bail!("No picker found (install rofi or wofi)")
+}
+
+#[cfg(target_os = "macos")]
+async fn choose(entries: &[String]) -> Result<Option<String>> {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let list = entries
+        .iter()
+        .map(|e| format!("\"{}\"", escape(e)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let script = format!(r#"choose from list {{{list}}} with prompt "Pick a rewrite""#);
+
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(&script)
+        .output()
+        .await
+        .context("Failed to show picker via osascript")?;
+
+    // `choose from list` prints "false" when cancelled
+    let chosen = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || chosen == "false" || chosen.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(chosen))
+}
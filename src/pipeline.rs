@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use std::str::FromStr;
+
+use crate::filter;
+use crate::markdown;
+use crate::ollama::TextImprover;
+use crate::plugin::Plugin;
+use crate::postprocess::{self, Limit};
+use crate::rewrite::Rewrites;
+
+/// One step of a pipeline: either a model request or a local transform.
+#[derive(Clone, Debug)]
+pub enum Stage {
+    Improve,
+    /// Shorten, optionally overriding the character limit
+    Shorten(Option<usize>),
+    Expand,
+    Summarize,
+    /// Review the current text against the pipeline's input
+    Critic,
+    /// Strip Markdown formatting
+    Plain,
+    /// Collapse runs of whitespace (including newlines) into single spaces
+    Squeeze,
+    /// Cut to the limit (characters, or a share of the pipeline's input) at
+    /// a sentence or word boundary
+    Truncate(Limit),
+    /// Ask the model to compress the text if it is over the limit, cutting
+    /// what still overshoots
+    Fit(Limit),
+    /// Cut to at most N words
+    Words(usize),
+    /// Run a WebAssembly plugin from the plugins directory
+    Plugin(Plugin),
+    /// Replace email addresses, phone and card numbers with `[redacted]`
+    Redact,
+    /// Remove chat model tells (announcing openers, offers of more help,
+    /// em dashes, typographic quotes)
+    Deai,
+    /// Apply a set of regex replacements from the regex directory
+    Regex(Rewrites),
+    /// Fall back to the pipeline's input if protected terms were changed
+    Verify,
+}
+
+impl Stage {
+    fn uses_model(&self) -> bool {
+        !matches!(
+            self,
+            Stage::Plain
+                | Stage::Squeeze
+                | Stage::Truncate(_)
+                | Stage::Words(_)
+                | Stage::Plugin(_)
+                | Stage::Redact
+                | Stage::Deai
+                | Stage::Regex(_)
+                | Stage::Verify
+        )
+    }
+}
+
+impl FromStr for Stage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let number = |what: &str| -> Result<usize> {
+            arg.with_context(|| format!("Stage {name:?} needs a {what}, e.g. {name}:200"))?
+                .parse()
+                .with_context(|| format!("Invalid {what} in stage {s:?}"))
+        };
+
+        let limit = || -> Result<Limit> {
+            arg.with_context(|| {
+                format!("Stage {name:?} needs a limit, e.g. {name}:280 or {name}:100%")
+            })?
+            .parse()
+            .with_context(|| format!("Invalid limit in stage {s:?}"))
+        };
+
+        let stage = match name {
+            "improve" => Stage::Improve,
+            "shorten" if arg.is_some() => Stage::Shorten(Some(number("character limit")?)),
+            "shorten" => Stage::Shorten(None),
+            "expand" => Stage::Expand,
+            "summarize" => Stage::Summarize,
+            "critic" => Stage::Critic,
+            "plain" => Stage::Plain,
+            "squeeze" => Stage::Squeeze,
+            "truncate" => Stage::Truncate(limit()?),
+            "fit" => Stage::Fit(limit()?),
+            "words" => Stage::Words(number("word limit")?),
+            "verify" => Stage::Verify,
+            "redact" => Stage::Redact,
+            "deai" => Stage::Deai,
+            "regex" => Stage::Regex(Rewrites::load(
+                arg.context("Stage \"regex\" needs a name, e.g. regex:cleanup")?,
+            )?),
+            "plugin" => Stage::Plugin(Plugin::load(
+                arg.context("Stage \"plugin\" needs a name, e.g. plugin:cleanup")?,
+            )?),
+            _ => anyhow::bail!(
+                "Unknown pipeline stage {name:?} (expected improve, shorten[:CHARS], expand, \
+                 summarize, critic, plain, squeeze, truncate:LIMIT, fit:LIMIT, words:N, verify, redact, \
+                 deai, regex:NAME or plugin:NAME)"
+            ),
+        };
+        Ok(stage)
+    }
+}
+
+/// A sequence of stages, each fed the previous stage's output.
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl FromStr for Pipeline {
+    type Err = anyhow::Error;
+
+    /// Parse a comma-separated list of stages, e.g. `improve,critic,truncate:280`.
+    fn from_str(s: &str) -> Result<Self> {
+        let stages = s
+            .split(',')
+            .map(str::trim)
+            .filter(|stage| !stage.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Stage>>>()
+            .with_context(|| format!("Invalid pipeline {s:?}"))?;
+        if stages.is_empty() {
+            anyhow::bail!("Pipeline {s:?} has no stages");
+        }
+        Ok(Self { stages })
+    }
+}
+
+impl Pipeline {
+    /// Run every stage in order. `refine` (REDO) applies to the first model
+    /// stage only; later stages always start a fresh conversation.
+    pub async fn run(
+        &self,
+        improver: &mut TextImprover,
+        input: &str,
+        refine: bool,
+    ) -> Result<String> {
+        let mut text = input.to_string();
+        let mut refine = refine;
+
+        for stage in &self.stages {
+            tracing::debug!("Pipeline stage {:?}", stage);
+            text = match stage {
+                Stage::Improve => improver.improve(&text, refine).await?,
+                Stage::Shorten(None) => improver.shorten(&text, refine).await?,
+                Stage::Shorten(Some(chars)) => {
+                    let words = improver.shorten_words();
+                    improver.shorten_to(&text, refine, words, *chars).await?
+                }
+                Stage::Expand => improver.expand(&text, refine).await?,
+                Stage::Summarize => improver.summarize(&text).await?,
+                Stage::Critic => improver.critique(input, &text).await?,
+                Stage::Plain => markdown::to_plain(&text),
+                Stage::Squeeze => text.split_whitespace().collect::<Vec<_>>().join(" "),
+                Stage::Truncate(limit) => postprocess::truncate(&text, limit.resolve(input)),
+                Stage::Fit(limit) => improver.compress(&text, limit.resolve(input)).await?,
+                Stage::Words(words) => postprocess::truncate_words(&text, *words),
+                Stage::Plugin(plugin) => plugin.transform(&text)?,
+                Stage::Redact => filter::redact(&text),
+                Stage::Deai => postprocess::deai(&text),
+                Stage::Regex(rewrites) => rewrites.apply(&text),
+                Stage::Verify => {
+                    let missing = improver.missing_terms(input, &text);
+                    if !missing.is_empty() {
+                        tracing::warn!(
+                            "Pipeline changed protected terms {:?}, keeping the original",
+                            missing
+                        );
+                        return Ok(input.to_string());
+                    }
+                    text
+                }
+            };
+            if stage.uses_model() {
+                refine = false;
+            }
+
+            if text.is_empty() {
+                tracing::warn!("Pipeline stage {:?} produced no text", stage);
+                break;
+            }
+        }
+
+        Ok(text)
+    }
+}
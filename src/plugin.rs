@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::PathBuf;
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::prompts;
+
+/// Instructions a plugin may execute per call before it is stopped.
+const FUEL: u64 = 1_000_000_000;
+
+/// Memory a plugin instance may grow to.
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Directory scanned for `*.wasm` plugins.
+pub fn plugins_dir() -> PathBuf {
+    prompts::config_dir().join("plugins")
+}
+
+/// A WebAssembly text transform.
+///
+/// Plugins get no imports at all (no WASI), so they can't touch files, the
+/// network, or the clock; each call runs in a fresh instance with bounded
+/// fuel and memory. A plugin exports:
+///
+/// - `memory`
+/// - `alloc(len: i32) -> i32`: reserve `len` bytes for the input
+/// - `transform(ptr: i32, len: i32) -> i64`: transform the UTF-8 input,
+///   returning the output as `(ptr << 32) | len`
+#[derive(Clone)]
+pub struct Plugin {
+    name: String,
+    engine: Engine,
+    module: Module,
+}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Plugin({})", self.name)
+    }
+}
+
+impl Plugin {
+    /// Compile `<plugins dir>/<name>.wasm`.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = plugins_dir().join(format!("{name}.wasm"));
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, &path)
+            .with_context(|| format!("Failed to load plugin {}", path.display()))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            engine,
+            module,
+        })
+    }
+
+    pub fn transform(&self, text: &str) -> Result<String> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL)?;
+
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .with_context(|| {
+                format!(
+                    "Plugin {} failed to start (plugins get no imports)",
+                    self.name
+                )
+            })?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .with_context(|| format!("Plugin {} does not export memory", self.name))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let transform = instance.get_typed_func::<(i32, i32), i64>(&mut store, "transform")?;
+
+        let input = text.as_bytes();
+        let len = i32::try_from(input.len()).context("Text too long for plugin")?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, ptr as u32 as usize, input)?;
+
+        let packed = transform
+            .call(&mut store, (ptr, len))
+            .with_context(|| format!("Plugin {} failed", self.name))?;
+        let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+
+        // The output must lie in the plugin's memory, itself capped at
+        // MAX_MEMORY_BYTES, before anything is allocated for it
+        let in_memory = out_ptr
+            .checked_add(out_len)
+            .is_some_and(|end| end <= memory.data_size(&store));
+        if !in_memory || out_len > MAX_MEMORY_BYTES {
+            anyhow::bail!(
+                "Plugin {} returned an output outside its memory ({} bytes at {})",
+                self.name,
+                out_len,
+                out_ptr
+            );
+        }
+        let mut output = vec![0; out_len];
+        memory.read(&store, out_ptr, &mut output)?;
+        String::from_utf8(output)
+            .with_context(|| format!("Plugin {} returned invalid UTF-8", self.name))
+    }
+}
+
+/// Print the name of every plugin in the plugins directory.
+pub fn list() -> Result<()> {
+    let dir = plugins_dir();
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    let mut names: Vec<String> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "wasm"))
+        .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+        .collect();
+    names.sort();
+    for name in names {
+        println!("{name}");
+    }
+    Ok(())
+}
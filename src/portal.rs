@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use ashpd::desktop::PersistMode;
+use ashpd::desktop::Session;
+use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+use ashpd::desktop::notification::{Notification, NotificationProxy};
+use ashpd::desktop::remote_desktop::{DeviceType, KeyState, RemoteDesktop};
+use std::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use std::sync::{Arc, OnceLock};
+use tokio_stream::StreamExt;
+
+use crate::queue::PressQueue;
+
+/// Keysyms of the keys sent for editing (see `xkbcommon-keysyms.h`).
+pub const BACKSPACE: i32 = 0xff08;
+pub const CONTROL: i32 = 0xffe3;
+const TAB: i32 = 0xff09;
+const RETURN: i32 = 0xff0d;
+
+/// The RemoteDesktop session keystrokes are sent through, once --portal
+/// started it.
+static KEYBOARD: OnceLock<Keyboard> = OnceLock::new();
+
+struct Keyboard {
+    proxy: RemoteDesktop<'static>,
+    session: Session<'static, RemoteDesktop<'static>>,
+}
+
+/// Whether input and output go through the XDG desktop portals (--portal)
+/// instead of `/dev/input` and helper programs.
+pub fn enabled() -> bool {
+    KEYBOARD.get().is_some()
+}
+
+/// Start a RemoteDesktop session for typing. The desktop asks the user to
+/// allow it, once per start.
+pub async fn init() -> Result<()> {
+    let proxy = RemoteDesktop::new()
+        .await
+        .context("The RemoteDesktop portal is not available")?;
+    let session = proxy.create_session().await?;
+    proxy
+        .select_devices(
+            &session,
+            DeviceType::Keyboard.into(),
+            None,
+            PersistMode::DoNot,
+        )
+        .await?;
+    proxy
+        .start(&session, None)
+        .await?
+        .response()
+        .context("Keyboard access through the RemoteDesktop portal was refused")?;
+
+    let _ = KEYBOARD.set(Keyboard { proxy, session });
+    tracing::info!("Typing through the RemoteDesktop portal");
+    Ok(())
+}
+
+/// Type `text` as keysyms.
+pub async fn type_text(text: &str) -> Result<()> {
+    for c in text.chars() {
+        let Some(keysym) = keysym(c) else {
+            continue;
+        };
+        press(&[keysym]).await?;
+    }
+    Ok(())
+}
+
+/// Press `keysyms` together (modifiers first) and release them in reverse.
+pub async fn press(keysyms: &[i32]) -> Result<()> {
+    let keyboard = KEYBOARD
+        .get()
+        .context("The RemoteDesktop session isn't started")?;
+    for &keysym in keysyms {
+        keyboard
+            .proxy
+            .notify_keyboard_keysym(&keyboard.session, keysym, KeyState::Pressed)
+            .await
+            .context("Failed to send a key through the RemoteDesktop portal")?;
+    }
+    for &keysym in keysyms.iter().rev() {
+        keyboard
+            .proxy
+            .notify_keyboard_keysym(&keyboard.session, keysym, KeyState::Released)
+            .await
+            .context("Failed to send a key through the RemoteDesktop portal")?;
+    }
+    Ok(())
+}
+
+/// Keysym typing `c`: Latin-1 characters are their own keysyms, others are
+/// `0x01000000` plus their code point. Other control characters are
+/// dropped.
+fn keysym(c: char) -> Option<i32> {
+    match c {
+        '\n' => Some(RETURN),
+        '\t' => Some(TAB),
+        c if c.is_control() => None,
+        c if (c as u32) < 0x100 => Some(c as i32),
+        c => Some(0x0100_0000 + c as i32),
+    }
+}
+
+/// A new id for `notify`.
+pub fn notification_id() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(1);
+    NEXT.fetch_add(1, Relaxed)
+}
+
+/// Show a notification through the Notification portal, replacing the one
+/// shown earlier with `id`, like `notify-send --replace-id`.
+pub async fn notify(id: u32, summary: &str, body: &str) -> Result<()> {
+    let proxy = NotificationProxy::new()
+        .await
+        .context("The Notification portal is not available")?;
+    proxy
+        .add_notification(
+            &format!("improve-writing-{id}"),
+            Notification::new(summary).body(body),
+        )
+        .await
+        .context("Failed to show notification through the portal")?;
+    Ok(())
+}
+
+/// Register `shortcuts` (preferred trigger and description, indexed like
+/// the hotkey modes) with the GlobalShortcuts portal and push their
+/// activations into `queue`. The desktop may let the user change the
+/// triggers.
+pub fn spawn_shortcuts(shortcuts: Vec<(String, String)>, queue: Arc<PressQueue>) {
+    tokio::spawn(async move {
+        if let Err(e) = listen(&shortcuts, &queue).await {
+            tracing::error!("Global shortcuts stopped: {:#}", e);
+        }
+        queue.disconnect();
+    });
+}
+
+async fn listen(shortcuts: &[(String, String)], queue: &PressQueue) -> Result<()> {
+    let proxy = GlobalShortcuts::new()
+        .await
+        .context("The GlobalShortcuts portal is not available")?;
+    let session = proxy.create_session().await?;
+    let new: Vec<NewShortcut> = shortcuts
+        .iter()
+        .enumerate()
+        .map(|(i, (hotkey, description))| {
+            NewShortcut::new(format!("hotkey-{i}"), description.as_str())
+                .preferred_trigger(trigger(hotkey).as_str())
+        })
+        .collect();
+    let bound = proxy
+        .bind_shortcuts(&session, &new, None)
+        .await?
+        .response()
+        .context("Binding global shortcuts was refused")?;
+    for shortcut in bound.shortcuts() {
+        tracing::info!(
+            "Global shortcut {}: {}",
+            shortcut.description(),
+            shortcut.trigger_description()
+        );
+    }
+
+    let mut activated = proxy.receive_activated().await?;
+    while let Some(event) = activated.next().await {
+        match event
+            .shortcut_id()
+            .strip_prefix("hotkey-")
+            .and_then(|i| i.parse().ok())
+        {
+            Some(i) => queue.push(i),
+            None => tracing::debug!("Unknown shortcut {:?}", event.shortcut_id()),
+        }
+    }
+    Ok(())
+}
+
+/// A hotkey (`Ctrl+Shift+F8`) as a shortcut trigger in the XDG format
+/// (`CTRL+SHIFT+F8`).
+fn trigger(hotkey: &str) -> String {
+    hotkey
+        .split('+')
+        .map(|part| match part.to_lowercase().as_str() {
+            "ctrl" | "control" => "CTRL".to_string(),
+            "alt" => "ALT".to_string(),
+            "shift" => "SHIFT".to_string(),
+            "super" | "meta" | "cmd" | "logo" => "LOGO".to_string(),
+            _ => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
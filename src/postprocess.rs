@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use std::str::FromStr;
+
+/// A length limit: a number of characters (`280`), or a share of the input's
+/// length (`100%`: never longer than the input).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Chars(usize),
+    Percent(usize),
+}
+
+impl Limit {
+    /// The limit in characters for a result made from `input`.
+    pub fn resolve(self, input: &str) -> usize {
+        match self {
+            Limit::Chars(chars) => chars,
+            Limit::Percent(percent) => input.chars().count() * percent / 100,
+        }
+    }
+}
+
+impl FromStr for Limit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_suffix('%') {
+            Some(percent) => Ok(Limit::Percent(
+                percent
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid percentage {s:?}"))?,
+            )),
+            None => {
+                Ok(Limit::Chars(s.trim().parse().with_context(|| {
+                    format!("Invalid character limit {s:?}")
+                })?))
+            }
+        }
+    }
+}
+
+/// Shorten text to at most `max_chars` characters, cutting after the last
+/// complete sentence that fits, or else at a word boundary with an ellipsis.
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let cut: String = text.chars().take(max_chars).collect();
+    if let Some(end) = cut.rfind(['.', '!', '?']).filter(|end| *end > 0) {
+        return cut[..=end].to_string();
+    }
+
+    let head: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    let head = match head.rfind(char::is_whitespace) {
+        Some(space) => head[..space].trim_end(),
+        None => head.as_str(),
+    };
+    format!("{head}…")
+}
+
+/// Shorten text to at most `max_words` words, cutting after the last complete
+/// sentence that fits, or else after the last word with an ellipsis.
+pub fn truncate_words(text: &str, max_words: usize) -> String {
+    let mut words = 0;
+    let mut in_word = false;
+    let mut end = None;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            if words == max_words {
+                end = Some(i);
+                break;
+            }
+            words += 1;
+            in_word = true;
+        }
+    }
+
+    let Some(end) = end else {
+        return text.to_string();
+    };
+    let head = text[..end].trim_end();
+    match head.rfind(['.', '!', '?']).filter(|end| *end > 0) {
+        Some(end) => head[..=end].to_string(),
+        None => format!("{head}…"),
+    }
+}
+
+/// Openers a chat model puts before the answer itself, e.g. "Sure! Here's the
+/// improved text:".
+const OPENERS: &[&str] = &["sure", "certainly", "of course", "here is", "here's"];
+
+/// Closers a chat model puts after the answer, e.g. "Let me know if you'd
+/// like any changes!".
+const CLOSERS: &[&str] = &["i hope this helps", "let me know if", "feel free to"];
+
+/// Remove the usual tells of chat model output: an opening line announcing
+/// the answer, a closing offer of more help, em dashes, and typographic
+/// quotes and ellipses.
+pub fn deai(text: &str) -> String {
+    let starts_with_any = |line: &str, phrases: &[&str]| {
+        let line = line.trim().to_lowercase();
+        phrases.iter().any(|phrase| line.starts_with(phrase))
+    };
+
+    let mut lines: Vec<&str> = text.lines().collect();
+    if lines.len() > 1 && starts_with_any(lines[0], OPENERS) && lines[0].trim_end().ends_with(':') {
+        lines.remove(0);
+    }
+    if lines.len() > 1 && starts_with_any(lines[lines.len() - 1], CLOSERS) {
+        lines.pop();
+    }
+
+    lines
+        .join("\n")
+        .trim()
+        .replace(" — ", ", ")
+        .replace('—', ", ")
+        .replace(['‘', '’'], "'")
+        .replace(['“', '”'], "\"")
+        .replace('…', "...")
+}
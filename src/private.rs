@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use std::collections::hash_map::RandomState;
+use std::fs::OpenOptions;
+use std::hash::BuildHasher;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Run `f` with the process umask set to `mask`, so the files and sockets it
+/// creates never exist with wider permissions, not even until a chmod. The
+/// umask is process-wide: keep `f` short.
+pub fn with_umask<T>(mask: libc::mode_t, f: impl FnOnce() -> T) -> T {
+    let previous = unsafe { libc::umask(mask) };
+    let result = f();
+    unsafe { libc::umask(previous) };
+    result
+}
+
+/// A new file in the temp directory that only the user can read, under an
+/// unpredictable name. It is created with `create_new`, so an existing file
+/// or a planted symlink is never written through. Removed when dropped.
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Create one holding `contents`. `suffix` (e.g. `.md`) tells editors
+    /// and other programs the format.
+    pub fn create(suffix: &str, contents: &[u8]) -> Result<Self> {
+        let dir = std::env::temp_dir();
+        for _ in 0..16 {
+            let name = RandomState::new().hash_one(SystemTime::now());
+            let path = dir.join(format!("improve-writing-{name:016x}{suffix}"));
+            let mut file = match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&path)
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to create {}", path.display()));
+                }
+            };
+            let temp = Self { path };
+            file.write_all(contents)
+                .with_context(|| format!("Failed to write {}", temp.path.display()))?;
+            return Ok(temp);
+        }
+        anyhow::bail!("Failed to create a temp file in {}", dir.display())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leave the file in place, for a command that removes it once done.
+    pub fn keep(mut self) -> PathBuf {
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
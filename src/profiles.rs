@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::filter::{self, OutputFilter};
+use crate::{config, prompts};
+
+/// Name of the profile made of the command line, environment, and top-level
+/// config file options.
+pub const DEFAULT: &str = "default";
+
+/// A named bundle of backend, model, prompts, reply tone, and redaction
+/// settings from `[profiles.NAME]` in the config file, switched as a whole.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub ollama_host: String,
+    pub ollama_port: u16,
+    pub model: String,
+    /// Resolved prompts; `None` keeps the built-in ones
+    pub improve_prompt: Option<String>,
+    pub command_prompt: Option<String>,
+    pub reply_tone: String,
+    pub output_filter: OutputFilter,
+    pub filter_words: Vec<String>,
+    /// Only allow an Ollama host on this machine or the local network
+    pub local_only: bool,
+    /// Rules sending some requests to another model or host, first match
+    /// first
+    pub routes: Vec<Route>,
+}
+
+/// A routing rule: requests that meet all of its conditions go to its
+/// model and host instead of the profile's.
+#[derive(Clone, Debug, Default)]
+pub struct Route {
+    /// Focused application, matched case-insensitively as a substring
+    pub app: Option<String>,
+    /// Action name, as passed to script hooks (`improve`, `command`, ...)
+    pub action: Option<String>,
+    pub min_chars: Option<usize>,
+    pub max_chars: Option<usize>,
+    /// Unset parts keep the profile's
+    pub ollama_host: Option<String>,
+    pub ollama_port: Option<u16>,
+    pub model: Option<String>,
+}
+
+impl Route {
+    fn matches(&self, app: Option<&str>, action: &str, chars: usize) -> bool {
+        let app_matches = match (&self.app, app) {
+            (None, _) => true,
+            (Some(wanted), Some(app)) => app.to_lowercase().contains(&wanted.to_lowercase()),
+            (Some(_), None) => false,
+        };
+        app_matches
+            && self.action.as_deref().is_none_or(|wanted| wanted == action)
+            && self.min_chars.is_none_or(|min| chars >= min)
+            && self.max_chars.is_none_or(|max| chars <= max)
+    }
+
+    /// The rule as written, for logs.
+    pub fn describe(&self) -> String {
+        let mut conditions = Vec::new();
+        if let Some(app) = &self.app {
+            conditions.push(format!("app {app}"));
+        }
+        if let Some(action) = &self.action {
+            conditions.push(format!("action {action}"));
+        }
+        if let Some(min) = self.min_chars {
+            conditions.push(format!("at least {min} chars"));
+        }
+        if let Some(max) = self.max_chars {
+            conditions.push(format!("at most {max} chars"));
+        }
+        if conditions.is_empty() {
+            conditions.push("any request".to_string());
+        }
+        conditions.join(", ")
+    }
+}
+
+impl Profile {
+    /// Fail if the profile would send text somewhere it mustn't.
+    pub fn check(&self) -> Result<()> {
+        if !self.local_only {
+            return Ok(());
+        }
+        let hosts = std::iter::once(&self.ollama_host).chain(
+            self.routes
+                .iter()
+                .filter_map(|route| route.ollama_host.as_ref()),
+        );
+        for host in hosts {
+            if !is_local(host) {
+                anyhow::bail!(
+                    "Profile {:?} is local-only, but its Ollama host {} is not on this machine or the local network",
+                    self.name,
+                    host
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The first routing rule a request matches, if any.
+    pub fn route(&self, app: Option<&str>, action: &str, chars: usize) -> Option<&Route> {
+        self.routes
+            .iter()
+            .find(|route| route.matches(app, action, chars))
+    }
+}
+
+/// The default profile followed by those in the config file, by name. Each
+/// starts from `base` with its own options on top; a `[profiles.default]`
+/// table adjusts the default profile itself.
+pub fn load(base: Profile) -> Result<Vec<Profile>> {
+    let mut profiles = vec![base.clone()];
+    for (name, table) in config::profiles()? {
+        let mut profile = Profile {
+            name: name.clone(),
+            ..base.clone()
+        };
+        for (key, value) in table {
+            let long = key.replace('_', "-");
+            let at = config::location(&format!("profiles.{name}-{long}").replace('_', "-"))
+                .unwrap_or_else(|| config::path().display().to_string());
+            let text = || {
+                value.as_str().map(str::to_string).with_context(|| {
+                    format!("{key:?} in profile {name:?} must be a string (at {at})")
+                })
+            };
+            match long.as_str() {
+                "ollama-host" => profile.ollama_host = text()?,
+                "ollama-port" => {
+                    profile.ollama_port = value
+                        .as_integer()
+                        .and_then(|port| u16::try_from(port).ok())
+                        .with_context(|| format!("Invalid port in profile {name:?} at {at}"))?
+                }
+                "ollama-model" => profile.model = text()?,
+                "prompt" => profile.improve_prompt = Some(prompts::resolve(&text()?)?),
+                "cmd-prompt" => profile.command_prompt = Some(prompts::resolve(&text()?)?),
+                "reply-tone" => profile.reply_tone = text()?,
+                "output-filter" => {
+                    profile.output_filter = text()?
+                        .parse()
+                        .with_context(|| format!("In profile {name:?} at {at}"))?
+                }
+                "filter-words" => profile.filter_words = filter::load_words(Path::new(&text()?))?,
+                "routes" => profile.routes = parse_routes(&name, &value, &at)?,
+                "local-only" => {
+                    profile.local_only = value.as_bool().with_context(|| {
+                        format!(
+                            "\"local-only\" in profile {name:?} must be true or false (at {at})"
+                        )
+                    })?
+                }
+                _ => anyhow::bail!(
+                    "Unknown option {key:?} in profile {name:?} at {at} (expected ollama-host, ollama-port, ollama-model, prompt, cmd-prompt, reply-tone, output-filter, filter-words, local-only or routes)"
+                ),
+            }
+        }
+        profile.check()?;
+
+        if name == DEFAULT {
+            profiles[0] = profile;
+        } else {
+            profiles.push(profile);
+        }
+    }
+    Ok(profiles)
+}
+
+/// Parse a profile's `[[profiles.NAME.routes]]` tables.
+fn parse_routes(profile: &str, value: &toml::Value, at: &str) -> Result<Vec<Route>> {
+    let invalid = || format!("Invalid routes in profile {profile:?} at {at}");
+    let tables = value.as_array().with_context(invalid)?;
+    let mut routes = Vec::new();
+    for table in tables {
+        let table = table.as_table().with_context(invalid)?;
+        let mut route = Route::default();
+        for (key, value) in table {
+            let text = || value.as_str().map(str::to_string).with_context(invalid);
+            let number = || {
+                value
+                    .as_integer()
+                    .and_then(|n| usize::try_from(n).ok())
+                    .with_context(invalid)
+            };
+            match key.replace('_', "-").as_str() {
+                "app" => route.app = Some(text()?),
+                "action" => route.action = Some(text()?),
+                "min-chars" => route.min_chars = Some(number()?),
+                "max-chars" => route.max_chars = Some(number()?),
+                "ollama-host" => route.ollama_host = Some(text()?),
+                "ollama-port" => {
+                    route.ollama_port = Some(u16::try_from(number()?).with_context(invalid)?)
+                }
+                "ollama-model" => route.model = Some(text()?),
+                _ => anyhow::bail!(
+                    "Unknown route option {key:?} in profile {profile:?} at {at} (expected app, action, min-chars, max-chars, ollama-host, ollama-port or ollama-model)"
+                ),
+            }
+        }
+        routes.push(route);
+    }
+    Ok(routes)
+}
+
+/// Whether `host` (an Ollama URL) is on this machine or the local network:
+/// a loopback, private, or link-local address, `localhost`, or an mDNS
+/// `.local` name. Other names aren't resolved, so they count as remote.
+pub fn is_local(host: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(host) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        Ok(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            // Unique local (fc00::/7) and link-local (fe80::/10)
+            ip.is_loopback() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+        }
+        Err(_) => host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local"),
+    }
+}
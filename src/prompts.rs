@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::i18n;
+
+/// Prompt files inside a pack use one of these extensions.
+const EXTENSIONS: [&str; 2] = ["txt", "md"];
+
+/// Per-user configuration directory (`$XDG_CONFIG_HOME/improve-writing`).
+pub fn config_dir() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_default()
+        .join("improve-writing")
+}
+
+/// Directory holding one subdirectory (namespace) per prompt pack.
+pub fn store_dir() -> PathBuf {
+    config_dir().join("prompts")
+}
+
+/// Load a prompt by its `namespace/name` reference.
+pub fn resolve(reference: &str) -> Result<String> {
+    Ok(read(reference)?.trim().to_string())
+}
+
+/// The contents of a `namespace/name` prompt file, as written.
+pub fn read(reference: &str) -> Result<String> {
+    let path = find(reference)?.with_context(|| {
+        format!("Prompt {reference:?} not found (see `improve-writing prompts list`)")
+    })?;
+    std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Save a `namespace/name` prompt to its file, or to a new `<name>.txt` in
+/// the pack.
+pub fn write(reference: &str, contents: &str) -> Result<()> {
+    let path = match find(reference)? {
+        Some(path) => path,
+        None => {
+            let (namespace, name) = split(reference)?;
+            let pack = store_dir().join(namespace);
+            std::fs::create_dir_all(&pack)
+                .with_context(|| format!("Failed to create {}", pack.display()))?;
+            pack.join(format!("{name}.txt"))
+        }
+    };
+    std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The file holding a `namespace/name` prompt, if there is one.
+fn find(reference: &str) -> Result<Option<PathBuf>> {
+    let (namespace, name) = split(reference)?;
+    let pack = store_dir().join(namespace);
+    Ok(EXTENSIONS
+        .iter()
+        .map(|ext| pack.join(format!("{name}.{ext}")))
+        .find(|path| path.is_file()))
+}
+
+fn split(reference: &str) -> Result<(&str, &str)> {
+    let (namespace, name) = reference
+        .split_once('/')
+        .with_context(|| format!("Prompt reference must be namespace/name, got {reference:?}"))?;
+    check_component("namespace", namespace)?;
+    check_component("prompt name", name)?;
+    Ok((namespace, name))
+}
+
+/// Add a pack from a git URL (cloned) or a local directory (symlinked).
+pub async fn add(source: &str, namespace: Option<&str>) -> Result<()> {
+    let namespace = match namespace {
+        Some(ns) => ns.to_string(),
+        None => default_namespace(source)?,
+    };
+    check_component("namespace", &namespace)?;
+    let target = store_dir().join(&namespace);
+    if target.exists() {
+        anyhow::bail!("Prompt pack {namespace:?} already exists");
+    }
+    std::fs::create_dir_all(store_dir())?;
+
+    let local = Path::new(source);
+    if local.is_dir() {
+        let local = local.canonicalize()?;
+        std::os::unix::fs::symlink(&local, &target)?;
+        println!("Linked {} as {}", local.display(), namespace);
+    } else {
+        let status = Command::new("git")
+            .args(["clone", "--depth", "1", source])
+            .arg(&target)
+            .status()
+            .await
+            .with_context(|| format!("Failed to run git ({})", i18n::missing_tool("git")))?;
+        if !status.success() {
+            anyhow::bail!("git clone failed: {:?}", status);
+        }
+        println!("Cloned {} as {}", source, namespace);
+    }
+
+    Ok(())
+}
+
+/// Pull the latest version of one git-backed pack, or all of them.
+pub async fn update(namespace: Option<&str>) -> Result<()> {
+    let namespaces = match namespace {
+        Some(ns) => vec![ns.to_string()],
+        None => packs()?,
+    };
+
+    for ns in namespaces {
+        check_component("namespace", &ns)?;
+        let dir = store_dir().join(&ns);
+        if !dir.join(".git").exists() {
+            println!("{ns}: not a git pack, skipping");
+            continue;
+        }
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&dir)
+            .args(["pull", "--ff-only", "--quiet"])
+            .status()
+            .await
+            .with_context(|| format!("Failed to run git ({})", i18n::missing_tool("git")))?;
+        if status.success() {
+            println!("{ns}: updated");
+        } else {
+            tracing::warn!("{}: git pull failed: {:?}", ns, status);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print every available `namespace/name` prompt.
+pub fn list() -> Result<()> {
+    for name in names()? {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+/// Every available prompt, as `namespace/name`.
+pub fn names() -> Result<Vec<String>> {
+    let mut all = Vec::new();
+    for ns in packs()? {
+        let mut names: Vec<String> = std::fs::read_dir(store_dir().join(&ns))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| EXTENSIONS.contains(&e))
+            })
+            .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+            .collect();
+        names.sort();
+        all.extend(names.into_iter().map(|name| format!("{ns}/{name}")));
+    }
+    Ok(all)
+}
+
+/// Remove a pack (deleting a clone, or just the link for a local pack).
+pub fn remove(namespace: &str) -> Result<()> {
+    check_component("namespace", namespace)?;
+    let target = store_dir().join(namespace);
+    let meta = std::fs::symlink_metadata(&target)
+        .with_context(|| format!("Prompt pack {namespace:?} not found"))?;
+    if meta.file_type().is_symlink() {
+        std::fs::remove_file(&target)?;
+    } else {
+        std::fs::remove_dir_all(&target)?;
+    }
+    println!("Removed {namespace}");
+    Ok(())
+}
+
+fn packs() -> Result<Vec<String>> {
+    let dir = store_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut packs: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    packs.sort();
+    Ok(packs)
+}
+
+/// Check that a namespace or prompt name is a single path component, so
+/// joining it to the store can't reach outside it.
+fn check_component(kind: &str, component: &str) -> Result<()> {
+    if component.is_empty()
+        || component == "."
+        || component.contains(['/', '\\'])
+        || component.contains("..")
+    {
+        anyhow::bail!("Invalid {kind} {component:?}: must be a plain name without /, \\ or ..");
+    }
+    Ok(())
+}
+
+/// Derive a namespace from the last path segment, minus any `.git` suffix.
+fn default_namespace(source: &str) -> Result<String> {
+    source
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .map(|last| last.trim_end_matches(".git").to_string())
+        .filter(|ns| !ns.is_empty())
+        .with_context(|| format!("Cannot derive a namespace from {source:?}, pass --name"))
+}
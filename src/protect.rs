@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Added to the system prompt when the text contains placeholders.
+pub const PLACEHOLDER_RULE: &str = "The text contains placeholders like [[KEEP_0]]. \
+    Copy each one into your output unchanged, in the right place.";
+
+/// Opening and closing marks of inline quotations.
+const QUOTE_PAIRS: [(char, char); 4] = [('"', '"'), ('“', '”'), ('«', '»'), ('„', '“')];
+
+/// Text with spans the model must not touch swapped out for placeholders.
+/// Stages run in order; `restore` undoes them in reverse, so spans that
+/// contain earlier placeholders come back intact.
+pub struct Protected {
+    pub text: String,
+    spans: Vec<String>,
+}
+
+impl Protected {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn has_placeholders(&self) -> bool {
+        !self.spans.is_empty()
+    }
+
+    /// Replace fenced (```) and inline (`) code spans with placeholders.
+    pub fn protect_code(&mut self) {
+        let source = std::mem::take(&mut self.text);
+        let mut rest = source.as_str();
+
+        while let Some(start) = rest.find('`') {
+            let marker = if rest[start..].starts_with("```") {
+                "```"
+            } else {
+                "`"
+            };
+            let body = &rest[start + marker.len()..];
+            let end = if marker == "```" {
+                body.find(marker)
+            } else {
+                // Inline code doesn't span lines
+                body.find(['`', '\n'])
+                    .filter(|i| body[*i..].starts_with('`'))
+            };
+
+            match end {
+                Some(end) if end > 0 => {
+                    let span_end = start + marker.len() + end + marker.len();
+                    self.text.push_str(&rest[..start]);
+                    self.push_span(&rest[start..span_end]);
+                    rest = &rest[span_end..];
+                }
+                _ => {
+                    self.text.push_str(&rest[..start + marker.len()]);
+                    rest = &rest[start + marker.len()..];
+                }
+            }
+        }
+
+        self.text.push_str(rest);
+    }
+
+    /// Replace quoted passages with placeholders: email-style `>` quote
+    /// blocks, and inline "…", “…”, «…» and „…“ quotations within a
+    /// paragraph.
+    pub fn protect_quotes(&mut self) {
+        // Quote blocks: runs of lines starting with `>`
+        let source = std::mem::take(&mut self.text);
+        let mut block = String::new();
+        for line in source.split_inclusive('\n') {
+            if line.trim_start().starts_with('>') {
+                block.push_str(line);
+                continue;
+            }
+            self.flush_quote_block(&mut block);
+            self.text.push_str(line);
+        }
+        self.flush_quote_block(&mut block);
+
+        // Inline quotations
+        let source = std::mem::take(&mut self.text);
+        let mut rest = source.as_str();
+        while let Some((start, open)) = rest
+            .char_indices()
+            .find(|(_, c)| QUOTE_PAIRS.iter().any(|(o, _)| o == c))
+        {
+            let close = QUOTE_PAIRS
+                .iter()
+                .find(|(o, _)| *o == open)
+                .map(|(_, c)| *c)
+                .unwrap_or(open);
+            let body_start = start + open.len_utf8();
+            let body = &rest[body_start..];
+            let end = body
+                .find(close)
+                .filter(|end| *end > 0 && !body[..*end].contains("\n\n"));
+
+            match end {
+                Some(end) => {
+                    let span_end = body_start + end + close.len_utf8();
+                    self.text.push_str(&rest[..start]);
+                    self.push_span(&rest[start..span_end]);
+                    rest = &rest[span_end..];
+                }
+                None => {
+                    self.text.push_str(&rest[..body_start]);
+                    rest = body;
+                }
+            }
+        }
+        self.text.push_str(rest);
+    }
+
+    /// Placeholders missing from the model's output.
+    pub fn missing(&self, output: &str) -> Vec<String> {
+        (0..self.spans.len())
+            .map(placeholder)
+            .filter(|p| !output.contains(p))
+            .collect()
+    }
+
+    /// Put the protected spans back into the model's output. Fails when the
+    /// model dropped a placeholder, since the span it stood for would be
+    /// silently lost.
+    pub fn restore(&self, output: &str) -> Result<String> {
+        let mut out = output.to_string();
+
+        for (i, span) in self.spans.iter().enumerate().rev() {
+            let placeholder = placeholder(i);
+            if !out.contains(&placeholder) {
+                tracing::warn!("Model dropped protected span {:?}", span);
+                anyhow::bail!("The response dropped protected span {placeholder}");
+            }
+            out = out.replacen(&placeholder, span, 1);
+        }
+
+        Ok(out)
+    }
+
+    /// Protect a collected quote block, keeping its final newline outside
+    /// the placeholder so the surrounding layout survives.
+    fn flush_quote_block(&mut self, block: &mut String) {
+        if block.is_empty() {
+            return;
+        }
+        let quote = std::mem::take(block);
+        let trimmed = quote.trim_end_matches('\n');
+        self.push_span(trimmed);
+        self.text.push_str(&quote[trimmed.len()..]);
+    }
+
+    fn push_span(&mut self, span: &str) {
+        self.text.push_str(&placeholder(self.spans.len()));
+        self.spans.push(span.to_string());
+    }
+}
+
+fn placeholder(i: usize) -> String {
+    format!("[[KEEP_{i}]]")
+}
+
+/// Read a protected-terms file: one name, product term, or phrase per line;
+/// blank lines and lines starting with `#` are skipped.
+pub fn load_terms(path: &Path) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read protected terms {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Protected terms that occur in `input` but are no longer in `output`,
+/// matched case-sensitively as whole words.
+pub fn missing_terms<'a>(terms: &'a [String], input: &str, output: &str) -> Vec<&'a str> {
+    terms
+        .iter()
+        .map(String::as_str)
+        .filter(|term| contains_word(input, term) && !contains_word(output, term))
+        .collect()
+}
+
+fn contains_word(text: &str, term: &str) -> bool {
+    text.match_indices(term).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + term.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
@@ -0,0 +1,169 @@
+use hotkey_listener::{HotkeyEvent, HotkeyListenerHandle};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering::Acquire};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::{i18n, notify};
+
+/// Hotkey presses waiting for the event loop. Presses are read on their own
+/// thread, so those made while a request is running can be coalesced or
+/// dropped (with a notification) instead of piling up.
+pub struct PressQueue {
+    state: Mutex<State>,
+    ready: Condvar,
+    capacity: usize,
+    runtime: tokio::runtime::Handle,
+}
+
+/// How often a suspended reader checks whether it should stop.
+const SUSPENDED_CHECK: Duration = Duration::from_secs(1);
+
+struct State {
+    /// Indices of the pressed hotkeys, oldest first
+    pending: VecDeque<usize>,
+    /// The event loop took a press and hasn't asked for the next one yet
+    busy: bool,
+    disconnected: bool,
+    /// Presses are dropped, and the listener isn't read at all, while the
+    /// session is away (--suspend-when-away)
+    suspended: bool,
+}
+
+impl PressQueue {
+    /// Read presses from `handle` until `running` is cleared, keeping at
+    /// most `capacity` waiting while a request is in flight.
+    pub fn spawn(
+        handle: HotkeyListenerHandle,
+        capacity: usize,
+        running: Arc<AtomicBool>,
+    ) -> Arc<Self> {
+        let queue = Self::new(capacity);
+        let reader = queue.clone();
+        std::thread::spawn(move || {
+            while running.load(Acquire) {
+                if reader.wait_while_suspended(&running) {
+                    // Presses made while away were left in the listener
+                    let mut dropped = 0;
+                    while handle.recv_timeout(Duration::ZERO).is_ok() {
+                        dropped += 1;
+                    }
+                    tracing::debug!("Dropped {} events from while suspended", dropped);
+                }
+                match handle.recv_timeout(Duration::from_millis(100)) {
+                    Ok(HotkeyEvent::Pressed(i)) => reader.push(i),
+                    Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            reader.disconnect();
+        });
+
+        queue
+    }
+
+    /// A queue fed by `push` rather than a keyboard listener, e.g. from the
+    /// GlobalShortcuts portal.
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State {
+                pending: VecDeque::new(),
+                busy: false,
+                disconnected: false,
+                suspended: false,
+            }),
+            ready: Condvar::new(),
+            capacity,
+            runtime: tokio::runtime::Handle::current(),
+        })
+    }
+
+    /// Drop presses until resumed, e.g. while the screen is locked. Presses
+    /// already waiting are dropped too.
+    pub fn set_suspended(&self, suspended: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.suspended = suspended;
+        if suspended {
+            state.pending.clear();
+        }
+        self.ready.notify_all();
+    }
+
+    /// Block the reader while suspended, until resumed or `running` is
+    /// cleared. Returns whether it was suspended.
+    fn wait_while_suspended(&self, running: &AtomicBool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let mut waited = false;
+        while state.suspended && running.load(Acquire) {
+            waited = true;
+            state = self.ready.wait_timeout(state, SUSPENDED_CHECK).unwrap().0;
+        }
+        waited
+    }
+
+    /// No more presses will come; the event loop sees a disconnect once the
+    /// pending ones are taken.
+    pub fn disconnect(&self) {
+        self.state.lock().unwrap().disconnected = true;
+        self.ready.notify_all();
+    }
+
+    pub fn push(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.suspended {
+            tracing::debug!("Suspended - dropping a hotkey press");
+            return;
+        }
+        if !state.busy && state.pending.is_empty() {
+            state.pending.push_back(index);
+            self.ready.notify_all();
+            return;
+        }
+
+        let message = if state.pending.contains(&index) {
+            tracing::info!("Hotkey already queued, ignoring the repeated press");
+            "busy-already-queued"
+        } else if state.pending.len() >= self.capacity {
+            tracing::warn!(
+                "Hotkey queue is full ({} waiting), dropping the press",
+                self.capacity
+            );
+            "busy-queue-full"
+        } else {
+            state.pending.push_back(index);
+            self.ready.notify_all();
+            tracing::info!("Busy, queued hotkey ({} waiting)", state.pending.len());
+            "busy-queued"
+        };
+        drop(state);
+
+        self.runtime.spawn(async move {
+            if let Err(e) = notify::notify(&i18n::t("busy"), &i18n::t(message)).await {
+                tracing::debug!("Failed to show busy notification: {}", e);
+            }
+        });
+    }
+
+    /// Wait up to `timeout` for the next press, returning its hotkey index.
+    /// Asking for the next press marks the event loop as idle again.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<usize, RecvTimeoutError> {
+        let mut state = self.state.lock().unwrap();
+        state.busy = false;
+        let (mut state, _) = self
+            .ready
+            .wait_timeout_while(state, timeout, |state| {
+                state.pending.is_empty() && !state.disconnected
+            })
+            .unwrap();
+
+        match state.pending.pop_front() {
+            Some(index) => {
+                state.busy = true;
+                Ok(index)
+            }
+            None if state.disconnected => Err(RecvTimeoutError::Disconnected),
+            None => Err(RecvTimeoutError::Timeout),
+        }
+    }
+}
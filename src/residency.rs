@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use fluent_bundle::FluentArgs;
+use ollama_rs::{
+    Ollama, generation::chat::request::ChatMessageRequest, generation::parameters::KeepAlive,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::i18n;
+use crate::notify;
+use crate::ollama;
+
+/// Where a model is running on the Ollama host.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Placement {
+    /// Not loaded; the next request waits for a load
+    Evicted,
+    /// Loaded into system memory only
+    Cpu,
+    /// Split between GPU and system memory, with this percentage on the GPU
+    Partial(u64),
+    Gpu,
+}
+
+/// `/api/ps` response
+#[derive(Deserialize)]
+struct Ps {
+    #[serde(default)]
+    models: Vec<Running>,
+}
+
+#[derive(Deserialize)]
+struct Running {
+    name: String,
+    size: u64,
+    size_vram: u64,
+}
+
+/// `/api/show` response
+#[derive(Deserialize)]
+struct Show {
+    #[serde(default)]
+    details: Details,
+}
+
+#[derive(Default, Deserialize)]
+struct Details {
+    #[serde(default)]
+    parameter_size: String,
+    #[serde(default)]
+    quantization_level: String,
+}
+
+/// Watches where models run on the Ollama host, warning when one was
+/// evicted or fell back to the CPU, and optionally loading evicted ones
+/// again before the next request has to.
+pub struct Monitor {
+    pub client: reqwest::Client,
+    /// Ollama's base URL, e.g. `http://localhost:11434`
+    pub base: String,
+    pub ollama: Ollama,
+    pub keep_alive: KeepAlive,
+    /// Limit for each request, loads included
+    pub timeout: Duration,
+    pub reload: bool,
+}
+
+impl Monitor {
+    /// Check `models` every `interval`, reporting each change of placement.
+    pub async fn run(self, models: Vec<String>, interval: Duration) {
+        let mut last: HashMap<String, Placement> = HashMap::new();
+        loop {
+            match self.placements(&models).await {
+                Ok(placements) => {
+                    for (model, placement) in models.iter().zip(placements) {
+                        if last.insert(model.clone(), placement) != Some(placement) {
+                            self.report(model, placement).await;
+                        }
+                        if placement == Placement::Evicted && self.reload {
+                            self.load(model).await;
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to check models on Ollama: {:#}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Where each of `models` runs, from `/api/ps`.
+    async fn placements(&self, models: &[String]) -> Result<Vec<Placement>> {
+        let ps: Ps = self
+            .client
+            .get(format!("{}/api/ps", self.base))
+            .timeout(self.timeout)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("GET /api/ps failed")?
+            .json()
+            .await
+            .context("Invalid /api/ps response")?;
+
+        let placements = models
+            .iter()
+            .map(|model| {
+                let Some(running) = ps.models.iter().find(|r| same_model(&r.name, model)) else {
+                    return Placement::Evicted;
+                };
+                match running.size_vram {
+                    0 => Placement::Cpu,
+                    vram if vram < running.size => {
+                        Placement::Partial(vram * 100 / running.size.max(1))
+                    }
+                    _ => Placement::Gpu,
+                }
+            })
+            .collect();
+        Ok(placements)
+    }
+
+    /// Size and quantization of an installed model, from `/api/show`.
+    async fn details(&self, model: &str) -> Result<String> {
+        let show: Show = self
+            .client
+            .post(format!("{}/api/show", self.base))
+            .json(&json!({ "model": model }))
+            .timeout(self.timeout)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("{model} is not installed on the Ollama host"))?
+            .json()
+            .await
+            .context("Invalid /api/show response")?;
+        let Details {
+            parameter_size,
+            quantization_level,
+        } = show.details;
+        Ok(format!("{parameter_size} {quantization_level}")
+            .trim()
+            .to_string())
+    }
+
+    async fn report(&self, model: &str, placement: Placement) {
+        let notification = match placement {
+            Placement::Gpu => {
+                tracing::info!("{} is loaded on the GPU", model);
+                None
+            }
+            Placement::Evicted => {
+                match self.details(model).await {
+                    Ok(details) => tracing::warn!("{} ({}) is not loaded", model, details),
+                    Err(e) => tracing::warn!("{:#}", e),
+                }
+                // Reloading hides the eviction, so it isn't worth a notification
+                (!self.reload).then_some(("model-evicted", 0))
+            }
+            Placement::Cpu => {
+                tracing::warn!("{} is running on the CPU; responses will be slow", model);
+                Some(("model-on-cpu", 0))
+            }
+            Placement::Partial(gpu) => {
+                tracing::warn!(
+                    "{} is only {}% on the GPU; responses will be slow",
+                    model,
+                    gpu
+                );
+                Some(("model-on-cpu", gpu))
+            }
+        };
+
+        if let Some((summary, gpu)) = notification {
+            let args =
+                FluentArgs::from_iter([("model", model.to_string()), ("gpu", gpu.to_string())]);
+            let body = i18n::t_args(&format!("{summary}-detail"), Some(&args));
+            if let Err(e) = notify::notify(&i18n::t(summary), &body).await {
+                tracing::warn!("Failed to show model warning: {}", e);
+            }
+        }
+    }
+
+    /// Load `model` with an empty request, as the keep-warm ping does.
+    async fn load(&self, model: &str) {
+        if let Err(e) = self.details(model).await {
+            tracing::debug!("Not reloading: {:#}", e);
+            return;
+        }
+        tracing::info!("Reloading {}", model);
+        let request = ChatMessageRequest::new(model.to_string(), Vec::new())
+            .keep_alive(self.keep_alive.clone());
+        let start = Instant::now();
+        match ollama::send_within(&self.ollama, request, self.timeout).await {
+            Ok(_) => tracing::info!("Reloaded {} in {:?}", model, start.elapsed()),
+            Err(e) => tracing::warn!("Failed to reload {}: {}", model, e),
+        }
+    }
+}
+
+/// Whether two model names refer to the same model; an untagged name means
+/// `:latest`.
+fn same_model(a: &str, b: &str) -> bool {
+    let tagged = |name: &str| {
+        if name.contains(':') {
+            name.to_string()
+        } else {
+            format!("{name}:latest")
+        }
+    };
+    tagged(a) == tagged(b)
+}
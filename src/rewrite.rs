@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::prompts;
+
+/// Directory holding `*.toml` rule sets for `regex:NAME` pipeline stages.
+pub fn rewrites_dir() -> PathBuf {
+    prompts::config_dir().join("regex")
+}
+
+#[derive(Deserialize)]
+struct RuleSet {
+    #[serde(default)]
+    rule: Vec<Rule>,
+}
+
+#[derive(Deserialize)]
+struct Rule {
+    pattern: String,
+    #[serde(default)]
+    replace: String,
+}
+
+/// Regex replacements applied in order, e.g. `regex/cleanup.toml`:
+///
+/// ```toml
+/// [[rule]]
+/// pattern = ' +([,.!?])'
+/// replace = '$1'
+/// ```
+///
+/// `replace` may refer to groups as `$1` or `${name}`, and defaults to
+/// removing the match.
+#[derive(Clone, Debug)]
+pub struct Rewrites {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Rewrites {
+    /// Load and compile `<regex dir>/<name>.toml`.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = rewrites_dir().join(format!("{name}.toml"));
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read rules {}", path.display()))?;
+        let set: RuleSet = toml::from_str(&contents)
+            .with_context(|| format!("Invalid rules in {}", path.display()))?;
+        if set.rule.is_empty() {
+            anyhow::bail!("{} has no [[rule]] entries", path.display());
+        }
+
+        let rules = set
+            .rule
+            .into_iter()
+            .map(|rule| {
+                let regex = Regex::new(&rule.pattern).with_context(|| {
+                    format!("Invalid pattern {:?} in {}", rule.pattern, path.display())
+                })?;
+                Ok((regex, rule.replace))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.rules
+            .iter()
+            .fold(text.to_string(), |text, (regex, replace)| {
+                regex.replace_all(&text, replace.as_str()).into_owned()
+            })
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{Context, Result};
+use landlock::{
+    ABI, Access, AccessFs, AccessNet, NetPort, Ruleset, RulesetAttr, RulesetCreatedAttr,
+    RulesetError, RulesetStatus, path_beneath_rules,
+};
+use std::ffi::{CStr, CString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Landlock ABI the rules are written for (Linux 6.7: TCP port rules).
+/// Older kernels enforce what they support.
+const ABI_VERSION: ABI = ABI::V4;
+
+/// System directories programs and libraries are read and run from.
+const SYSTEM: [&str; 9] = [
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib64",
+    "/etc",
+    "/opt",
+    "/nix",
+    "/run/current-system",
+];
+
+/// Directories that are read but never written: keyboards, process and
+/// device information.
+const READ_ONLY: [&str; 3] = ["/dev", "/proc", "/sys"];
+
+/// What the process (and the helpers it runs) may touch once restricted.
+pub struct Rules {
+    /// Read and run only
+    pub read: Vec<PathBuf>,
+    /// Read and write: sockets, the history database, temporary files
+    pub write: Vec<PathBuf>,
+    /// TCP ports connections may go to (Ollama, the OTLP collector), on any
+    /// host: Landlock filters connections by port only
+    pub ports: Vec<u16>,
+    /// TCP ports a server may listen on (the `ui` page)
+    pub bind: Vec<u16>,
+}
+
+impl Rules {
+    /// The defaults for the current user: system directories and `$PATH`,
+    /// the configuration directory, and the runtime, data and temporary
+    /// directories for writing.
+    pub fn new(ports: Vec<u16>) -> Self {
+        let env_dir = |var: &str| std::env::var_os(var).map(PathBuf::from);
+        let mut read: Vec<PathBuf> = SYSTEM.iter().chain(&READ_ONLY).map(PathBuf::from).collect();
+        if let Some(path) = std::env::var_os("PATH") {
+            read.extend(std::env::split_paths(&path));
+        }
+        read.push(crate::prompts::config_dir());
+
+        let mut write = vec![PathBuf::from("/tmp"), PathBuf::from("/dev/null")];
+        write.extend(env_dir("XDG_RUNTIME_DIR"));
+        write.extend(env_dir("TMPDIR"));
+        if let Some(dir) = crate::history::default_path().parent() {
+            write.push(dir.to_path_buf());
+        }
+
+        Self {
+            read,
+            write,
+            ports,
+            bind: Vec::new(),
+        }
+    }
+}
+
+/// Restrict file access to `rules`' directories and TCP connections to its
+/// ports with Landlock, for this thread and every thread and process
+/// started after it; call it before starting the runtime. UDP (DNS) and
+/// Unix sockets are not restricted.
+pub fn restrict(rules: &Rules) -> Result<RulesetStatus> {
+    let ports = rules
+        .ports
+        .iter()
+        .map(|&port| Ok::<_, RulesetError>(NetPort::new(port, AccessNet::ConnectTcp)))
+        .chain(
+            rules
+                .bind
+                .iter()
+                .map(|&port| Ok(NetPort::new(port, AccessNet::BindTcp))),
+        );
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(ABI_VERSION))?
+        .handle_access(AccessNet::from_all(ABI_VERSION))?
+        .create()?
+        .add_rules(path_beneath_rules(
+            &rules.read,
+            AccessFs::from_read(ABI_VERSION),
+        ))?
+        .add_rules(path_beneath_rules(
+            &rules.write,
+            AccessFs::from_all(ABI_VERSION),
+        ))?
+        .add_rules(ports)?
+        .restrict_self()
+        .context("Failed to enable the Landlock sandbox")?;
+    Ok(status.ruleset)
+}
+
+/// The user who started this process as root through `sudo` or `pkexec`.
+pub struct User {
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    name: CString,
+    home: PathBuf,
+}
+
+/// When running as root through `sudo` or `pkexec` (to read `/dev/input`),
+/// the invoking user, whom privileges are dropped to.
+pub fn invoking_user() -> Result<Option<User>> {
+    if unsafe { libc::geteuid() } != 0 {
+        return Ok(None);
+    }
+    let Some(uid) = ["SUDO_UID", "PKEXEC_UID"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok()?.parse::<libc::uid_t>().ok())
+    else {
+        return Ok(None);
+    };
+
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if passwd.is_null() {
+        anyhow::bail!("No user with uid {uid} to drop privileges to");
+    }
+    let (name, gid, home) = unsafe {
+        (
+            CStr::from_ptr((*passwd).pw_name).to_owned(),
+            (*passwd).pw_gid,
+            CStr::from_ptr((*passwd).pw_dir),
+        )
+    };
+    let home = PathBuf::from(OsStr::from_bytes(home.to_bytes()));
+    Ok(Some(User {
+        uid,
+        gid,
+        name,
+        home,
+    }))
+}
+
+/// Point `HOME`, `USER`, `LOGNAME` and `XDG_RUNTIME_DIR` at `user`, and drop
+/// XDG directories outside their home, so the config file, history, sockets
+/// and sandbox rules are theirs rather than root's.
+///
+/// # Safety
+///
+/// Changes the environment: call it before any other thread starts.
+pub unsafe fn adopt_environment(user: &User) {
+    let name = user.name.to_string_lossy();
+    unsafe {
+        std::env::set_var("HOME", &user.home);
+        std::env::set_var("USER", name.as_ref());
+        std::env::set_var("LOGNAME", name.as_ref());
+        std::env::set_var("XDG_RUNTIME_DIR", format!("/run/user/{}", user.uid));
+        for var in [
+            "XDG_CONFIG_HOME",
+            "XDG_DATA_HOME",
+            "XDG_STATE_HOME",
+            "XDG_CACHE_HOME",
+        ] {
+            if std::env::var_os(var).is_some_and(|dir| !Path::new(&dir).starts_with(&user.home)) {
+                std::env::remove_var(var);
+            }
+        }
+    }
+}
+
+/// When running as root through `sudo` or `pkexec`, switch to the invoking
+/// user for good. Call it once the keyboards are open. Returns the user
+/// switched to.
+pub fn drop_privileges() -> Result<Option<String>> {
+    if unsafe { libc::geteuid() } != 0 {
+        return Ok(None);
+    }
+    let Some(User { uid, gid, name, .. }) = invoking_user()? else {
+        tracing::warn!(
+            "Running as root, and not started by sudo or pkexec: not dropping privileges"
+        );
+        return Ok(None);
+    };
+
+    // Groups first: without root, they can't be changed any more. glibc
+    // applies setgid/setuid to every thread
+    unsafe {
+        if libc::initgroups(name.as_ptr(), gid) != 0 {
+            return Err(std::io::Error::last_os_error()).context("initgroups failed");
+        }
+        if libc::setgid(gid) != 0 {
+            return Err(std::io::Error::last_os_error()).context("setgid failed");
+        }
+        if libc::setuid(uid) != 0 {
+            return Err(std::io::Error::last_os_error()).context("setuid failed");
+        }
+        if libc::setuid(0) == 0 {
+            anyhow::bail!("Root privileges could be regained after dropping them");
+        }
+    }
+    Ok(Some(name.to_string_lossy().to_string()))
+}
@@ -0,0 +1,116 @@
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use anyhow::{Context, bail};
+#[cfg(target_os = "linux")]
+use serde::Deserialize;
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use tokio::process::Command;
+
+/// Whether the screen is being shared or recorded.
+///
+/// - Linux: a screen cast stream in PipeWire that something is reading. The
+///   ScreenCast portal (on every desktop) and GNOME's own recorder deliver
+///   frames as a PipeWire video source; cameras are told apart by their
+///   device, and a stream counts only while its node is running
+/// - macOS: not detected
+#[cfg(target_os = "linux")]
+pub async fn active() -> Result<bool> {
+    let output = Command::new("pw-dump")
+        .output()
+        .await
+        .context("Failed to run pw-dump")?;
+    if !output.status.success() {
+        bail!(
+            "pw-dump failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let casts = running_casts(&output.stdout)?;
+    if !casts.is_empty() {
+        tracing::debug!("Screen cast streams running: {}", casts.join(", "));
+    }
+    Ok(!casts.is_empty())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn active() -> Result<bool> {
+    Ok(false)
+}
+
+/// An object in `pw-dump` output
+#[cfg(target_os = "linux")]
+#[derive(Deserialize)]
+struct Object {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    info: Option<Info>,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Deserialize)]
+struct Info {
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    props: HashMap<String, serde_json::Value>,
+}
+
+/// Names of the screen cast nodes in `pw-dump` output that are running.
+/// Screen casts are video sources without a device; cameras have one.
+#[cfg(target_os = "linux")]
+fn running_casts(dump: &[u8]) -> Result<Vec<String>> {
+    let objects: Vec<Object> = serde_json::from_slice(dump).context("Invalid pw-dump output")?;
+    Ok(objects
+        .into_iter()
+        .filter(|object| object.kind == "PipeWire:Interface:Node")
+        .filter_map(|object| object.info)
+        .filter(|info| {
+            info.state.as_deref() == Some("running")
+                && info.props.get("media.class").and_then(|c| c.as_str()) == Some("Video/Source")
+                && !info.props.contains_key("device.api")
+        })
+        .map(|info| {
+            info.props
+                .get("node.name")
+                .and_then(|name| name.as_str())
+                .unwrap_or("unnamed")
+                .to_string()
+        })
+        .collect())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, state: &str, device: bool) -> serde_json::Value {
+        let mut props = serde_json::json!({
+            "node.name": name,
+            "media.class": "Video/Source",
+        });
+        if device {
+            props["device.api"] = "v4l2".into();
+        }
+        serde_json::json!({
+            "id": 42,
+            "type": "PipeWire:Interface:Node",
+            "info": { "state": state, "props": props },
+        })
+    }
+
+    #[test]
+    fn finds_running_casts_only() {
+        let dump = serde_json::json!([
+            { "id": 0, "type": "PipeWire:Interface:Core", "info": {} },
+            node("xdpw_stream", "running", false),
+            node("gnome-shell-screencast", "suspended", false),
+            node("v4l2_input.webcam", "running", true),
+            { "id": 7, "type": "PipeWire:Interface:Link" },
+        ]);
+        let casts = running_casts(dump.to_string().as_bytes()).unwrap();
+        assert_eq!(casts, ["xdpw_stream"]);
+    }
+}
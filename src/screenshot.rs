@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::i18n;
+
+/// Where image actions take their picture from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImageSource {
+    /// Interactively select a screen region
+    Screenshot,
+    /// Use the image currently on the clipboard
+    Clipboard,
+}
+
+/// Grab an image from the given source as a base64-encoded PNG.
+pub async fn capture_image(source: ImageSource) -> Result<String> {
+    match source {
+        ImageSource::Screenshot => Ok(STANDARD.encode(capture_region().await?)),
+        ImageSource::Clipboard => clipboard_image().await,
+    }
+}
+
+/// Read a PNG image from the clipboard as base64.
+///
+/// - Linux: `wl-paste --type image/png`
+/// - macOS: reads the PNG pasteboard type via JavaScript for Automation
+#[cfg(target_os = "linux")]
+async fn clipboard_image() -> Result<String> {
+    let output = Command::new("wl-paste")
+        .args(["--type", "image/png"])
+        .output()
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to read clipboard image ({})",
+                i18n::missing_tool("wl-clipboard")
+            )
+        })?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        anyhow::bail!("No PNG image on the clipboard");
+    }
+
+    Ok(STANDARD.encode(output.stdout))
+}
+
+#[cfg(target_os = "macos")]
+async fn clipboard_image() -> Result<String> {
+    const SCRIPT: &str = r#"ObjC.import('AppKit');
+const data = $.NSPasteboard.generalPasteboard.dataForType($.NSPasteboardTypePNG);
+data.isNil() ? '' : data.base64EncodedStringWithOptions(0).js"#;
+
+    let output = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", SCRIPT])
+        .output()
+        .await
+        .context("Failed to read clipboard image via osascript")?;
+
+    let encoded = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || encoded.is_empty() {
+        anyhow::bail!("No PNG image on the clipboard");
+    }
+
+    Ok(encoded)
+}
+
+/// Let the user pick a screen region and return it as PNG bytes.
+///
+/// - Linux: `slurp` to pick the region, `grim` to capture it
+/// - macOS: interactive `screencapture -i`
+#[cfg(target_os = "linux")]
+pub async fn capture_region() -> Result<Vec<u8>> {
+    let region = Command::new("slurp")
+        .output()
+        .await
+        .with_context(|| format!("Failed to select region ({})", i18n::missing_tool("slurp")))?;
+
+    if !region.status.success() {
+        anyhow::bail!("Region selection cancelled");
+    }
+
+    let geometry = String::from_utf8_lossy(&region.stdout).trim().to_string();
+    let output = Command::new("grim")
+        .args(["-g", &geometry, "-"])
+        .output()
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to capture screenshot ({})",
+                i18n::missing_tool("grim")
+            )
+        })?;
+
+    if !output.status.success() {
+        anyhow::bail!("grim failed: {:?}", output.status);
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(target_os = "macos")]
+pub async fn capture_region() -> Result<Vec<u8>> {
+    let file = crate::private::TempFile::create(".png", &[])?;
+
+    let status = Command::new("screencapture")
+        .args(["-i", "-x"])
+        .arg(file.path())
+        .status()
+        .await
+        .context("Failed to run screencapture")?;
+
+    // A cancelled selection leaves the file empty
+    let png = tokio::fs::read(file.path())
+        .await
+        .context("Failed to read screenshot")?;
+    if !status.success() || png.is_empty() {
+        anyhow::bail!("Region selection cancelled");
+    }
+    Ok(png)
+}
+
+/// Run OCR over a PNG image using `tesseract`.
+pub async fn recognize_text(png: &[u8]) -> Result<String> {
+    let mut child = Command::new("tesseract")
+        .args(["stdin", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run OCR ({})", i18n::missing_tool("tesseract")))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(png).await?;
+    }
+
+    let output = child.wait_with_output().await.context("tesseract failed")?;
+    if !output.status.success() {
+        anyhow::bail!("tesseract failed: {:?}", output.status);
+    }
+
+    // OCR output hard-wraps at the image's line breaks; rejoin paragraphs
+    let text = String::from_utf8_lossy(&output.stdout)
+        .split("\n\n")
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(text)
+}
+
+/// Capture a screen region and return the text recognized in it.
+pub async fn capture_text() -> Result<String> {
+    let png = capture_region().await?;
+    recognize_text(&png).await
+}
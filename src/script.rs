@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use std::path::Path;
+
+/// Limits that stop a runaway hook (an endless loop, deep recursion, or a
+/// string doubled in a loop) instead of hanging the event loop.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 1 << 20;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+/// What the `before` hook decided about a request.
+pub enum Before {
+    /// Go ahead with this (possibly rewritten) text, adding `instructions`
+    /// to the system prompt if set
+    Proceed {
+        text: String,
+        instructions: Option<String>,
+    },
+    /// Drop the request
+    Veto,
+}
+
+/// A user script with optional `before(ctx)` and `after(ctx)` hooks.
+///
+/// `ctx` is a map with `action`, `app` (focused application, or `()` if
+/// unknown), and `text` (the selection); `after` also gets `result`.
+///
+/// `before` may return `()` to continue unchanged, `false` to veto, a string
+/// to replace the text, or a map with any of `text`, `instructions` and
+/// `veto`. `after` may return `()` to keep the result, `false` to veto
+/// output, or a string to replace the result.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE);
+        engine.on_print(|s| tracing::info!("script: {}", s));
+        engine.on_debug(|s, _, _| tracing::debug!("script: {}", s));
+
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("Failed to load script {}", path.display()))?;
+
+        let script = Self { engine, ast };
+        if !script.has_fn("before") && !script.has_fn("after") {
+            tracing::warn!(
+                "Script {} defines neither before(ctx) nor after(ctx)",
+                path.display()
+            );
+        }
+        Ok(script)
+    }
+
+    pub fn before(&self, action: &str, app: Option<&str>, text: &str) -> Result<Before> {
+        let proceed =
+            |text: String, instructions: Option<String>| Before::Proceed { text, instructions };
+        if !self.has_fn("before") {
+            return Ok(proceed(text.to_string(), None));
+        }
+
+        let result = self.call("before", context(action, app, text))?;
+        if result.is_unit() {
+            return Ok(proceed(text.to_string(), None));
+        }
+        if let Ok(proceed_flag) = result.as_bool() {
+            return Ok(if proceed_flag {
+                proceed(text.to_string(), None)
+            } else {
+                Before::Veto
+            });
+        }
+        if result.is_string() {
+            return Ok(proceed(result.into_string().unwrap_or_default(), None));
+        }
+
+        let Some(map) = result.try_cast::<Map>() else {
+            anyhow::bail!("before() must return (), a bool, a string, or a map");
+        };
+        if map.get("veto").and_then(|v| v.as_bool().ok()) == Some(true) {
+            return Ok(Before::Veto);
+        }
+        let text = string_field(&map, "text").unwrap_or_else(|| text.to_string());
+        Ok(proceed(text, string_field(&map, "instructions")))
+    }
+
+    /// Returns the result to output, or `None` if the script vetoed it.
+    pub fn after(
+        &self,
+        action: &str,
+        app: Option<&str>,
+        text: &str,
+        result: &str,
+    ) -> Result<Option<String>> {
+        if !self.has_fn("after") {
+            return Ok(Some(result.to_string()));
+        }
+
+        let mut ctx = context(action, app, text);
+        ctx.insert("result".into(), Dynamic::from(result.to_string()));
+        let returned = self.call("after", ctx)?;
+
+        if returned.is_unit() || returned.as_bool() == Ok(true) {
+            Ok(Some(result.to_string()))
+        } else if returned.as_bool() == Ok(false) {
+            Ok(None)
+        } else if returned.is_string() {
+            Ok(returned.into_string().ok())
+        } else {
+            anyhow::bail!("after() must return (), a bool, or a string")
+        }
+    }
+
+    fn has_fn(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    fn call(&self, name: &str, ctx: Map) -> Result<Dynamic> {
+        self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, (ctx,))
+            .map_err(|e| anyhow::anyhow!("{name}() failed: {e}"))
+    }
+}
+
+fn context(action: &str, app: Option<&str>, text: &str) -> Map {
+    let mut ctx = Map::new();
+    ctx.insert("action".into(), Dynamic::from(action.to_string()));
+    ctx.insert(
+        "app".into(),
+        app.map(|app| Dynamic::from(app.to_string()))
+            .unwrap_or(Dynamic::UNIT),
+    );
+    ctx.insert("text".into(), Dynamic::from(text.to_string()));
+    ctx
+}
+
+fn string_field(map: &Map, key: &str) -> Option<String> {
+    map.get(key)?.clone().into_string().ok()
+}
@@ -0,0 +1,58 @@
+use crate::diff::{self, Edit};
+
+/// How the best of several sampled rewrites is chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Strategy {
+    /// The rewrite most similar to all the others (self-consistency); no
+    /// extra request
+    Consensus,
+    /// Ask the model to judge which rewrite is best
+    Judge,
+}
+
+/// Index of the candidate with the highest total similarity to the others,
+/// i.e. the one the samples agree on most. Ties go to the earlier one.
+pub fn consensus(candidates: &[String]) -> usize {
+    let mut best = (0, f64::MIN);
+    for (i, candidate) in candidates.iter().enumerate() {
+        let score: f64 = candidates
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| similarity(candidate, other))
+            .sum();
+        if score > best.1 {
+            best = (i, score);
+        }
+    }
+    best.0
+}
+
+/// Share of the two texts' characters that a word-level diff finds in
+/// common, from 0 (nothing) to 1 (identical).
+fn similarity(a: &str, b: &str) -> f64 {
+    let total = a.chars().count() + b.chars().count();
+    if total == 0 {
+        return 1.0;
+    }
+    let common: usize = diff::diff(a, b)
+        .iter()
+        .map(|edit| match edit {
+            Edit::Equal(text) => text.chars().count(),
+            Edit::Delete(_) | Edit::Insert(_) => 0,
+        })
+        .sum();
+    (2 * common) as f64 / total as f64
+}
+
+/// The 1-based candidate number a judge answered with, as an index, if it
+/// names one of `count` candidates.
+pub fn parse_choice(reply: &str, count: usize) -> Option<usize> {
+    let digits: String = reply
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(char::is_ascii_digit)
+        .collect();
+    let number: usize = digits.parse().ok()?;
+    (1..=count).contains(&number).then(|| number - 1)
+}
@@ -0,0 +1,357 @@
+use anyhow::{Context, Result};
+#[cfg(target_os = "macos")]
+use std::os::unix::fs::MetadataExt;
+#[cfg(target_os = "macos")]
+use std::path::Path;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// launchd label of the daemon's agent; the menu bar's ends in `.menubar`.
+#[cfg(target_os = "macos")]
+const LABEL: &str = "com.github.martintrojer.improve-writing";
+
+/// systemd user unit running the daemon.
+#[cfg(target_os = "linux")]
+const UNIT: &str = "improve-writing.service";
+
+/// Tools the daemon runs for the basic hotkeys.
+#[cfg(target_os = "linux")]
+const TOOLS: [(&str, &str); 3] = [
+    ("wl-paste", "wl-clipboard"),
+    ("wl-copy", "wl-clipboard"),
+    ("wtype", "wtype"),
+];
+
+fn home() -> Result<PathBuf> {
+    Ok(PathBuf::from(
+        std::env::var_os("HOME").context("HOME is not set")?,
+    ))
+}
+
+fn exe() -> Result<String> {
+    let exe = std::env::current_exe().context("Failed to locate improve-writing binary")?;
+    Ok(exe.to_string_lossy().to_string())
+}
+
+/// Install and start the service running the daemon (with `args`) at
+/// login, replacing one installed earlier, and report missing permissions.
+///
+/// - Linux: a systemd user unit in the graphical session
+/// - macOS: launchd agents for the daemon and the menu bar item
+#[cfg(target_os = "linux")]
+pub async fn install(args: Vec<String>) -> Result<()> {
+    let dir = home()?.join(".config/systemd/user");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(UNIT);
+    let program = std::iter::once(exe()?).chain(args).collect::<Vec<_>>();
+    std::fs::write(&path, unit(&program))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("Installed unit: {}", path.display());
+
+    systemctl(&["daemon-reload"]).await?;
+    systemctl(&["enable", "--now", UNIT]).await?;
+    println!("Enabled and started {UNIT}: {}", program.join(" "));
+    println!("Logs: journalctl --user -u {UNIT}");
+    check_permissions().await;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn install(args: Vec<String>) -> Result<()> {
+    let home = home()?;
+    let exe = exe()?;
+    let agents = home.join("Library/LaunchAgents");
+    std::fs::create_dir_all(&agents)
+        .with_context(|| format!("Failed to create {}", agents.display()))?;
+    let log = home.join("Library/Logs/improve-writing.log");
+
+    let daemon = std::iter::once(exe.clone()).chain(args).collect::<Vec<_>>();
+    let menubar = vec![exe, "menubar".to_string()];
+    for (label, program) in [
+        (LABEL.to_string(), daemon),
+        (format!("{LABEL}.menubar"), menubar),
+    ] {
+        let path = agents.join(format!("{label}.plist"));
+        std::fs::write(&path, plist(&label, &program, &log))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Installed agent: {}", path.display());
+        bootout(&path).await?;
+        bootstrap(&path).await?;
+        println!("Loaded {label}: {}", program.join(" "));
+    }
+    println!("Logs: {}", log.display());
+    check_permissions().await;
+    Ok(())
+}
+
+/// Start the installed service.
+#[cfg(target_os = "linux")]
+pub async fn start() -> Result<()> {
+    systemctl(&["start", UNIT]).await?;
+    println!("Started {UNIT}");
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn start() -> Result<()> {
+    for path in agents()? {
+        bootout(&path).await?;
+        bootstrap(&path).await?;
+        println!("Loaded {}", path.display());
+    }
+    Ok(())
+}
+
+/// Stop the running service, until the next login or `start`.
+#[cfg(target_os = "linux")]
+pub async fn stop() -> Result<()> {
+    systemctl(&["stop", UNIT]).await?;
+    println!("Stopped {UNIT}");
+    Ok(())
+}
+
+// Unloading rather than killing, so launchd doesn't restart the agents
+#[cfg(target_os = "macos")]
+pub async fn stop() -> Result<()> {
+    for path in agents()? {
+        bootout(&path).await?;
+        println!("Unloaded {}", path.display());
+    }
+    Ok(())
+}
+
+/// Print whether the service is installed and running, and what it is
+/// missing.
+#[cfg(target_os = "linux")]
+pub async fn status() -> Result<()> {
+    let output = Command::new("systemctl")
+        .args(["--user", "status", "--no-pager", UNIT])
+        .output()
+        .await
+        .context("Failed to run systemctl")?;
+    // Non-zero just means not running, or not installed
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    if output.stdout.is_empty() {
+        println!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    check_permissions().await;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn status() -> Result<()> {
+    let domain = domain()?;
+    for path in agents()? {
+        let label = path.file_stem().unwrap_or_default().to_string_lossy();
+        let output = Command::new("launchctl")
+            .arg("print")
+            .arg(format!("{domain}/{label}"))
+            .output()
+            .await
+            .context("Failed to run launchctl")?;
+        let printed = String::from_utf8_lossy(&output.stdout);
+        let state = printed
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("state = "))
+            .unwrap_or("not loaded");
+        println!("{label}: {state}");
+    }
+    check_permissions().await;
+    Ok(())
+}
+
+/// Warn about what the daemon needs but can't use: reading keyboards
+/// (`/dev/input`) and the clipboard and typing tools.
+#[cfg(target_os = "linux")]
+async fn check_permissions() {
+    let devices: Vec<PathBuf> = std::fs::read_dir("/dev/input")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("event"))
+        })
+        .collect();
+    if !devices.is_empty()
+        && devices
+            .iter()
+            .all(|path| std::fs::File::open(path).is_err())
+    {
+        println!(
+            "Warning: can't read keyboards in /dev/input, so hotkeys won't work. Add yourself to the input group and log in again: sudo usermod -aG input $USER"
+        );
+    }
+
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    for (tool, package) in TOOLS {
+        if !std::env::split_paths(&path).any(|dir| dir.join(tool).is_file()) {
+            println!("Warning: {tool} not found; install {package}");
+        }
+    }
+}
+
+/// Warn when the binary may not type: System Events refuses scripting
+/// without Accessibility permissions.
+#[cfg(target_os = "macos")]
+async fn check_permissions() {
+    let allowed = Command::new("osascript")
+        .args([
+            "-e",
+            r#"tell application "System Events" to get name of first process"#,
+        ])
+        .output()
+        .await
+        .is_ok_and(|output| output.status.success());
+    if !allowed {
+        println!(
+            "Warning: no Accessibility permissions, so text can't be typed. Add {} in System Settings > Privacy & Security > Accessibility",
+            exe().unwrap_or_else(|_| "improve-writing".to_string())
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn systemctl(args: &[&str]) -> Result<()> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run systemctl")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// A unit started with the graphical session, which provides
+/// `WAYLAND_DISPLAY`, and restarted if it fails.
+#[cfg(target_os = "linux")]
+fn unit(program: &[String]) -> String {
+    // systemd splits ExecStart= like a shell and expands % specifiers and
+    // $VARIABLES; quote and escape every argument
+    let exec: Vec<String> = program
+        .iter()
+        .map(|arg| {
+            let arg = arg
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('%', "%%")
+                .replace('$', "$$");
+            format!("\"{arg}\"")
+        })
+        .collect();
+    format!(
+        "[Unit]
+Description=Hotkey-triggered text improvement via Ollama
+After=graphical-session.target
+PartOf=graphical-session.target
+
+[Service]
+ExecStart={}
+Restart=on-failure
+
+[Install]
+WantedBy=graphical-session.target
+",
+        exec.join(" ")
+    )
+}
+
+/// launchd's domain for the user's GUI session, by the owner of `$HOME`.
+#[cfg(target_os = "macos")]
+fn domain() -> Result<String> {
+    Ok(format!("gui/{}", std::fs::metadata(home()?)?.uid()))
+}
+
+/// The installed agents' plists.
+#[cfg(target_os = "macos")]
+fn agents() -> Result<Vec<PathBuf>> {
+    let dir = home()?.join("Library/LaunchAgents");
+    let agents: Vec<PathBuf> = [LABEL.to_string(), format!("{LABEL}.menubar")]
+        .iter()
+        .map(|label| dir.join(format!("{label}.plist")))
+        .filter(|path| path.is_file())
+        .collect();
+    if agents.is_empty() {
+        anyhow::bail!("Not installed (run improve-writing service install)");
+    }
+    Ok(agents)
+}
+
+/// Unload an agent; one that isn't loaded is fine.
+#[cfg(target_os = "macos")]
+async fn bootout(plist: &Path) -> Result<()> {
+    let _ = Command::new("launchctl")
+        .args(["bootout", &domain()?])
+        .arg(plist)
+        .output()
+        .await;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+async fn bootstrap(plist: &Path) -> Result<()> {
+    let output = Command::new("launchctl")
+        .args(["bootstrap", &domain()?])
+        .arg(plist)
+        .output()
+        .await
+        .context("Failed to run launchctl")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "launchctl bootstrap failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// A launch agent started at login and restarted if it fails (but not when
+/// quit).
+#[cfg(target_os = "macos")]
+fn plist(label: &str, program: &[String], log: &Path) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let arguments: String = program
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", escape(arg)))
+        .collect();
+    let log = escape(&log.to_string_lossy());
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>ProcessType</key>
+    <string>Interactive</string>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#
+    )
+}
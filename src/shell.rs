@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::control;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// Each widget sends the command line to the running daemon with
+// `improve-writing generate-command` and replaces it with the result, bound to
+// Ctrl+X Ctrl+G.
+
+const BASH: &str = r#"# improve-writing: eval "$(improve-writing shell-init bash)"
+__improve_writing_widget() {
+    [[ -z $READLINE_LINE ]] && return
+    local result
+    if result=$(improve-writing generate-command -- "$READLINE_LINE"); then
+        READLINE_LINE=$result
+        READLINE_POINT=${#READLINE_LINE}
+    fi
+}
+bind -x '"\C-x\C-g": __improve_writing_widget'
+"#;
+
+const ZSH: &str = r#"# improve-writing: eval "$(improve-writing shell-init zsh)"
+__improve_writing_widget() {
+    [[ -z $BUFFER ]] && return
+    local result
+    if result=$(improve-writing generate-command -- "$BUFFER" 2>/dev/null); then
+        BUFFER=$result
+        CURSOR=${#BUFFER}
+    else
+        zle -M "improve-writing: request failed (is the daemon running?)"
+    fi
+}
+zle -N __improve_writing_widget
+bindkey '^X^G' __improve_writing_widget
+"#;
+
+const FISH: &str = r#"# improve-writing: improve-writing shell-init fish | source
+function __improve_writing_widget
+    set -l buffer (commandline)
+    test -z "$buffer"; and return
+    if set -l result (improve-writing generate-command -- "$buffer" 2>/dev/null)
+        commandline -r -- (string join \n -- $result)
+    else
+        echo
+        echo "improve-writing: request failed (is the daemon running?)"
+    end
+    commandline -f repaint
+end
+bind \cx\cg __improve_writing_widget
+"#;
+
+/// Print the widget script for `shell`, to be evaluated in its rc file.
+pub fn init(shell: Shell) {
+    let script = match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    };
+    print!("{script}");
+}
+
+impl Shell {
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    /// The history file: $HISTFILE for bash and zsh when the daemon sees it,
+    /// otherwise each shell's default.
+    fn history_path(self) -> Result<PathBuf> {
+        let home = std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .context("HOME is not set")?;
+        let histfile = std::env::var_os("HISTFILE").map(PathBuf::from);
+        Ok(match self {
+            Shell::Bash => histfile.unwrap_or_else(|| home.join(".bash_history")),
+            Shell::Zsh => histfile.unwrap_or_else(|| {
+                std::env::var_os("ZDOTDIR")
+                    .map(PathBuf::from)
+                    .unwrap_or(home)
+                    .join(".zsh_history")
+            }),
+            Shell::Fish => std::env::var_os("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.join(".local/share"))
+                .join("fish/fish_history"),
+        })
+    }
+
+    /// `command` as a history file entry.
+    fn history_entry(self, command: &str, when: u64) -> Vec<u8> {
+        let entry = match self {
+            // Bash only keeps timestamps with HISTTIMEFORMAT, and reads every
+            // line as a command of its own
+            Shell::Bash => format!("{}\n", command.lines().collect::<Vec<_>>().join(" ")),
+            // Extended format, which zsh reads whether or not
+            // EXTENDED_HISTORY is set; continuation lines end in a backslash
+            Shell::Zsh => {
+                let entry = format!(": {when}:0;{}\n", command.replace('\n', "\\\n"));
+                return metafy(&entry);
+            }
+            Shell::Fish => format!(
+                "- cmd: {}\n  when: {when}\n",
+                command.replace('\\', "\\\\").replace('\n', "\\n")
+            ),
+        };
+        entry.into_bytes()
+    }
+}
+
+/// zsh's history file encoding: bytes it uses internally (0x83 to 0xa2) are
+/// written as 0x83 followed by the byte XOR 32.
+fn metafy(text: &str) -> Vec<u8> {
+    const META: u8 = 0x83;
+    let mut bytes = Vec::with_capacity(text.len());
+    for &byte in text.as_bytes() {
+        if (META..=0xa2).contains(&byte) {
+            bytes.extend([META, byte ^ 32]);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+/// Quote `s` as a single word for a POSIX shell.
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Append `command` to the history file of `shell`, so it can be recalled
+/// with Ctrl+R. Shells that are already open only see it once they reload
+/// their history (immediately with zsh's SHARE_HISTORY). Returns the file.
+pub fn append_history(shell: Shell, command: &str) -> Result<PathBuf> {
+    let path = shell.history_path()?;
+    let when = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let entry = shell.history_entry(command.trim(), when);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    // A single write, so it isn't interleaved with a shell appending
+    file.write_all(&entry)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    tracing::info!("Appended command to {}", path.display());
+    Ok(path)
+}
+
+/// Ask the running instance to turn `text` into a shell command and print it.
+pub async fn generate_command(socket: &Path, text: &str, refine: bool) -> Result<()> {
+    let result = control::call(
+        socket,
+        "generate_command",
+        json!({ "text": text, "refine": refine }),
+    )
+    .await?;
+    let command = result["text"]
+        .as_str()
+        .filter(|command| !command.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Daemon returned no command"))?;
+    println!("{command}");
+    Ok(())
+}
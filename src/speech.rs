@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use std::sync::Mutex;
+use tokio::process::{Child, Command};
+
+use crate::i18n;
+use crate::window::Window;
+
+/// Start speaking `text` aloud, returning the speaking process. Dropping it
+/// stops the speech (see `stop`).
+///
+/// - Linux: `spd-say` (speech-dispatcher), which the screen reader uses too
+/// - macOS: `say`
+#[cfg(target_os = "linux")]
+pub fn speak(text: &str) -> Result<Child> {
+    Command::new("spd-say")
+        .args(["--wait", "--"])
+        .arg(text)
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| {
+            format!(
+                "Failed to speak text ({})",
+                i18n::missing_tool("speech-dispatcher")
+            )
+        })
+}
+
+#[cfg(target_os = "macos")]
+pub fn speak(text: &str) -> Result<Child> {
+    Command::new("say")
+        .arg("--")
+        .arg(text)
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to speak text via say")
+}
+
+/// Stop speaking. Killing `say` silences it; speech-dispatcher keeps reading
+/// a message whose client has gone, so it is cancelled explicitly.
+#[cfg(target_os = "linux")]
+async fn stop(speaker: Child) {
+    drop(speaker);
+    if let Err(e) = Command::new("spd-say").arg("--cancel").status().await {
+        tracing::debug!("Failed to cancel speech: {}", e);
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn stop(speaker: Child) {
+    drop(speaker);
+}
+
+/// A result read aloud and waiting for the accept or reject hotkey.
+pub struct Pending {
+    pub text: String,
+    /// Window the result's hotkey was pressed in
+    pub origin: Option<Window>,
+    speaker: Option<Child>,
+}
+
+/// The read-aloud accept flow: results are spoken instead of typed, and
+/// typed only once accepted. Holds at most one result; a newer one replaces
+/// it.
+#[derive(Default)]
+pub struct ReadAloud {
+    pending: Mutex<Option<Pending>>,
+}
+
+impl ReadAloud {
+    /// Read `text` aloud and keep it until it is accepted or rejected.
+    pub async fn hold(&self, origin: Option<&Window>, text: &str) -> Result<()> {
+        if self.take().await.is_some() {
+            tracing::info!("Dropping the previous result waiting to be accepted");
+        }
+        let speaker = speak(text)?;
+        *self.pending.lock().unwrap() = Some(Pending {
+            text: text.to_string(),
+            origin: origin.cloned(),
+            speaker: Some(speaker),
+        });
+        tracing::info!("Result read aloud - press the accept or reject hotkey");
+        Ok(())
+    }
+
+    /// Take the waiting result, stopping its speech.
+    pub async fn take(&self) -> Option<Pending> {
+        let mut pending = self.pending.lock().unwrap().take()?;
+        if let Some(speaker) = pending.speaker.take() {
+            stop(speaker).await;
+        }
+        Some(pending)
+    }
+}
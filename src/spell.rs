@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use whatlang::Lang;
+
+use crate::i18n;
+
+/// Hunspell dictionary used for each language unless --spellcheck-dict
+/// names another.
+const DICTIONARIES: &[(&str, &str)] = &[
+    ("en", "en_US"),
+    ("de", "de_DE"),
+    ("fr", "fr_FR"),
+    ("es", "es_ES"),
+    ("it", "it_IT"),
+    ("pt", "pt_PT"),
+    ("nl", "nl_NL"),
+    ("sv", "sv_SE"),
+    ("da", "da_DK"),
+    ("nb", "nb_NO"),
+    ("fi", "fi_FI"),
+    ("pl", "pl_PL"),
+    ("cs", "cs_CZ"),
+    ("ru", "ru_RU"),
+    ("uk", "uk_UA"),
+];
+
+/// Local spellcheck run over text before it goes to the model, fixing only
+/// typos with one obvious correction.
+#[derive(Clone, Debug)]
+pub struct Spellcheck {
+    /// Dictionary by ISO 639-1 language code
+    dictionaries: HashMap<String, String>,
+}
+
+impl Spellcheck {
+    /// `overrides` are `LANG=DICT`, e.g. `en=en_GB`.
+    pub fn new(overrides: &[String]) -> Result<Self> {
+        let mut dictionaries: HashMap<String, String> = DICTIONARIES
+            .iter()
+            .map(|(lang, dict)| (lang.to_string(), dict.to_string()))
+            .collect();
+        for spec in overrides {
+            let (lang, dict) = spec
+                .split_once('=')
+                .map(|(lang, dict)| (lang.trim(), dict.trim()))
+                .filter(|(lang, dict)| !lang.is_empty() && !dict.is_empty())
+                .with_context(|| format!("Invalid dictionary {spec:?} (expected LANG=DICT)"))?;
+            dictionaries.insert(lang.to_lowercase(), dict.to_string());
+        }
+        Ok(Self { dictionaries })
+    }
+
+    /// The dictionary for the language `text` is written in. Short texts
+    /// can't be told apart reliably, so they are taken to be in the user's
+    /// language.
+    fn dictionary(&self, text: &str) -> Option<&str> {
+        let lang = match whatlang::detect(text) {
+            Some(info) if info.is_reliable() => match iso_639_1(info.lang()) {
+                Some(lang) => lang.to_string(),
+                None => {
+                    tracing::debug!("No dictionary for {:?}, not spellchecking", info.lang());
+                    return None;
+                }
+            },
+            _ => i18n::lang(),
+        };
+        tracing::debug!("Spellchecking as {:?}", lang);
+        self.dictionaries.get(&lang).map(String::as_str)
+    }
+
+    /// Fix unambiguous typos in `text`, leaving `keep` (protected terms)
+    /// alone.
+    pub async fn correct(&self, text: &str, keep: &[String]) -> Result<String> {
+        let Some(dictionary) = self.dictionary(text) else {
+            return Ok(text.to_string());
+        };
+
+        let mut words: Vec<&str> = text
+            .split_whitespace()
+            .map(core)
+            .filter(|word| checkable(word) && !keep.iter().any(|term| term.contains(word)))
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+        if words.is_empty() {
+            return Ok(text.to_string());
+        }
+
+        let fixes = check(dictionary, &words).await?;
+        if fixes.is_empty() {
+            return Ok(text.to_string());
+        }
+        tracing::debug!("Spelling fixes: {:?}", fixes);
+
+        let corrected = text
+            .split_inclusive(char::is_whitespace)
+            .map(|token| {
+                let word = core(token.trim_end());
+                match fixes.get(word) {
+                    Some(fix) => token.replacen(word, fix, 1),
+                    None => token.to_string(),
+                }
+            })
+            .collect();
+        Ok(corrected)
+    }
+}
+
+/// Run `words` through `hunspell -a` (ispell pipe mode), one per line, and
+/// return the unambiguous corrections.
+async fn check(dictionary: &str, words: &[&str]) -> Result<HashMap<String, String>> {
+    let mut child = Command::new("hunspell")
+        .args(["-a", "-d", dictionary])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| {
+            format!(
+                "Failed to run hunspell ({})",
+                i18n::missing_tool("hunspell")
+            )
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // `^` keeps a line from being read as a pipe-mode command
+        let input: String = words.iter().map(|word| format!("^{word}\n")).collect();
+        stdin.write_all(input.as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await.context("hunspell failed")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "hunspell -d {} failed: {}",
+            dictionary,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    // A version banner, then one result line and a blank line per word
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let results = stdout.lines().skip(1).filter(|line| !line.is_empty());
+    let mut fixes = HashMap::new();
+    for (word, result) in words.iter().zip(results) {
+        // & WORD COUNT OFFSET: SUGGESTION, SUGGESTION, ...
+        let Some(suggestions) = result.strip_prefix('&').and_then(|r| r.split_once(": ")) else {
+            continue;
+        };
+        let suggestions: Vec<&str> = suggestions.1.split(", ").collect();
+        if let Some(fix) = unambiguous(word, &suggestions) {
+            fixes.insert(word.to_string(), fix.to_string());
+        }
+    }
+    Ok(fixes)
+}
+
+/// Hunspell's best suggestion, if it is a single edit away and no other
+/// suggestion is.
+fn unambiguous<'a>(word: &str, suggestions: &[&'a str]) -> Option<&'a str> {
+    let (first, rest) = suggestions.split_first()?;
+    (edit_distance(word, first) == 1 && rest.iter().all(|s| edit_distance(word, s) > 1))
+        .then_some(*first)
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions
+/// and transpositions of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// A whitespace-separated token without surrounding punctuation.
+fn core(token: &str) -> &str {
+    token.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+/// Whether a word is plain enough to correct: letters only, capitalized at
+/// most at the start. Leaves out names with inner capitals, acronyms,
+/// numbers, contractions (which dictionaries split differently),
+/// identifiers, paths, URLs and placeholders.
+fn checkable(word: &str) -> bool {
+    word.chars().count() >= 3
+        && word.chars().all(char::is_alphabetic)
+        && !word.chars().skip(1).any(char::is_uppercase)
+}
+
+/// ISO 639-1 code of a detected language with a bundled default dictionary.
+fn iso_639_1(lang: Lang) -> Option<&'static str> {
+    let code = match lang {
+        Lang::Eng => "en",
+        Lang::Deu => "de",
+        Lang::Fra => "fr",
+        Lang::Spa => "es",
+        Lang::Ita => "it",
+        Lang::Por => "pt",
+        Lang::Nld => "nl",
+        Lang::Swe => "sv",
+        Lang::Dan => "da",
+        Lang::Nob => "nb",
+        Lang::Fin => "fi",
+        Lang::Pol => "pl",
+        Lang::Ces => "cs",
+        Lang::Rus => "ru",
+        Lang::Ukr => "uk",
+        _ => return None,
+    };
+    Some(code)
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Per-action cooldowns and a global requests-per-minute cap for hotkey
+/// actions, so a stuck key can't flood Ollama.
+pub struct Throttle {
+    /// Cooldown for actions without their own
+    default_cooldown: Option<Duration>,
+    /// By action name, as passed to script hooks
+    cooldowns: HashMap<String, Duration>,
+    per_minute: Option<usize>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    last: HashMap<String, Instant>,
+    /// Start times of the requests in the last minute, oldest first
+    recent: VecDeque<Instant>,
+}
+
+impl Throttle {
+    /// `cooldowns` are `ACTION=SECONDS`, or bare `SECONDS` for every action
+    /// without its own.
+    pub fn new(cooldowns: &[String], per_minute: Option<usize>) -> Result<Self> {
+        let mut default_cooldown = None;
+        let mut by_action = HashMap::new();
+        for spec in cooldowns {
+            let (action, seconds) = match spec.split_once('=') {
+                Some((action, seconds)) => (Some(action.trim()), seconds),
+                None => (None, spec.as_str()),
+            };
+            let seconds: f64 = seconds.trim().parse().with_context(|| {
+                format!("Invalid cooldown {spec:?} (expected [ACTION=]SECONDS)")
+            })?;
+            let cooldown = Duration::try_from_secs_f64(seconds)
+                .with_context(|| format!("Invalid cooldown {spec:?}"))?;
+            match action {
+                Some(action) => {
+                    by_action.insert(action.to_string(), cooldown);
+                }
+                None => default_cooldown = Some(cooldown),
+            }
+        }
+
+        Ok(Self {
+            default_cooldown,
+            cooldowns: by_action,
+            per_minute,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    /// Record a request for `action`, or say why it has to be dropped.
+    /// Dropped requests don't count towards either limit.
+    pub fn check(&self, action: &str) -> Result<(), String> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        let cooldown = self
+            .cooldowns
+            .get(action)
+            .copied()
+            .or(self.default_cooldown);
+        if let Some(cooldown) = cooldown
+            && let Some(last) = state.last.get(action)
+        {
+            let elapsed = now.duration_since(*last);
+            if elapsed < cooldown {
+                return Err(format!(
+                    "{action} is cooling down for another {:.1}s",
+                    (cooldown - elapsed).as_secs_f64()
+                ));
+            }
+        }
+
+        if let Some(per_minute) = self.per_minute {
+            while state
+                .recent
+                .front()
+                .is_some_and(|start| now.duration_since(*start) >= MINUTE)
+            {
+                state.recent.pop_front();
+            }
+            if state.recent.len() >= per_minute {
+                return Err(format!("already {per_minute} requests in the last minute"));
+            }
+        }
+
+        state.last.insert(action.to_string(), now);
+        state.recent.push_back(now);
+        Ok(())
+    }
+}
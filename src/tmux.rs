@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use fluent_bundle::FluentArgs;
+use tokio::process::Command;
+
+use crate::danger;
+use crate::i18n;
+use crate::ollama::TextImprover;
+use crate::private::TempFile;
+use crate::shell;
+
+/// What to do with the captured pane text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Task {
+    /// Explain the output, e.g. an error and how to fix it
+    Explain,
+    /// Summarize the output
+    Summarize,
+    /// Correct the last failed command and put it on the prompt, unrun
+    Fix,
+}
+
+impl Task {
+    fn title(self) -> &'static str {
+        match self {
+            Task::Explain => "Explanation",
+            Task::Summarize => "Summary",
+            Task::Fix => "Fix",
+        }
+    }
+}
+
+/// Run `task` over the current tmux pane's visible text (or, with `buffer`,
+/// the most recent copy-mode selection) and show the result in a popup.
+/// Meant to be bound in tmux, e.g. `bind-key E run-shell -b "improve-writing tmux"`.
+pub async fn run(improver: &TextImprover, task: Task, buffer: bool) -> Result<()> {
+    if std::env::var_os("TMUX").is_none() {
+        anyhow::bail!("Not running inside tmux");
+    }
+
+    let text = if buffer {
+        tmux(&["show-buffer"]).await?
+    } else {
+        tmux(&["capture-pane", "-p", "-J"]).await?
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        anyhow::bail!("Nothing to {}", task.title().to_lowercase());
+    }
+
+    let _ = tmux(&["display-message", "improve-writing: thinking..."]).await;
+    let result = match task {
+        Task::Explain => improver.explain(text).await?,
+        Task::Summarize => improver.summarize(text).await?,
+        Task::Fix => return fix(improver, text).await,
+    };
+    popup(task.title(), &result).await
+}
+
+/// Ask for a corrected version of the last failed command in `text` and
+/// type it at the pane's prompt without pressing Enter. Commands that look
+/// destructive are shown in a popup instead.
+async fn fix(improver: &TextImprover, text: &str) -> Result<()> {
+    let command = improver.fix_command(text).await?;
+    if command.is_empty() {
+        anyhow::bail!("No corrected command");
+    }
+    tracing::debug!("Fixed command: {:?}", command);
+
+    let dangers = danger::check(&command);
+    if !dangers.is_empty() {
+        let args = FluentArgs::from_iter([("reasons", dangers.join(", "))]);
+        let warning = format!(
+            "{command}\n\n{}\n{}",
+            i18n::t("command-not-typed"),
+            i18n::t_args("command-dangerous", Some(&args))
+        );
+        return popup("Fix", &warning).await;
+    }
+    tmux(&["send-keys", "-l", "--", &command]).await?;
+    Ok(())
+}
+
+/// Show `text` in a tmux popup with a pager. The file is removed when the
+/// popup closes.
+async fn popup(title: &str, text: &str) -> Result<()> {
+    let file = TempFile::create(".txt", format!("{}\n", text.trim_end()).as_bytes())?;
+    let path = shell::quote(&file.path().to_string_lossy());
+    let command = format!("${{PAGER:-less}} {path}; rm -f {path}");
+    tmux(&[
+        "display-popup",
+        "-w",
+        "80%",
+        "-h",
+        "60%",
+        "-T",
+        &format!(" {title} "),
+        "-E",
+        &command,
+    ])
+    .await?;
+    // The popup may still be open; its command removes the file
+    file.keep();
+    Ok(())
+}
+
+async fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run tmux")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "tmux {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
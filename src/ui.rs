@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::config;
+use crate::control;
+use crate::prompts;
+
+/// The page, with `{{token}}` filled in when served.
+const PAGE: &str = include_str!("../ui/index.html");
+
+/// Control socket methods the page may call.
+const METHODS: [&str; 8] = [
+    "status",
+    "set_model",
+    "set_profile",
+    "recent",
+    "history",
+    "improve",
+    "generate_command",
+    "reload",
+];
+
+struct Ui {
+    socket: PathBuf,
+    port: u16,
+    /// Sent by the page with every call; other sites can't read the page to
+    /// learn it
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct Call {
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// A prompt file, by its `namespace/name` reference.
+#[derive(Deserialize)]
+struct Prompt {
+    name: String,
+}
+
+/// Serve the configuration and history page on `127.0.0.1:port`, driving
+/// the running instance over its control socket.
+pub async fn run(socket: PathBuf, port: u16) -> Result<()> {
+    let token = format!(
+        "{:016x}{:016x}",
+        RandomState::new().hash_one(SystemTime::now()),
+        RandomState::new().hash_one(std::process::id())
+    );
+    let ui = Arc::new(Ui {
+        socket,
+        port,
+        token,
+    });
+    let app = Router::new()
+        .route("/", get(page))
+        .route("/rpc", post(rpc))
+        .route("/config", get(read_config).put(write_config))
+        .route("/prompts", get(list_prompts))
+        .route("/prompt", get(read_prompt).put(write_prompt))
+        .with_state(ui);
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Failed to listen on 127.0.0.1:{port}"))?;
+    println!("Open http://127.0.0.1:{port}/");
+    axum::serve(listener, app).await.context("UI server failed")
+}
+
+/// Refuse requests for another host name, so a web page can't reach the
+/// server through DNS rebinding, and calls without the page's token, so a
+/// form on another site can't either.
+fn check(ui: &Ui, headers: &HeaderMap, call: bool) -> Result<(), Response> {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or_default();
+    if host != format!("127.0.0.1:{}", ui.port) && host != format!("localhost:{}", ui.port) {
+        return Err((StatusCode::FORBIDDEN, "Unknown host").into_response());
+    }
+    let token = headers.get("x-token").and_then(|token| token.to_str().ok());
+    if call && token != Some(ui.token.as_str()) {
+        return Err((StatusCode::FORBIDDEN, "Missing token").into_response());
+    }
+    Ok(())
+}
+
+async fn page(State(ui): State<Arc<Ui>>, headers: HeaderMap) -> Response {
+    if let Err(response) = check(&ui, &headers, false) {
+        return response;
+    }
+    Html(PAGE.replace("{{token}}", &ui.token)).into_response()
+}
+
+async fn rpc(State(ui): State<Arc<Ui>>, headers: HeaderMap, Json(call): Json<Call>) -> Response {
+    if let Err(response) = check(&ui, &headers, true) {
+        return response;
+    }
+    if !METHODS.contains(&call.method.as_str()) {
+        return Json(json!({ "error": format!("{} is not available here", call.method) }))
+            .into_response();
+    }
+    reply(control::call(&ui.socket, &call.method, call.params).await)
+}
+
+/// A call's result or error, as the page expects it.
+fn reply(result: Result<Value>) -> Response {
+    let reply = match result {
+        Ok(result) => json!({ "result": result }),
+        Err(e) => json!({ "error": format!("{e:#}") }),
+    };
+    Json(reply).into_response()
+}
+
+async fn read_config(State(ui): State<Arc<Ui>>, headers: HeaderMap) -> Response {
+    if let Err(response) = check(&ui, &headers, true) {
+        return response;
+    }
+    let path = config::path();
+    let contents = std::fs::read_to_string(&path).unwrap_or_default();
+    Json(json!({ "path": path, "contents": contents })).into_response()
+}
+
+/// Save the config file if it is valid TOML, and have the running instance
+/// reload its profiles from it.
+async fn write_config(State(ui): State<Arc<Ui>>, headers: HeaderMap, contents: String) -> Response {
+    if let Err(response) = check(&ui, &headers, true) {
+        return response;
+    }
+    reply(save_config(&ui, &contents).await)
+}
+
+async fn save_config(ui: &Ui, contents: &str) -> Result<Value> {
+    contents
+        .parse::<toml::Table>()
+        .context("Not saved: invalid TOML")?;
+    let path = config::path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(&path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    control::call(&ui.socket, "reload", json!({}))
+        .await
+        .context("Saved, but the running instance didn't reload it")
+}
+
+async fn list_prompts(State(ui): State<Arc<Ui>>, headers: HeaderMap) -> Response {
+    if let Err(response) = check(&ui, &headers, true) {
+        return response;
+    }
+    reply(prompts::names().map(|names| json!(names)))
+}
+
+async fn read_prompt(
+    State(ui): State<Arc<Ui>>,
+    headers: HeaderMap,
+    Query(prompt): Query<Prompt>,
+) -> Response {
+    if let Err(response) = check(&ui, &headers, true) {
+        return response;
+    }
+    reply(prompts::read(&prompt.name).map(Value::String))
+}
+
+/// Save a prompt file and have the running instance reload the profiles
+/// that use it.
+async fn write_prompt(
+    State(ui): State<Arc<Ui>>,
+    headers: HeaderMap,
+    Query(prompt): Query<Prompt>,
+    contents: String,
+) -> Response {
+    if let Err(response) = check(&ui, &headers, true) {
+        return response;
+    }
+    if let Err(e) = prompts::write(&prompt.name, &contents) {
+        return reply(Err(e));
+    }
+    reply(
+        control::call(&ui.socket, "reload", json!({}))
+            .await
+            .context("Saved, but the running instance didn't reload it"),
+    )
+}
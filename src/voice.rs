@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::i18n;
+use crate::private::TempFile;
+
+/// How voice instructions are recorded and transcribed.
+#[derive(Clone, Debug)]
+pub struct Voice {
+    /// whisper.cpp model file (`ggml-*.bin`)
+    pub model: PathBuf,
+    /// whisper.cpp command-line program
+    pub program: String,
+    /// How long to record for
+    pub seconds: u64,
+}
+
+impl Voice {
+    /// Record an instruction from the microphone and transcribe it locally.
+    pub async fn listen(&self) -> Result<String> {
+        // The recorder writes into the private file created here
+        let file = TempFile::create(".wav", &[])?;
+        record(file.path(), self.seconds).await?;
+        let instruction = self.transcribe(file.path()).await?;
+        if instruction.is_empty() {
+            anyhow::bail!("No instruction heard");
+        }
+        tracing::info!("Voice instruction: {:?}", instruction);
+        Ok(instruction)
+    }
+
+    /// Transcribe a 16 kHz mono WAV file with whisper.cpp.
+    async fn transcribe(&self, path: &Path) -> Result<String> {
+        let output = Command::new(&self.program)
+            .arg("-m")
+            .arg(&self.model)
+            .arg("-f")
+            .arg(path)
+            .args(["-l", "auto", "--no-timestamps", "--no-prints"])
+            .output()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to run {} ({})",
+                    self.program,
+                    i18n::missing_tool("whisper.cpp")
+                )
+            })?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} failed: {}",
+                self.program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        // whisper.cpp marks silence and noise as [BLANK_AUDIO], (music) etc.
+        let text = String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .filter(|word| !(word.starts_with('[') || word.starts_with('(')))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(text)
+    }
+}
+
+/// Record `seconds` of 16 kHz mono audio, the format whisper.cpp expects.
+///
+/// - Linux: `arecord` (ALSA, also served by PipeWire and PulseAudio)
+/// - macOS: `rec` from SoX
+#[cfg(target_os = "linux")]
+async fn record(path: &Path, seconds: u64) -> Result<()> {
+    let status = Command::new("arecord")
+        .args(["-q", "-f", "S16_LE", "-r", "16000", "-c", "1", "-d"])
+        .arg(seconds.to_string())
+        .arg(path)
+        .status()
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to record audio ({})",
+                i18n::missing_tool("alsa-utils")
+            )
+        })?;
+
+    if !status.success() {
+        anyhow::bail!("arecord failed: {:?}", status);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+async fn record(path: &Path, seconds: u64) -> Result<()> {
+    let status = Command::new("rec")
+        .args(["-q", "-r", "16000", "-c", "1", "-b", "16"])
+        .arg(path)
+        .args(["trim", "0"])
+        .arg(seconds.to_string())
+        .status()
+        .await
+        .with_context(|| format!("Failed to record audio ({})", i18n::missing_tool("sox")))?;
+
+    if !status.success() {
+        anyhow::bail!("rec failed: {:?}", status);
+    }
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use std::time::Duration;
+
+/// Elements whose content is never readable text.
+const SKIPPED: [&str; 8] = [
+    "script", "style", "noscript", "svg", "nav", "header", "footer", "aside",
+];
+
+/// Elements that start a new line of text.
+const BLOCKS: [&str; 14] = [
+    "p",
+    "div",
+    "br",
+    "li",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "tr",
+    "section",
+    "article",
+    "blockquote",
+];
+
+/// Whether the selection is a single http(s) URL.
+pub fn is_url(text: &str) -> bool {
+    let text = text.trim();
+    (text.starts_with("http://") || text.starts_with("https://"))
+        && !text.contains(char::is_whitespace)
+}
+
+/// Fetch a page and extract its readable text.
+pub async fn fetch_readable_text(url: &str) -> Result<String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent(concat!("improve-writing/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let html = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Failed to fetch {url}"))?
+        .text()
+        .await
+        .context("Failed to read page body")?;
+
+    Ok(extract_text(&html))
+}
+
+/// Readability-style extraction: prefer the `<article>` or `<main>` element,
+/// drop non-content elements, strip tags, and collapse whitespace.
+pub fn extract_text(html: &str) -> String {
+    // ASCII lowercasing keeps byte offsets identical to the original
+    let lower = html.to_ascii_lowercase();
+    let (start, end) = main_content(&lower);
+    let html = &html[start..end];
+    let lower = &lower[start..end];
+
+    let mut raw = String::new();
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find('<') {
+        let tag_start = pos + offset;
+        raw.push_str(&html[pos..tag_start]);
+
+        if lower[tag_start..].starts_with("<!--") {
+            match lower[tag_start..].find("-->") {
+                Some(close_at) => pos = tag_start + close_at + 3,
+                None => pos = lower.len(),
+            }
+            continue;
+        }
+
+        let name = tag_name(&lower[tag_start + 1..]);
+        if SKIPPED.contains(&name) {
+            let close = format!("</{name}");
+            match lower[tag_start..].find(&close) {
+                Some(close_at) => pos = tag_start + close_at + close.len(),
+                None => {
+                    pos = lower.len();
+                    break;
+                }
+            }
+        } else {
+            pos = tag_start + 1;
+        }
+
+        match lower[pos..].find('>') {
+            Some(tag_end) => pos += tag_end + 1,
+            None => {
+                pos = lower.len();
+                break;
+            }
+        }
+
+        if BLOCKS.contains(&name) {
+            raw.push('\n');
+        }
+    }
+    raw.push_str(&html[pos..]);
+
+    let text = decode_entities(&raw);
+    let mut paragraphs = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !line.is_empty() {
+            paragraphs.push(line);
+        }
+    }
+    paragraphs.join("\n\n")
+}
+
+/// Byte range of the most content-like element.
+fn main_content(lower: &str) -> (usize, usize) {
+    for tag in ["article", "main", "body"] {
+        if let Some(start) = lower.find(&format!("<{tag}")) {
+            let end = lower
+                .rfind(&format!("</{tag}>"))
+                .filter(|end| *end > start)
+                .unwrap_or(lower.len());
+            return (start, end);
+        }
+    }
+    (0, lower.len())
+}
+
+/// Name of the tag starting right after `<` (or `</`).
+fn tag_name(rest: &str) -> &str {
+    let rest = rest.strip_prefix('/').unwrap_or(rest);
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+    &rest[..end]
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end + 1]).map(|ch| (ch, end + 2)));
+        match decoded {
+            Some((ch, len)) => {
+                out.push(ch);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let value = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(value)
+        }
+    }
+}
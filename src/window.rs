@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+#[cfg(target_os = "linux")]
+use serde_json::Value;
+use tokio::process::Command;
+
+/// A window that had keyboard focus.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Window {
+    /// Compositor window id (sway con_id, Hyprland address) or, on macOS,
+    /// the process id of the frontmost application
+    pub id: String,
+    /// Application name (Wayland app_id / X11 class, or process name)
+    pub app: Option<String>,
+    /// Window title, e.g. the page a browser shows
+    pub title: Option<String>,
+}
+
+/// Applications and window titles never read from or typed into
+/// (--block-apps, --block-titles). Both match case-insensitively:
+/// application names whole, titles by substring.
+#[derive(Clone, Debug, Default)]
+pub struct Blocklist {
+    pub apps: Vec<String>,
+    pub titles: Vec<String>,
+}
+
+impl Blocklist {
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty() && self.titles.is_empty()
+    }
+
+    /// The entry blocking `window`, if any.
+    pub fn blocking(&self, window: &Window) -> Option<&str> {
+        let lower = |s: Option<&str>| s.unwrap_or_default().to_lowercase();
+        let app = lower(window.app.as_deref());
+        let title = lower(window.title.as_deref());
+        self.apps
+            .iter()
+            .find(|blocked| !app.is_empty() && blocked.to_lowercase() == app)
+            .or_else(|| {
+                self.titles
+                    .iter()
+                    .find(|blocked| title.contains(&blocked.to_lowercase()))
+            })
+            .map(String::as_str)
+    }
+}
+
+/// The window that has keyboard focus, if it can be found.
+///
+/// - Linux: `swaymsg -t get_tree`, or `hyprctl activewindow -j`
+/// - macOS: `osascript`, the frontmost application process and its front
+///   window
+#[cfg(target_os = "linux")]
+pub async fn focused() -> Option<Window> {
+    if let Some(tree) = query_json("swaymsg", &["-t", "get_tree"]).await {
+        let node = find_focused(&tree)?;
+        return Some(Window {
+            id: node["id"].to_string(),
+            app: node["app_id"]
+                .as_str()
+                .or_else(|| node["window_properties"]["class"].as_str())
+                .map(str::to_string),
+            title: node["name"].as_str().map(str::to_string),
+        });
+    }
+
+    if let Some(window) = query_json("hyprctl", &["activewindow", "-j"]).await {
+        return Some(Window {
+            id: window["address"].as_str()?.to_string(),
+            app: window["class"].as_str().map(str::to_string),
+            title: window["title"].as_str().map(str::to_string),
+        });
+    }
+
+    tracing::debug!("No supported compositor found to query the focused window");
+    None
+}
+
+#[cfg(target_os = "macos")]
+pub async fn focused() -> Option<Window> {
+    let script = r#"tell application "System Events"
+    set p to first application process whose frontmost is true
+    set t to ""
+    try
+        set t to name of front window of p
+    end try
+    return (unix id of p as text) & tab & name of p & tab & t
+end tell"#;
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.trim_end_matches('\n').splitn(3, '\t');
+    let (id, name, title) = (fields.next()?, fields.next()?, fields.next()?);
+    Some(Window {
+        id: id.to_string(),
+        app: Some(name.to_string()).filter(|n| !n.is_empty()),
+        title: Some(title.to_string()).filter(|t| !t.is_empty()),
+    })
+}
+
+/// Give keyboard focus back to `window`.
+///
+/// - Linux: `swaymsg [con_id=…] focus`, or `hyprctl dispatch focuswindow`
+/// - macOS: `osascript`, raising the application process
+#[cfg(target_os = "linux")]
+pub async fn focus(window: &Window) -> Result<()> {
+    // Hyprland addresses are hex, sway ids are plain numbers
+    let (program, args) = if window.id.starts_with("0x") {
+        (
+            "hyprctl",
+            vec![
+                "dispatch".to_string(),
+                "focuswindow".to_string(),
+                format!("address:{}", window.id),
+            ],
+        )
+    } else {
+        ("swaymsg", vec![format!("[con_id={}] focus", window.id)])
+    };
+
+    let output = Command::new(program)
+        .args(&args)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{program} failed to focus window: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub async fn focus(window: &Window) -> Result<()> {
+    let script = format!(
+        r#"tell application "System Events" to set frontmost of (first application process whose unix id is {}) to true"#,
+        window.id
+    );
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(&script)
+        .output()
+        .await
+        .context("Failed to run osascript")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "osascript failed to focus window: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Run a compositor IPC command and parse its JSON output.
+#[cfg(target_os = "linux")]
+async fn query_json(program: &str, args: &[&str]) -> Option<Value> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// Depth-first search of a sway tree for the focused node.
+#[cfg(target_os = "linux")]
+fn find_focused(node: &Value) -> Option<&Value> {
+    if node["focused"].as_bool() == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(find_focused)
+}